@@ -1,11 +1,38 @@
-use std::path::Path;
+use std::{fs, path::Path};
+
+const SCHEMA: &str = "src/flatbuffers/ztoc.fbs";
+// Bindings generated from the schema, checked in for environments without flatc.
+const CHECKED_IN: &str = "src/flatbuffers/ztoc_generated.rs";
+const OUT_DIR: &str = "target/flatbuffers/";
 
 fn main() {
-    println!("cargo:rerun-if-changed=src/flatbuffers/ztoc.fbs");
-    flatc_rust::run(flatc_rust::Args {
-        inputs: &[Path::new("src/flatbuffers/ztoc.fbs")],
-        out_dir: Path::new("target/flatbuffers/"),
-        ..Default::default()
-    })
-    .expect("flatc");
+    println!("cargo:rerun-if-changed={}", SCHEMA);
+    println!("cargo:rerun-if-changed={}", CHECKED_IN);
+
+    let generated = Path::new(OUT_DIR).join("ztoc_generated.rs");
+    let flatc = flatc_rust::Flatc::from_env_path();
+    if flatc.check().is_ok() {
+        flatc
+            .run(flatc_rust::Args {
+                inputs: &[Path::new(SCHEMA)],
+                out_dir: Path::new(OUT_DIR),
+                ..Default::default()
+            })
+            .expect("flatc");
+        if fs::read(&generated).ok() != fs::read(CHECKED_IN).ok() {
+            println!(
+                "cargo:warning={} is out of date with {}, copy {} over it",
+                CHECKED_IN,
+                SCHEMA,
+                generated.display()
+            );
+        }
+    } else {
+        println!(
+            "cargo:warning=flatc not found, using the checked-in {}",
+            CHECKED_IN
+        );
+        fs::create_dir_all(OUT_DIR).expect("failed to create flatbuffers output directory");
+        fs::copy(CHECKED_IN, &generated).expect("failed to copy checked-in flatbuffers bindings");
+    }
 }
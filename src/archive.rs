@@ -0,0 +1,101 @@
+use std::io::Read;
+
+use tar::Archive;
+
+use crate::{
+    error::ZtocError,
+    zinfo::{GzipZInfoDecompressor, ZInfo},
+    ztoc::{FileMetadata, DEFAULT_SPAN_SIZE},
+};
+
+/// A gzip-compressed tar archive whose entries can be read while the zinfo index is built.
+///
+/// Entry contents are read through the same decompressor that records checkpoints, so reading
+/// (or skipping) entries always advances the index along with them.
+pub struct ZtocArchive<R: Read> {
+    archive: Archive<GzipZInfoDecompressor<R>>,
+}
+
+impl<R: Read> ZtocArchive<R> {
+    pub fn new(reader: R) -> Result<Self, ZtocError> {
+        Ok(Self::from_decompressor(GzipZInfoDecompressor::new(
+            reader,
+            DEFAULT_SPAN_SIZE,
+        )?))
+    }
+
+    /// Wraps an already configured decompressor.
+    pub fn from_decompressor(decompressor: GzipZInfoDecompressor<R>) -> Self {
+        let mut archive = Archive::new(decompressor);
+        archive.set_unpack_xattrs(true);
+        archive.set_preserve_permissions(true);
+        ZtocArchive { archive }
+    }
+
+    /// Returns an iterator over the entries of the archive, yielding each entry's metadata along
+    /// with a reader for its decompressed contents. Entries must be consumed in order.
+    pub fn entries(&mut self) -> Result<ZtocEntries<'_, R>, ZtocError> {
+        Ok(ZtocEntries {
+            entries: self.archive.entries()?,
+        })
+    }
+
+    /// Reads the rest of the archive and returns the completed zinfo.
+    pub fn into_zinfo(self) -> Result<ZInfo, ZtocError> {
+        Ok(self.archive.into_inner().finish()?)
+    }
+}
+
+/// An iterator over the entries of a [`ZtocArchive`].
+pub struct ZtocEntries<'a, R: 'a + Read> {
+    entries: tar::Entries<'a, GzipZInfoDecompressor<R>>,
+}
+
+impl<'a, R: Read> Iterator for ZtocEntries<'a, R> {
+    type Item = Result<(FileMetadata, tar::Entry<'a, GzipZInfoDecompressor<R>>), ZtocError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        Some((|| {
+            let mut entry = entry?;
+            let metadata = FileMetadata::try_from(&mut entry)?;
+            Ok((metadata, entry))
+        })())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_entries_and_zinfo() {
+        let compressed = include_bytes!("testdata/test.tar.gz");
+        let mut expected = tar::Archive::new(&include_bytes!("testdata/test.tar")[..]);
+        let mut expected = expected.entries().unwrap();
+
+        let mut archive = ZtocArchive::new(&compressed[..]).unwrap();
+        let mut count = 0;
+        for entry in archive.entries().unwrap() {
+            let (metadata, mut reader) = entry.unwrap();
+            let mut want = expected.next().unwrap().unwrap();
+            assert_eq!(metadata.name, want.path().unwrap());
+            assert_eq!(metadata.uncompressed_offset.0, want.raw_file_position());
+
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            let mut want_data = Vec::new();
+            want.read_to_end(&mut want_data).unwrap();
+            assert_eq!(data, want_data);
+            assert_eq!(data.len() as u64, metadata.uncompressed_size.0);
+            count += 1;
+        }
+        assert!(expected.next().is_none());
+        assert!(count > 0);
+
+        let zinfo = archive.into_zinfo().unwrap();
+        assert_eq!(zinfo.total_in, compressed.len());
+        assert_eq!(zinfo.total_out, include_bytes!("testdata/test.tar").len());
+        assert!(!zinfo.checkpoints.is_empty());
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    path::PathBuf,
+};
+
+use chrono::DateTime;
+use flatbuffers::{
+    ForwardsUOffset, InvalidFlatbuffer, Vector, Verifiable, Verifier, VerifierOptions,
+};
+use tar::EntryType;
+
+use crate::{
+    encode::string_to_entry,
+    error::ZtocError,
+    zinfo::{DigestAlgorithm, SpanDigestKind},
+    ztoc::{self, plain_checkpoints, BuildMetrics, CompressionOffset, Toc, ZToc},
+    ztoc_flatbuffers::{
+        self,
+        ztoc::{root_as_ztoc_unchecked, Annotation, CompressionInfo, FileMetadata, Ztoc, TOC},
+    },
+};
+
+/// Decodes an encoded ztoc back into a [`ZToc`].
+///
+/// The checkpoints blob is converted back into the layout `CompressionInfo` holds in memory, so
+/// ztocs encoded with compressed windows, varint offsets or a sentinel decode the same as plain
+/// ones. Every entry must have a type and a modification time, so ztocs that were encoded
+/// without those fields can't be decoded. Information the encoding doesn't keep is lost:
+/// contiguous files decode as regular files and GNU dumpdirs as directories, and there is no
+/// compressed layer digest or build metrics.
+pub fn decode_ztoc(buf: &[u8]) -> Result<ZToc, ZtocError> {
+    let invalid = |msg: String| ZtocError::InvalidZtoc(msg);
+    let ztoc = ztoc_flatbuffers::ztoc::root_as_ztoc(buf).map_err(|err| invalid(err.to_string()))?;
+    let size = |name: &str, size: i64| {
+        u64::try_from(size)
+            .map(CompressionOffset)
+            .map_err(|_| invalid(format!("negative {} {}", name, size)))
+    };
+
+    let metadata = ztoc
+        .toc()
+        .and_then(|toc| toc.metadata())
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, entry)| {
+            decode_entry(entry).map_err(|msg| invalid(format!("TOC entry {}: {}", index, msg)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let info = ztoc
+        .compression_info()
+        .ok_or_else(|| invalid("missing compression info".into()))?;
+    let strings = |strings: Option<Vector<'_, ForwardsUOffset<&str>>>| {
+        strings
+            .into_iter()
+            .flatten()
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+    let mut extra_span_digests = BTreeMap::new();
+    for list in info.extra_span_digests().into_iter().flatten() {
+        let name = list.algorithm().unwrap_or_default();
+        let algorithm = DigestAlgorithm::from_name(name)
+            .ok_or_else(|| invalid(format!("unknown span digest algorithm {:?}", name)))?;
+        extra_span_digests.insert(algorithm, strings(list.digests()));
+    }
+    let compression_info = ztoc::CompressionInfo {
+        max_span_id: usize::try_from(info.max_span_id())
+            .map_err(|_| invalid(format!("negative max span id {}", info.max_span_id())))?,
+        span_digests: strings(info.span_digests()),
+        span_digest_kind: match info.span_digest_kind() {
+            ztoc_flatbuffers::ztoc::SpanDigestKind::Uncompressed => SpanDigestKind::Uncompressed,
+            _ => SpanDigestKind::Compressed,
+        },
+        extra_span_digests,
+        checkpoints: plain_checkpoints(info.checkpoints().map_or(&[], |blob| blob.bytes()))?,
+    };
+
+    Ok(ZToc {
+        version: ztoc.version().unwrap_or_default().to_string(),
+        build_tool_identifier: ztoc.build_tool_identifier().unwrap_or_default().to_string(),
+        compressed_achrive_size: size("compressed archive size", ztoc.compressed_archive_size())?,
+        uncompressed_archive_size: size(
+            "uncompressed archive size",
+            ztoc.uncompressed_archive_size(),
+        )?,
+        toc: Toc { metadata },
+        compression_info,
+        annotations: decode_annotations(&ztoc),
+        compressed_digest: None,
+        metrics: BuildMetrics::default(),
+    })
+}
+
+/// Decodes a single TOC entry, see [`decode_ztoc`].
+fn decode_entry(entry: FileMetadata<'_>) -> Result<ztoc::FileMetadata, String> {
+    let name = entry.type_().ok_or("missing type")?;
+    let r#type = string_to_entry(name).ok_or_else(|| format!("unknown type {:?}", name))?;
+    let mod_time = entry.mod_time().ok_or("missing modification time")?;
+    let mod_time = DateTime::parse_from_rfc3339(mod_time)
+        .map_err(|err| format!("invalid modification time {:?}: {}", mod_time, err))?
+        .naive_utc();
+    let is_device = matches!(r#type, EntryType::Char | EntryType::Block);
+    let int = |name: &str, value: i64| {
+        u64::try_from(value).map_err(|_| format!("negative {} {}", name, value))
+    };
+    Ok(ztoc::FileMetadata {
+        name: PathBuf::from(entry.name().ok_or("missing name")?),
+        r#type,
+        uncompressed_offset: CompressionOffset(int(
+            "uncompressed offset",
+            entry.uncompressed_offset(),
+        )?),
+        uncompressed_size: CompressionOffset(int("uncompressed size", entry.uncompressed_size())?),
+        link_name: matches!(r#type, EntryType::Link | EntryType::Symlink)
+            .then(|| PathBuf::from(entry.linkname().unwrap_or_default())),
+        mode: u32::try_from(entry.mode()).map_err(|_| format!("invalid mode {}", entry.mode()))?,
+        uid: entry.uid().into(),
+        gid: entry.gid().into(),
+        uname: entry.uname().map(String::from),
+        gname: entry.gname().map(String::from),
+        mod_time,
+        dev_major: is_device.then_some(entry.devmajor() as u32),
+        dev_minor: is_device.then_some(entry.devminor() as u32),
+        x_attrs: entry
+            .xattrs()
+            .into_iter()
+            .flatten()
+            .filter_map(|xattr| {
+                Some((
+                    xattr.key()?.to_string(),
+                    xattr.value().unwrap_or_default().to_string(),
+                ))
+            })
+            .collect(),
+        compressed_span_offset: Some(int(
+            "compressed span offset",
+            entry.compressed_span_offset(),
+        )?)
+        .filter(|&offset| offset != 0),
+        sparse: entry
+            .sparse()
+            .into_iter()
+            .flatten()
+            .map(|segment| {
+                Ok((
+                    int("sparse segment offset", segment.offset())?,
+                    int("sparse segment length", segment.length())?,
+                ))
+            })
+            .collect::<Result<_, String>>()?,
+    })
+}
+
+/// Returns the annotations of a decoded ztoc. Ztocs without annotations yield an empty map.
+pub fn decode_annotations(ztoc: &Ztoc<'_>) -> HashMap<String, String> {
+    ztoc.annotations()
+        .into_iter()
+        .flatten()
+        .filter_map(|annotation| {
+            Some((
+                annotation.key()?.to_string(),
+                annotation.value().unwrap_or_default().to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Reads the uncompressed archive size of an encoded ztoc. Only the root table and that field
+/// are verified and read, so this is cheap even for a huge ztoc.
+pub fn peek_uncompressed_size(buf: &[u8]) -> Result<u64, ZtocError> {
+    let opts = VerifierOptions::default();
+    <ForwardsUOffset<ZtocSize>>::run_verifier(&mut Verifier::new(&opts, buf), 0)
+        .map_err(|err| ZtocError::InvalidZtoc(err.to_string()))?;
+    // SAFETY: the root table and the accessed field are verified by `ZtocSize`.
+    let size = unsafe { root_as_ztoc_unchecked(buf) }.uncompressed_archive_size();
+    u64::try_from(size)
+        .map_err(|_| ZtocError::InvalidZtoc(format!("negative uncompressed archive size {}", size)))
+}
+
+/// Iterates the TOC entries of an encoded ztoc, verifying each entry only when it is reached.
+///
+/// [`root_as_ztoc`](crate::ztoc_flatbuffers::ztoc::root_as_ztoc) verifies the whole buffer
+/// before returning, which touches every entry of the TOC. For a huge TOC in a memory-mapped
+/// file, that faults in the whole file before the first entry can be used. This instead verifies
+/// the root table, the compression info and the bounds of the TOC's entry offsets upfront, and
+/// each entry's table, strings and xattrs as the iterator yields it. Entries that are never
+/// reached are never read.
+///
+/// Lazy verification is as safe as full verification for the entries that are yielded, which is
+/// all that is ever accessed through them, given two assumptions:
+///
+/// - `buf` doesn't change while the iterator or any yielded entry is alive. This matters for
+///   memory maps, where another process could truncate or rewrite the file.
+/// - A malformed entry is only reported when it is reached, so consumers that act on entries
+///   as they go must tolerate an error partway through.
+///
+/// The whole buffer still has to be available: a flatbuffer's root is written last, so entries
+/// can't be located before the end of the buffer has arrived.
+pub fn lazy_toc_entries(buf: &[u8]) -> Result<LazyTocEntries<'_>, ZtocError> {
+    let invalid = |err: InvalidFlatbuffer| ZtocError::InvalidZtoc(err.to_string());
+    let opts = VerifierOptions::default();
+    <ForwardsUOffset<ShallowZtoc>>::run_verifier(&mut Verifier::new(&opts, buf), 0)
+        .map_err(invalid)?;
+    // SAFETY: the root table and every field accessed below are verified by `ShallowZtoc`.
+    let ztoc = unsafe { root_as_ztoc_unchecked(buf) };
+    let entries = ztoc.toc().and_then(|toc| toc.metadata());
+    let offsets = entries.map_or(0..0, |entries| {
+        let start = entries.bytes().as_ptr() as usize - buf.as_ptr() as usize;
+        start..start + entries.bytes().len()
+    });
+    Ok(LazyTocEntries {
+        buf,
+        entries,
+        offsets,
+        next: 0,
+    })
+}
+
+/// An iterator over the entries of a ztoc's TOC, created by [`lazy_toc_entries`].
+pub struct LazyTocEntries<'a> {
+    buf: &'a [u8],
+    entries: Option<Vector<'a, ForwardsUOffset<FileMetadata<'a>>>>,
+    /// Where the entries' offsets lie in `buf`.
+    offsets: Range<usize>,
+    next: usize,
+}
+
+impl<'a> Iterator for LazyTocEntries<'a> {
+    type Item = Result<FileMetadata<'a>, ZtocError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entries = self.entries?;
+        if self.next == entries.len() {
+            return None;
+        }
+        let index = self.next;
+        self.next += 1;
+
+        // Each entry is verified on its own, so the verifier's limits on table count and
+        // apparent size apply per entry rather than to the whole TOC.
+        let opts = VerifierOptions::default();
+        let pos = self.offsets.start + index * flatbuffers::SIZE_UOFFSET;
+        Some(
+            <ForwardsUOffset<FileMetadata>>::run_verifier(&mut Verifier::new(&opts, self.buf), pos)
+                .map(|()| entries.get(index))
+                .map_err(|err| ZtocError::InvalidZtoc(format!("TOC entry {}: {}", index, err))),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.entries.map_or(0, |entries| entries.len() - self.next);
+        (remaining, Some(remaining))
+    }
+}
+
+/// Verifies only the `uncompressed_archive_size` field of a `Ztoc` table.
+struct ZtocSize;
+
+impl Verifiable for ZtocSize {
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<i64>(
+                "uncompressed_archive_size",
+                Ztoc::VT_UNCOMPRESSED_ARCHIVE_SIZE,
+                false,
+            )?
+            .finish();
+        Ok(())
+    }
+}
+
+/// Verifies a `Ztoc` table like its generated verifier, except that the TOC's entries are
+/// only checked to be in bounds.
+struct ShallowZtoc;
+
+impl Verifiable for ShallowZtoc {
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<ForwardsUOffset<&str>>("version", Ztoc::VT_VERSION, false)?
+            .visit_field::<ForwardsUOffset<&str>>(
+                "build_tool_identifier",
+                Ztoc::VT_BUILD_TOOL_IDENTIFIER,
+                false,
+            )?
+            .visit_field::<i64>(
+                "compressed_archive_size",
+                Ztoc::VT_COMPRESSED_ARCHIVE_SIZE,
+                false,
+            )?
+            .visit_field::<i64>(
+                "uncompressed_archive_size",
+                Ztoc::VT_UNCOMPRESSED_ARCHIVE_SIZE,
+                false,
+            )?
+            .visit_field::<ForwardsUOffset<ShallowToc>>("toc", Ztoc::VT_TOC, false)?
+            .visit_field::<ForwardsUOffset<CompressionInfo>>(
+                "compression_info",
+                Ztoc::VT_COMPRESSION_INFO,
+                false,
+            )?
+            .visit_field::<ForwardsUOffset<Vector<'_, ForwardsUOffset<Annotation>>>>(
+                "annotations",
+                Ztoc::VT_ANNOTATIONS,
+                false,
+            )?
+            .finish();
+        Ok(())
+    }
+}
+
+/// Verifies a `TOC` table, checking only that its entries' offsets are in bounds.
+struct ShallowToc;
+
+impl Verifiable for ShallowToc {
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<ForwardsUOffset<Vector<'_, u32>>>("metadata", TOC::VT_METADATA, false)?
+            .finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{encode::encode_ztoc, testutil, ztoc::ZToc, ztoc_flatbuffers};
+
+    use super::*;
+
+    #[test]
+    fn test_annotations_round_trip() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let ztoc = ZToc::builder()
+            .annotation("org.example.source", "registry.example.com/app:1")
+            .annotation("org.example.build-id", "42")
+            .build(layer)
+            .unwrap();
+
+        let encoded = encode_ztoc(&ztoc).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        assert_eq!(decode_annotations(&decoded), ztoc.annotations);
+        assert_eq!(decode_annotations(&decoded)["org.example.build-id"], "42");
+
+        let encoded = encode_ztoc(&ZToc::new(layer).unwrap()).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        assert!(decoded.annotations().is_none());
+        assert!(decode_annotations(&decoded).is_empty());
+    }
+
+    #[test]
+    fn test_decode_ztoc() {
+        let layer = &include_bytes!("testdata/layer.tar.gz")[..];
+        let ztoc = ZToc::builder()
+            .compressed_span_offsets(true)
+            .annotation("org.example.key", "value")
+            .build(layer)
+            .unwrap();
+        let encoded = encode_ztoc(&ztoc).unwrap();
+        let decoded = decode_ztoc(&encoded).unwrap();
+
+        assert_eq!(decoded.version, ztoc.version);
+        assert_eq!(decoded.build_tool_identifier, ztoc.build_tool_identifier);
+        assert_eq!(
+            decoded.compressed_achrive_size.0,
+            ztoc.compressed_achrive_size.0
+        );
+        assert_eq!(
+            decoded.uncompressed_archive_size.0,
+            ztoc.uncompressed_archive_size.0
+        );
+        assert_eq!(decoded.annotations, ztoc.annotations);
+        assert_eq!(decoded.toc.metadata.len(), ztoc.toc.metadata.len());
+        for (decoded, entry) in decoded.toc.metadata.iter().zip(&ztoc.toc.metadata) {
+            assert_eq!(decoded.name, entry.name);
+            assert_eq!(decoded.r#type, entry.r#type);
+            assert_eq!(decoded.uncompressed_offset.0, entry.uncompressed_offset.0);
+            assert_eq!(decoded.uncompressed_size.0, entry.uncompressed_size.0);
+            assert_eq!(decoded.link_name, entry.link_name);
+            assert_eq!(decoded.mode, entry.mode);
+            assert_eq!((decoded.uid, decoded.gid), (entry.uid, entry.gid));
+            assert_eq!(decoded.mod_time, entry.mod_time);
+            assert_eq!(decoded.x_attrs, entry.x_attrs);
+            assert_eq!(decoded.compressed_span_offset, entry.compressed_span_offset);
+        }
+        let (decoded, info) = (&decoded.compression_info, &ztoc.compression_info);
+        assert_eq!(decoded.checkpoints, info.checkpoints);
+        assert_eq!(decoded.span_digests, info.span_digests);
+        assert_eq!(decoded.max_span_id, info.max_span_id);
+
+        // Other checkpoint layouts decode to the same in-memory layout.
+        let options = crate::encode::EncodeOptions {
+            compress_windows: true,
+            varint_offsets: true,
+            stream_end_sentinel: true,
+            ..Default::default()
+        };
+        let encoded = crate::encode::encode_ztoc_with_options(&ztoc, &options).unwrap();
+        let decoded = decode_ztoc(&encoded).unwrap();
+        assert_eq!(decoded.compression_info.checkpoints, info.checkpoints);
+    }
+
+    #[test]
+    fn test_peek_uncompressed_size() {
+        let encoded = include_bytes!("testdata/expected");
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(encoded).unwrap();
+        let size = peek_uncompressed_size(encoded).unwrap();
+        assert_eq!(size, decoded.uncompressed_archive_size() as u64);
+        assert_eq!(size, 75_156_480);
+
+        assert!(peek_uncompressed_size(&encoded[..2]).is_err());
+    }
+
+    #[test]
+    fn test_lazy_toc_entries() {
+        let names = (0..20_000)
+            .map(|i| format!("dir/file-{}", i))
+            .collect::<Vec<_>>();
+        let files = names
+            .iter()
+            .map(|name| (name.as_str(), &b""[..]))
+            .collect::<Vec<_>>();
+        let layer = testutil::gzip(&testutil::tar_of(&files));
+        let encoded = encode_ztoc(&ZToc::new(&layer[..]).unwrap()).unwrap();
+
+        let entries = lazy_toc_entries(&encoded).unwrap();
+        assert_eq!(entries.size_hint(), (names.len(), Some(names.len())));
+        let mut count = 0;
+        for (entry, name) in entries.zip(&names) {
+            assert_eq!(entry.unwrap().name(), Some(name.as_str()));
+            count += 1;
+        }
+        assert_eq!(count, names.len());
+
+        // Point the last entry outside the buffer. Full verification rejects the whole ztoc,
+        // lazy verification only the last entry.
+        let offsets = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded)
+            .unwrap()
+            .toc()
+            .unwrap()
+            .metadata()
+            .unwrap()
+            .bytes()
+            .as_ptr() as usize
+            - encoded.as_ptr() as usize;
+        let mut corrupted = encoded.clone();
+        let last = offsets + (names.len() - 1) * 4;
+        corrupted[last..last + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(ztoc_flatbuffers::ztoc::root_as_ztoc(&corrupted).is_err());
+
+        let mut entries = lazy_toc_entries(&corrupted).unwrap();
+        for _ in 0..names.len() - 1 {
+            entries.next().unwrap().unwrap();
+        }
+        let err = entries.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("TOC entry 19999"), "{}", err);
+        assert!(entries.next().is_none());
+    }
+}
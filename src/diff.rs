@@ -0,0 +1,135 @@
+//! Compares two ztocs of the same layer, e.g. to catch nondeterminism between two builds.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::ztoc::{parse_checkpoints, CompressionInfo, FileMetadata, ZToc};
+
+/// What changed from one ztoc to another, as returned by [`diff`]. Paths are sorted.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ZtocDiff {
+    /// Entries only in the second ztoc.
+    pub added: Vec<PathBuf>,
+    /// Entries only in the first ztoc.
+    pub removed: Vec<PathBuf>,
+    /// Entries in both whose size, mode, modification time or offset differ.
+    pub modified: Vec<PathBuf>,
+    /// Whether the checkpoints are at different offsets, or there is a different number of them.
+    pub checkpoints_changed: bool,
+}
+
+impl ZtocDiff {
+    /// Returns whether the ztocs agree on every entry and checkpoint.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+            && !self.checkpoints_changed
+    }
+}
+
+/// Compares the entries and checkpoints of `a` and `b`. Entries are matched by name; if a name
+/// appears more than once, the last entry counts, as it is the one extracted.
+pub fn diff(a: &ZToc, b: &ZToc) -> ZtocDiff {
+    let (a_entries, b_entries) = (entries_by_name(a), entries_by_name(b));
+
+    let mut diff = ZtocDiff::default();
+    for (name, a_entry) in &a_entries {
+        match b_entries.get(name) {
+            None => diff.removed.push(name.to_path_buf()),
+            Some(b_entry) if !same_entry(a_entry, b_entry) => {
+                diff.modified.push(name.to_path_buf())
+            }
+            Some(_) => {}
+        }
+    }
+    diff.added = b_entries
+        .keys()
+        .filter(|name| !a_entries.contains_key(*name))
+        .map(|name| name.to_path_buf())
+        .collect();
+    diff.checkpoints_changed = match (
+        checkpoint_layout(&a.compression_info),
+        checkpoint_layout(&b.compression_info),
+    ) {
+        (Some(a_layout), Some(b_layout)) => a_layout != b_layout,
+        // Blobs that can't be parsed can still be told apart byte for byte.
+        _ => a.compression_info.checkpoints != b.compression_info.checkpoints,
+    };
+    diff
+}
+
+fn entries_by_name(ztoc: &ZToc) -> BTreeMap<&Path, &FileMetadata> {
+    ztoc.toc
+        .metadata
+        .iter()
+        .map(|entry| (entry.name.as_path(), entry))
+        .collect()
+}
+
+fn same_entry(a: &FileMetadata, b: &FileMetadata) -> bool {
+    a.uncompressed_size.0 == b.uncompressed_size.0
+        && a.mode == b.mode
+        && a.mod_time == b.mod_time
+        && a.uncompressed_offset.0 == b.uncompressed_offset.0
+}
+
+/// The `(in, out, bits)` offsets of each checkpoint, or `None` if the blob can't be parsed.
+fn checkpoint_layout(info: &CompressionInfo) -> Option<Vec<(usize, usize, u8)>> {
+    let checkpoints = parse_checkpoints(&info.checkpoints).ok()?;
+    Some(
+        checkpoints
+            .iter()
+            .map(|checkpoint| (checkpoint.r#in, checkpoint.out, checkpoint.bits))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::{testutil, ztoc::ZToc};
+
+    use super::{diff, ZtocDiff};
+
+    #[test]
+    fn test_diff() {
+        let tar = testutil::tar_of(&[
+            ("first", &testutil::random_bytes(1 << 16, 1)),
+            ("second", b"hello"),
+        ]);
+        let layer = testutil::gzip(&tar);
+        let build = |span_size| {
+            ZToc::builder()
+                .span_size(span_size)
+                .build(&layer[..])
+                .unwrap()
+        };
+        let a = build(1 << 14);
+        let mut b = build(1 << 14);
+        assert!(diff(&a, &b).is_empty());
+
+        let second = &mut b.toc.metadata[1];
+        assert_eq!(second.name, PathBuf::from("second"));
+        second.mode ^= 0o111;
+        assert_eq!(
+            diff(&a, &b),
+            ZtocDiff {
+                modified: vec![PathBuf::from("second")],
+                ..Default::default()
+            }
+        );
+
+        // The same entries, checkpointed differently.
+        b.toc.metadata.remove(0);
+        let c = build(1 << 20);
+        let changed = diff(&b, &c);
+        assert_eq!(changed.added, [PathBuf::from("first")]);
+        assert_eq!(changed.modified, [PathBuf::from("second")]);
+        assert!(changed.removed.is_empty());
+        assert!(changed.checkpoints_changed);
+    }
+}
@@ -1,145 +1,1194 @@
-use chrono::Utc;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use flatbuffers::{FlatBufferBuilder, ForwardsUOffset, Vector, WIPOffset};
 use tar::EntryType;
 
-use crate::ztoc_flatbuffers::ztoc::{
-    CompressionAlgorithm, CompressionInfo, CompressionInfoArgs, FileMetadata, FileMetadataArgs,
-    TOCArgs, Xattr, XattrArgs, Ztoc, ZtocArgs, TOC,
+use crate::{
+    error::ZtocError,
+    ztoc::{
+        compress_checkpoint_windows, varint_checkpoint_offsets, CompressionArtifact, StreamEnd,
+        Toc, TocArtifact, ZToc,
+    },
+    ztoc_flatbuffers::ztoc::{
+        Annotation, AnnotationArgs, CompressionAlgorithm, CompressionInfo, CompressionInfoArgs,
+        FileMetadata, FileMetadataArgs, SpanDigestKind, SpanDigestList, SpanDigestListArgs,
+        SparseSegment, SparseSegmentArgs, TOCArgs, Xattr, XattrArgs, Ztoc, ZtocArgs, TOC,
+    },
 };
 
-fn entry_to_string(entry: &EntryType) -> &'static str {
-    match entry {
-        EntryType::Regular => "reg",
+/// Returns the name of an entry type in a ztoc, or `None` for a type the format has no name for.
+pub(crate) fn entry_to_string(entry: &EntryType) -> Option<&'static str> {
+    let name = match entry {
+        // Contiguous files are regular files with an allocation hint, so most tools read them
+        // the same way. `FileMetadata::type` still records the distinction.
+        EntryType::Regular | EntryType::Continuous => "reg",
+        // Sparse files expand to regular files, with their holes recorded in `sparse`.
+        EntryType::GNUSparse => "reg",
         EntryType::Link => "hardlink",
         EntryType::Symlink => "symlink",
         EntryType::Char => "char",
         EntryType::Block => "block",
         EntryType::Directory => "dir",
         EntryType::Fifo => "fifo",
-        _ => unimplemented!("Unexpected entry type {:?}", entry),
+        // GNU incremental dumps write directories as dumpdir ('D') entries, whose data lists the
+        // directory's contents at dump time.
+        entry if entry.as_byte() == b'D' => "dir",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The inverse of [`entry_to_string`]. Every name maps back to the most common type written
+/// with it, so contiguous and sparse files come back as regular files and GNU dumpdirs as
+/// directories. Unknown names return `None`.
+pub fn string_to_entry(name: &str) -> Option<EntryType> {
+    Some(match name {
+        "reg" => EntryType::Regular,
+        "hardlink" => EntryType::Link,
+        "symlink" => EntryType::Symlink,
+        "char" => EntryType::Char,
+        "block" => EntryType::Block,
+        "dir" => EntryType::Directory,
+        "fifo" => EntryType::Fifo,
+        _ => return None,
+    })
+}
+
+/// Returns the ztoc name of the type of the entry at `path`, failing with
+/// [`ZtocError::UnsupportedEntryType`] if it has none.
+pub(crate) fn entry_type_name(path: &Path, entry: EntryType) -> Result<&'static str, ZtocError> {
+    entry_to_string(&entry).ok_or_else(|| ZtocError::UnsupportedEntryType {
+        path: path.to_path_buf(),
+        entry_type: entry,
+    })
+}
+
+/// Options controlling how a ztoc is encoded. The defaults match the soci-snapshotter.
+#[derive(Debug, Clone, Default)]
+pub struct EncodeOptions {
+    /// Omit `linkname` for entries without a link target instead of writing an empty string.
+    /// Links to an empty target are still written as an empty string, so readers can tell the
+    /// two apart. By default an empty `linkname` means "no link target", as in soci.
+    pub omit_absent_link_names: bool,
+    /// The optional TOC fields to encode, or `None` to encode all of them. Names, offsets and
+    /// sizes are always encoded. Left out fields are absent from the flatbuffer, so readers of a
+    /// projected ztoc must tolerate missing strings and zero scalars.
+    pub toc_fields: Option<Vec<TocField>>,
+    /// Append an end-of-stream sentinel holding the total compressed and uncompressed sizes to
+    /// the checkpoints blob, see [`crate::ztoc::parse_stream_end`]. Readers that don't know about
+    /// it must tolerate trailing bytes after the declared checkpoints.
+    pub stream_end_sentinel: bool,
+    /// Store each checkpoint window zlib-compressed in the checkpoints blob, see
+    /// [`crate::ztoc::compress_checkpoint_windows`]. This typically shrinks the ztoc severalfold,
+    /// but soci and other readers that don't know about it can't read the checkpoints.
+    pub compress_windows: bool,
+    /// Store each checkpoint's offsets as LEB128 varints in the checkpoints blob, see
+    /// [`crate::ztoc::varint_checkpoint_offsets`]. This saves a few bytes per checkpoint, which
+    /// only matters next to compressed windows, and readers must know about it too.
+    pub varint_offsets: bool,
+    /// Write TOC entries ordered by `uncompressed_offset`, ties broken by name, instead of in
+    /// the order of [`Toc::metadata`], for readers that binary search the TOC. Entries without
+    /// data, such as directories and hardlinks, have the offset just past their header, so they
+    /// sort where they appear in the archive.
+    pub sort_by_offset: bool,
+    /// Write modification times before the Unix epoch as the epoch itself,
+    /// `1970-01-01T00:00:00+00:00`, for consumers that reject pre-1970 dates. By default they
+    /// are written as they are.
+    pub clamp_pre_epoch_mtimes: bool,
+}
+
+/// An optional field of each TOC entry, see [`EncodeOptions::toc_fields`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TocField {
+    Type,
+    LinkName,
+    Mode,
+    Uid,
+    Gid,
+    Uname,
+    Gname,
+    ModTime,
+    DevMajor,
+    DevMinor,
+    Xattrs,
+    CompressedSpanOffset,
+    Sparse,
+}
+
+impl EncodeOptions {
+    fn includes(&self, field: TocField) -> bool {
+        self.toc_fields
+            .as_ref()
+            .is_none_or(|fields| fields.contains(&field))
     }
 }
 
-pub fn encode_ztoc(ztoc: &crate::ztoc::ZToc) -> Vec<u8> {
+pub fn encode_ztoc(ztoc: &ZToc) -> Result<Vec<u8>, ZtocError> {
+    let mut encoded = Vec::new();
+    encode_ztoc_to(ztoc, &mut encoded)?;
+    Ok(encoded)
+}
+
+/// Encodes `ztoc` with the default options and writes it to `out`, without copying the finished
+/// flatbuffer first. Encoding errors are returned as an [`io::Error`] wrapping the [`ZtocError`].
+pub fn encode_ztoc_to<W: Write>(ztoc: &ZToc, mut out: W) -> io::Result<()> {
+    let builder = build_ztoc(ztoc, &EncodeOptions::default())?;
+    out.write_all(builder.finished_data())
+}
+
+/// Builds a ztoc for a gzip-compressed layer with the default options and encodes it, returning
+/// both the model and the encoded bytes.
+pub fn index_layer<R: Read>(reader: R) -> Result<(ZToc, Vec<u8>), ZtocError> {
+    let ztoc = ZToc::new(reader)?;
+    let encoded = encode_ztoc(&ztoc)?;
+    Ok((ztoc, encoded))
+}
+
+pub fn encode_ztoc_with_options(
+    ztoc: &ZToc,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, ZtocError> {
+    Ok(build_ztoc(ztoc, options)?.finished_data().to_vec())
+}
+
+/// Builds the finished ztoc flatbuffer.
+fn build_ztoc<'a>(
+    ztoc: &ZToc,
+    options: &EncodeOptions,
+) -> Result<FlatBufferBuilder<'a>, ZtocError> {
     let mut builder = flatbuffers::FlatBufferBuilder::with_capacity(1024);
     let version = builder.create_string(&ztoc.version);
     let build_tool_identifier = builder.create_string(&ztoc.build_tool_identifier);
+    let toc = encode_toc(&mut builder, &ztoc.toc, options)?;
+    let compression_info = encode_compression_info(
+        &mut builder,
+        &ztoc.compression_info,
+        options,
+        StreamEnd {
+            r#in: ztoc.compressed_achrive_size.0,
+            out: ztoc.uncompressed_archive_size.0,
+        },
+    )?;
+    let annotations = encode_annotations(&mut builder, &ztoc.annotations);
 
-    let mut metadata = Vec::with_capacity(ztoc.toc.metadata.len());
-    for entry in &ztoc.toc.metadata {
-        let name =
-            builder.create_string(entry.name.to_str().expect("unexpected non-UTF 8 encoding"));
-        let linkname = builder.create_string(
-            entry
-                .link_name
-                .as_ref()
-                .map(|link| link.to_str().expect("unexpected non-UTF 8 encoding"))
-                .unwrap_or_default(),
-        );
+    let ztoc = Ztoc::create(
+        &mut builder,
+        &ZtocArgs {
+            version: Some(version),
+            build_tool_identifier: Some(build_tool_identifier),
+            compressed_archive_size: ztoc.compressed_achrive_size.0 as i64,
+            uncompressed_archive_size: ztoc.uncompressed_archive_size.0 as i64,
+            toc: Some(toc),
+            compression_info: Some(compression_info),
+            annotations,
+        },
+    );
+    builder.finish(ztoc, None);
+
+    Ok(builder)
+}
+
+/// Encodes the TOC half of a split ztoc. It is a ztoc flatbuffer without `compression_info`
+/// and with a zero `compressed_archive_size`.
+pub fn encode_toc_artifact(
+    artifact: &TocArtifact,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, ZtocError> {
+    let mut builder = flatbuffers::FlatBufferBuilder::with_capacity(1024);
+    let version = builder.create_string(&artifact.version);
+    let build_tool_identifier = builder.create_string(&artifact.build_tool_identifier);
+    let toc = encode_toc(&mut builder, &artifact.toc, options)?;
+    let annotations = encode_annotations(&mut builder, &artifact.annotations);
+
+    let ztoc = Ztoc::create(
+        &mut builder,
+        &ZtocArgs {
+            version: Some(version),
+            build_tool_identifier: Some(build_tool_identifier),
+            uncompressed_archive_size: artifact.uncompressed_archive_size.0 as i64,
+            toc: Some(toc),
+            annotations,
+            ..Default::default()
+        },
+    );
+    builder.finish(ztoc, None);
+
+    Ok(builder.finished_data().to_vec())
+}
+
+/// Encodes the compression half of a split ztoc. It is a ztoc flatbuffer without
+/// `build_tool_identifier`, `toc` or annotations.
+pub fn encode_compression_artifact(
+    artifact: &CompressionArtifact,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, ZtocError> {
+    let mut builder = flatbuffers::FlatBufferBuilder::with_capacity(1024);
+    let version = builder.create_string(&artifact.version);
+    let compression_info = encode_compression_info(
+        &mut builder,
+        &artifact.compression_info,
+        options,
+        StreamEnd {
+            r#in: artifact.compressed_archive_size.0,
+            out: artifact.uncompressed_archive_size.0,
+        },
+    )?;
+
+    let ztoc = Ztoc::create(
+        &mut builder,
+        &ZtocArgs {
+            version: Some(version),
+            compressed_archive_size: artifact.compressed_archive_size.0 as i64,
+            uncompressed_archive_size: artifact.uncompressed_archive_size.0 as i64,
+            compression_info: Some(compression_info),
+            ..Default::default()
+        },
+    );
+    builder.finish(ztoc, None);
+
+    Ok(builder.finished_data().to_vec())
+}
+
+fn encode_toc<'a>(
+    builder: &mut FlatBufferBuilder<'a>,
+    toc: &Toc,
+    options: &EncodeOptions,
+) -> Result<WIPOffset<TOC<'a>>, ZtocError> {
+    let mut entries: Vec<_> = toc.metadata.iter().collect();
+    if options.sort_by_offset {
+        entries.sort_by(|a, b| {
+            (a.uncompressed_offset.0, &a.name).cmp(&(b.uncompressed_offset.0, &b.name))
+        });
+    }
+    let mut metadata = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let name = builder.create_string(&path_str(&entry.name)?);
+        let linkname = match &entry.link_name {
+            _ if !options.includes(TocField::LinkName) => None,
+            Some(link) => Some(builder.create_string(&path_str(link)?)),
+            None if options.omit_absent_link_names => None,
+            None => Some(builder.create_string("")),
+        };
         let uname = entry
             .uname
             .as_ref()
+            .filter(|_| options.includes(TocField::Uname))
             .map(|uname| builder.create_string(uname));
         let gname = entry
             .gname
             .as_ref()
+            .filter(|_| options.includes(TocField::Gname))
             .map(|gname| builder.create_string(gname));
-        let type_ = builder.create_string(entry_to_string(&entry.r#type));
-        let mod_time =
-            builder.create_string(&entry.mod_time.and_local_timezone(Utc).unwrap().to_rfc3339());
+        let type_ = match options.includes(TocField::Type) {
+            true => Some(builder.create_string(entry_type_name(&entry.name, entry.r#type)?)),
+            false => None,
+        };
+        let mod_time = options.includes(TocField::ModTime).then(|| {
+            let mod_time = match options.clamp_pre_epoch_mtimes {
+                true => entry.mod_time.max(DateTime::UNIX_EPOCH.naive_utc()),
+                false => entry.mod_time,
+            };
+            builder.create_string(&mod_time.and_local_timezone(Utc).unwrap().to_rfc3339())
+        });
 
-        let mut xattrs = Vec::with_capacity(entry.x_attrs.len());
-        for (key, value) in &entry.x_attrs {
-            let key = builder.create_string(key);
-            let value = builder.create_string(value);
-            xattrs.push(Xattr::create(
-                &mut builder,
-                &XattrArgs {
-                    key: Some(key),
-                    value: Some(value),
-                },
-            ))
-        }
-        let xattrs = builder.create_vector(&xattrs);
+        let xattrs = if options.includes(TocField::Xattrs) {
+            let mut xattrs = Vec::with_capacity(entry.x_attrs.len());
+            for (key, value) in &entry.x_attrs {
+                let key = builder.create_string(key);
+                let value = builder.create_string(value);
+                xattrs.push(Xattr::create(
+                    builder,
+                    &XattrArgs {
+                        key: Some(key),
+                        value: Some(value),
+                    },
+                ))
+            }
+            Some(builder.create_vector(&xattrs))
+        } else {
+            None
+        };
+        let sparse = if options.includes(TocField::Sparse) && !entry.sparse.is_empty() {
+            let mut segments = Vec::with_capacity(entry.sparse.len());
+            for &(offset, length) in &entry.sparse {
+                segments.push(SparseSegment::create(
+                    builder,
+                    &SparseSegmentArgs {
+                        offset: offset as i64,
+                        length: length as i64,
+                    },
+                ))
+            }
+            Some(builder.create_vector(&segments))
+        } else {
+            None
+        };
+        // Left out scalars are written as their default, which flatbuffers omits.
+        let scalar = |field, value: i64| if options.includes(field) { value } else { 0 };
 
         metadata.push(FileMetadata::create(
-            &mut builder,
+            builder,
             &FileMetadataArgs {
                 name: Some(name),
-                type_: Some(type_),
+                type_,
                 uncompressed_offset: entry.uncompressed_offset.0 as i64,
                 uncompressed_size: entry.uncompressed_size.0 as i64,
-                linkname: Some(linkname),
-                mode: entry.mode as i64,
-                uid: entry.uid as u32,
-                gid: entry.gid as u32,
+                linkname,
+                mode: scalar(TocField::Mode, entry.mode as i64),
+                uid: scalar(TocField::Uid, entry.uid as i64) as u32,
+                gid: scalar(TocField::Gid, entry.gid as i64) as u32,
                 uname,
                 gname,
-                mod_time: Some(mod_time),
-                devmajor: entry.dev_minor.unwrap_or_default() as i64,
-                devminor: entry.dev_major.unwrap_or_default() as i64,
-                xattrs: Some(xattrs),
+                mod_time,
+                devmajor: scalar(
+                    TocField::DevMajor,
+                    entry.dev_major.unwrap_or_default() as i64,
+                ),
+                devminor: scalar(
+                    TocField::DevMinor,
+                    entry.dev_minor.unwrap_or_default() as i64,
+                ),
+                xattrs,
+                compressed_span_offset: scalar(
+                    TocField::CompressedSpanOffset,
+                    entry.compressed_span_offset.unwrap_or_default() as i64,
+                ),
+                sparse,
             },
         ));
     }
 
     let metadata = builder.create_vector(&metadata);
-    let toc = TOC::create(
-        &mut builder,
+    Ok(TOC::create(
+        builder,
         &TOCArgs {
             metadata: Some(metadata),
         },
-    );
+    ))
+}
 
-    let span_digests = ztoc
-        .compression_info
+/// Encodes `info`, compressing the checkpoint windows and appending `stream_end` to the
+/// checkpoints blob if `options` ask for it.
+fn encode_compression_info<'a>(
+    builder: &mut FlatBufferBuilder<'a>,
+    info: &crate::ztoc::CompressionInfo,
+    options: &EncodeOptions,
+    stream_end: StreamEnd,
+) -> Result<WIPOffset<CompressionInfo<'a>>, ZtocError> {
+    let span_digests = info
         .span_digests
         .iter()
         .map(|digest| builder.create_string(digest))
         .collect::<Vec<_>>();
     let span_digests = builder.create_vector(&span_digests);
-    let checkpoints = builder.create_vector(&ztoc.compression_info.checkpoints);
+    let checkpoints = match options {
+        EncodeOptions {
+            compress_windows: false,
+            varint_offsets: false,
+            stream_end_sentinel: false,
+            ..
+        } => builder.create_vector(&info.checkpoints),
+        _ => {
+            let mut checkpoints = if options.compress_windows {
+                compress_checkpoint_windows(&info.checkpoints)?
+            } else {
+                info.checkpoints.clone()
+            };
+            if options.varint_offsets {
+                checkpoints = varint_checkpoint_offsets(&checkpoints)?;
+            }
+            if options.stream_end_sentinel {
+                checkpoints.extend_from_slice(&stream_end.to_bytes());
+            }
+            builder.create_vector(&checkpoints)
+        }
+    };
 
-    let compression_info = CompressionInfo::create(
-        &mut builder,
+    let extra_span_digests = if info.extra_span_digests.is_empty() {
+        None
+    } else {
+        let mut lists = Vec::with_capacity(info.extra_span_digests.len());
+        for (algorithm, digests) in &info.extra_span_digests {
+            let algorithm = builder.create_string(algorithm.name());
+            let digests = digests
+                .iter()
+                .map(|digest| builder.create_string(digest))
+                .collect::<Vec<_>>();
+            let digests = builder.create_vector(&digests);
+            lists.push(SpanDigestList::create(
+                builder,
+                &SpanDigestListArgs {
+                    algorithm: Some(algorithm),
+                    digests: Some(digests),
+                },
+            ));
+        }
+        Some(builder.create_vector(&lists))
+    };
+
+    Ok(CompressionInfo::create(
+        builder,
         &CompressionInfoArgs {
             compression_algorithm: CompressionAlgorithm::Gzip,
-            max_span_id: ztoc.compression_info.max_span_id as i32,
+            max_span_id: info.max_span_id as i32,
             span_digests: Some(span_digests),
             checkpoints: Some(checkpoints),
+            extra_span_digests,
+            span_digest_kind: match info.span_digest_kind {
+                crate::zinfo::SpanDigestKind::Compressed => SpanDigestKind::Compressed,
+                crate::zinfo::SpanDigestKind::Uncompressed => SpanDigestKind::Uncompressed,
+            },
         },
-    );
+    ))
+}
 
-    let ztoc = Ztoc::create(
-        &mut builder,
-        &ZtocArgs {
-            version: Some(version),
-            build_tool_identifier: Some(build_tool_identifier),
-            compressed_archive_size: ztoc.compressed_achrive_size.0 as i64,
-            uncompressed_archive_size: ztoc.uncompressed_archive_size.0 as i64,
-            toc: Some(toc),
-            compression_info: Some(compression_info),
-        },
-    );
-    builder.finish(ztoc, None);
+/// Encodes `annotations` sorted by key, or not at all if there are none.
+fn encode_annotations<'a>(
+    builder: &mut FlatBufferBuilder<'a>,
+    annotations: &HashMap<String, String>,
+) -> Option<WIPOffset<Vector<'a, ForwardsUOffset<Annotation<'a>>>>> {
+    if annotations.is_empty() {
+        None
+    } else {
+        let mut sorted = annotations.iter().collect::<Vec<_>>();
+        sorted.sort();
+        let mut annotations = Vec::with_capacity(sorted.len());
+        for (key, value) in sorted {
+            let key = builder.create_string(key);
+            let value = builder.create_string(value);
+            annotations.push(Annotation::create(
+                builder,
+                &AnnotationArgs {
+                    key: Some(key),
+                    value: Some(value),
+                },
+            ));
+        }
+        Some(builder.create_vector(&annotations))
+    }
+}
 
-    builder.finished_data().to_vec()
+/// Returns `path` as a string that can be stored in the ztoc, always using forward slashes as in
+/// tar and OCI. Flatbuffers strings must be UTF-8, so paths that aren't are rejected. They may
+/// contain NUL bytes, but readers commonly treat them as C strings, so such paths are rejected
+/// too.
+fn path_str(path: &Path) -> Result<Cow<'_, str>, ZtocError> {
+    let invalid = |reason: &str| ZtocError::InvalidPath {
+        path: path.to_path_buf(),
+        reason: reason.into(),
+    };
+    let path_str = forward_slashes(path).ok_or_else(|| invalid("not valid UTF-8"))?;
+    if path_str.contains('\0') {
+        return Err(invalid("contains a NUL byte"));
+    }
+    Ok(path_str)
+}
+
+/// On Unix `/` is the only separator, so the path is used exactly as it appeared in the archive.
+#[cfg(unix)]
+fn forward_slashes(path: &Path) -> Option<Cow<'_, str>> {
+    path.to_str().map(Cow::Borrowed)
+}
+
+/// Elsewhere the components are joined explicitly, keeping a trailing separator on directories.
+#[cfg(not(unix))]
+fn forward_slashes(path: &Path) -> Option<Cow<'_, str>> {
+    use std::path::{is_separator, Component};
+
+    let raw = path.to_str()?;
+    let mut joined = String::with_capacity(raw.len());
+    for component in path.components() {
+        if component == Component::RootDir {
+            joined.push('/');
+            continue;
+        }
+        if !joined.is_empty() && !joined.ends_with('/') {
+            joined.push('/');
+        }
+        joined.push_str(component.as_os_str().to_str()?);
+    }
+    if raw.ends_with(is_separator) && !joined.ends_with('/') {
+        joined.push('/');
+    }
+    Some(Cow::Owned(joined))
 }
 
 #[cfg(test)]
 mod test {
-    use std::fs::File;
+    use std::{
+        fs::File,
+        io::{self, Read},
+    };
 
     use chrono::DateTime;
+    use tar::EntryType;
 
-    use crate::{ztoc::ZToc, ztoc_flatbuffers};
+    use crate::{
+        error::ZtocError,
+        fetch::fetch_file,
+        testutil,
+        zinfo::{build_zinfo, DigestAlgorithm, SpanDigestKind},
+        ztoc::{parse_checkpoints, parse_stream_end, ZToc},
+        ztoc_flatbuffers,
+    };
 
-    use super::encode_ztoc;
+    use super::{
+        encode_compression_artifact, encode_toc_artifact, encode_ztoc, encode_ztoc_to,
+        encode_ztoc_with_options, entry_to_string, index_layer, string_to_entry, EncodeOptions,
+        StreamEnd, TocField,
+    };
+
+    #[test]
+    fn test_span_digest_kind() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let compressed = ZToc::new(layer).unwrap();
+        let uncompressed = ZToc::builder()
+            .span_digest_kind(SpanDigestKind::Uncompressed)
+            .build(layer)
+            .unwrap();
+        assert_ne!(
+            compressed.compression_info.span_digests,
+            uncompressed.compression_info.span_digests
+        );
+
+        let encoded = encode_ztoc(&compressed).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        assert_eq!(
+            decoded.compression_info().unwrap().span_digest_kind(),
+            ztoc_flatbuffers::ztoc::SpanDigestKind::Compressed
+        );
+        let encoded = encode_ztoc(&uncompressed).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        assert_eq!(
+            decoded.compression_info().unwrap().span_digest_kind(),
+            ztoc_flatbuffers::ztoc::SpanDigestKind::Uncompressed
+        );
+    }
+
+    #[test]
+    fn test_absent_and_empty_link_names() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "file", &b"hello"[..])
+            .unwrap();
+        // A symlink whose target is empty.
+        let mut header = testutil::header(tar::EntryType::Symlink, 0);
+        builder
+            .append_data(&mut header, "empty-link", &[][..])
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let ztoc = ZToc::new(&layer[..]).unwrap();
+        assert_eq!(ztoc.toc.metadata[0].link_name, None);
+        assert_eq!(ztoc.toc.metadata[1].link_name, Some("".into()));
+
+        // By default both are written as empty strings, matching soci.
+        let encoded = encode_ztoc(&ztoc).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let metadata = decoded.toc().unwrap().metadata().unwrap();
+        assert_eq!(metadata.get(0).linkname(), Some(""));
+        assert_eq!(metadata.get(1).linkname(), Some(""));
+
+        let encoded = encode_ztoc_with_options(
+            &ztoc,
+            &EncodeOptions {
+                omit_absent_link_names: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let metadata = decoded.toc().unwrap().metadata().unwrap();
+        assert_eq!(metadata.get(0).linkname(), None);
+        assert_eq!(metadata.get(1).linkname(), Some(""));
+    }
+
+    #[test]
+    fn test_continuous_entry() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Continuous, 5);
+        builder
+            .append_data(&mut header, "contiguous", &b"hello"[..])
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let ztoc = ZToc::new(&layer[..]).unwrap();
+        let entry = &ztoc.toc.metadata[0];
+        assert_eq!(entry.r#type, tar::EntryType::Continuous);
+        assert_eq!(entry.uncompressed_offset.0, 512);
+        assert_eq!(entry.uncompressed_size.0, 5);
+
+        let encoded = encode_ztoc(&ztoc).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let metadata = decoded.toc().unwrap().metadata().unwrap();
+        assert_eq!(metadata.get(0).type_(), Some("reg"));
+        assert_eq!(metadata.get(0).uncompressed_offset(), 512);
+        assert_eq!(metadata.get(0).uncompressed_size(), 5);
+    }
+
+    #[test]
+    fn test_string_to_entry() {
+        for entry in [
+            EntryType::Regular,
+            EntryType::Link,
+            EntryType::Symlink,
+            EntryType::Char,
+            EntryType::Block,
+            EntryType::Directory,
+            EntryType::Fifo,
+        ] {
+            let name = entry_to_string(&entry).unwrap();
+            assert_eq!(string_to_entry(name), Some(entry), "{}", name);
+        }
+        for (entry, decoded) in [
+            (EntryType::Continuous, EntryType::Regular),
+            (EntryType::GNUSparse, EntryType::Regular),
+            (EntryType::new(b'D'), EntryType::Directory),
+        ] {
+            assert_eq!(
+                string_to_entry(entry_to_string(&entry).unwrap()),
+                Some(decoded)
+            );
+        }
+        for unknown in ["", "file", "REG", "unknown"] {
+            assert_eq!(string_to_entry(unknown), None, "{:?}", unknown);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_entry_type() {
+        // A volume label, which tar stores as a regular header of its own type.
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::new(b'V'), 0);
+        builder.append_data(&mut header, "label", &[][..]).unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+        let ztoc = ZToc::new(&layer[..]).unwrap();
+
+        let err = encode_ztoc(&ztoc).unwrap_err();
+        assert!(
+            matches!(err, ZtocError::UnsupportedEntryType { .. }),
+            "{}",
+            err
+        );
+        assert_eq!(
+            err.to_string(),
+            "label has entry type 'V', which a ztoc can't represent"
+        );
+        assert!(crate::json::to_json(&ztoc).is_err());
+
+        // Without the type there is nothing to name.
+        encode_ztoc_with_options(
+            &ztoc,
+            &EncodeOptions {
+                toc_fields: Some(Vec::new()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // The entry can be left out of the TOC instead.
+        let ztoc = ZToc::builder()
+            .included_entry_types(&[tar::EntryType::Regular])
+            .build(&layer[..])
+            .unwrap();
+        encode_ztoc(&ztoc).unwrap();
+    }
+
+    #[test]
+    fn test_gnu_dumpdir_entry() {
+        let layer = testutil::gzip(include_bytes!("testdata/incremental.tar"));
+        let ztoc = ZToc::new(&layer[..]).unwrap();
+        let encoded = encode_ztoc(&ztoc).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let metadata = decoded.toc().unwrap().metadata().unwrap();
+        assert_eq!(metadata.get(0).type_(), Some("dir"));
+        assert_eq!(metadata.get(0).uid(), 1234);
+        assert_eq!(metadata.get(0).gid(), 5678);
+        assert_eq!(metadata.get(1).type_(), Some("reg"));
+    }
+
+    #[test]
+    fn test_index_layer() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let (ztoc, encoded) = index_layer(layer).unwrap();
+        assert_eq!(encoded, encode_ztoc(&ztoc).unwrap());
+        assert_eq!(encoded, encode_ztoc(&ZToc::new(layer).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_split_and_join() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let ztoc = ZToc::builder()
+            .annotation("org.example.source", "fixture")
+            .build(layer)
+            .unwrap();
+        let encoded = encode_ztoc(&ztoc).unwrap();
+
+        let (toc, compression) = ztoc.split();
+        let options = EncodeOptions::default();
+        let toc_only = encode_toc_artifact(&toc, &options).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&toc_only).unwrap();
+        assert_eq!(
+            decoded.toc().unwrap().metadata().unwrap().len(),
+            toc.toc.metadata.len()
+        );
+        assert!(decoded.compression_info().is_none());
+        let compression_only = encode_compression_artifact(&compression, &options).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&compression_only).unwrap();
+        assert!(decoded.toc().is_none());
+        assert_eq!(
+            decoded
+                .compression_info()
+                .unwrap()
+                .checkpoints()
+                .unwrap()
+                .bytes(),
+            compression.compression_info.checkpoints
+        );
+
+        let joined = ZToc::join(toc, compression).unwrap();
+        assert_eq!(encode_ztoc(&joined).unwrap(), encoded);
+
+        // Halves of different layers don't join.
+        let (toc, _) = ZToc::new(layer).unwrap().split();
+        let other = testutil::gzip(&testutil::tar_of(&[("file", b"hello")]));
+        let (_, compression) = ZToc::new(&other[..]).unwrap().split();
+        assert!(ZToc::join(toc, compression).is_err());
+    }
+
+    #[test]
+    fn test_nul_in_name() {
+        // Tar headers can't hold a NUL in a name, but PAX records can.
+        let mut builder = tar::Builder::new(Vec::new());
+        let record = b"21 path=bad\0name.txt\n";
+        let mut header = testutil::header(tar::EntryType::XHeader, record.len() as u64);
+        builder
+            .append_data(&mut header, "PaxHeaders/name", &record[..])
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "placeholder", &b"hello"[..])
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let ztoc = ZToc::new(&layer[..]).unwrap();
+        assert_eq!(ztoc.toc.metadata[0].name.to_str(), Some("bad\0name.txt"));
+        let err = encode_ztoc(&ztoc).unwrap_err();
+        assert!(
+            matches!(&err, ZtocError::InvalidPath { path, .. } if path.to_str() == Some("bad\0name.txt")),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_names() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let latin1 = OsStr::from_bytes(b"caf\xe9.txt");
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, latin1, &b"hello"[..])
+            .unwrap();
+        let name_layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Symlink, 0);
+        builder.append_link(&mut header, "link", latin1).unwrap();
+        let link_layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        for layer in [name_layer, link_layer] {
+            let ztoc = ZToc::new(&layer[..]).unwrap();
+            let err = encode_ztoc(&ztoc).unwrap_err();
+            assert!(
+                matches!(&err, ZtocError::InvalidPath { path, .. } if path.as_os_str() == latin1),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_gnu_long_names() {
+        let long_name = format!("{}/file.txt", ["component"; 30].join("/"));
+        let long_target = format!("../{}", "t".repeat(197));
+        assert!(long_name.len() >= 300 && long_target.len() == 200);
+
+        let mut builder = tar::Builder::new(Vec::new());
+        builder
+            .append_data(
+                &mut testutil::header(tar::EntryType::Regular, 5),
+                &long_name,
+                &b"hello"[..],
+            )
+            .unwrap();
+        builder
+            .append_link(
+                &mut testutil::header(tar::EntryType::Symlink, 0),
+                "link",
+                &long_target,
+            )
+            .unwrap();
+        let tar = builder.into_inner().unwrap();
+        // Both names were written as GNU long name ('L') and long link name ('K') entries.
+        assert_eq!(tar[156], b'L');
+        let layer = testutil::gzip(&tar);
+
+        let encoded = encode_ztoc(&ZToc::new(&layer[..]).unwrap()).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let entries = decoded.toc().unwrap().metadata().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries.get(0).name(), Some(long_name.as_str()));
+        assert_eq!(entries.get(0).uncompressed_size(), 5);
+        assert_eq!(entries.get(1).name(), Some("link"));
+        assert_eq!(entries.get(1).linkname(), Some(long_target.as_str()));
+    }
+
+    #[test]
+    fn test_forward_slashes() {
+        let nested = std::path::Path::new("usr").join("share").join("doc.txt");
+        assert_eq!(super::path_str(&nested).unwrap(), "usr/share/doc.txt");
+        assert_eq!(
+            super::path_str(std::path::Path::new("usr/share/")).unwrap(),
+            "usr/share/"
+        );
+
+        #[cfg(windows)]
+        assert_eq!(
+            super::path_str(std::path::Path::new(r"usr\share\doc.txt")).unwrap(),
+            "usr/share/doc.txt"
+        );
+    }
+
+    #[test]
+    fn test_stream_end_sentinel() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let ztoc = ZToc::new(layer).unwrap();
+        let encoded = encode_ztoc_with_options(
+            &ztoc,
+            &EncodeOptions {
+                stream_end_sentinel: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let blob = decoded
+            .compression_info()
+            .unwrap()
+            .checkpoints()
+            .unwrap()
+            .bytes();
+
+        assert_eq!(
+            parse_stream_end(blob).unwrap(),
+            Some(StreamEnd {
+                r#in: layer.len() as u64,
+                out: include_bytes!("testdata/test.tar").len() as u64,
+            })
+        );
+        assert_eq!(
+            parse_checkpoints(blob).unwrap(),
+            parse_checkpoints(&ztoc.compression_info.checkpoints).unwrap()
+        );
+        assert_eq!(
+            parse_stream_end(&ztoc.compression_info.checkpoints).unwrap(),
+            None
+        );
+
+        // Seeking only uses the checkpoints.
+        let data = fetch_file(&decoded, "src/main.rs", |range| {
+            Ok(layer[range.start as usize..range.end as usize].to_vec())
+        })
+        .unwrap();
+        let mut archive = tar::Archive::new(&include_bytes!("testdata/test.tar")[..]);
+        let mut expected = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            if entry.path().unwrap().to_str() == Some("src/main.rs") {
+                entry.read_to_end(&mut expected).unwrap();
+            }
+        }
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn test_device_numbers() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Char, 0);
+        header.set_device_major(4).unwrap();
+        header.set_device_minor(64).unwrap();
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "dev/ttyS0", io::empty())
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let ztoc = ZToc::new(&layer[..]).unwrap();
+        let entry = &ztoc.toc.metadata[0];
+        assert_eq!((entry.dev_major, entry.dev_minor), (Some(4), Some(64)));
+
+        let encoded = encode_ztoc(&ztoc).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let entry = decoded.toc().unwrap().metadata().unwrap().get(0);
+        assert_eq!(entry.type_(), Some("char"));
+        assert_eq!((entry.devmajor(), entry.devminor()), (4, 64));
+    }
+
+    #[test]
+    fn test_compressed_windows() {
+        let text = testutil::text_like(1 << 20);
+        let tar = testutil::tar_of(&[("text", &text)]);
+        let layer = testutil::gzip(&tar);
+        let zinfo = build_zinfo(&layer[..], 1 << 16).unwrap();
+        let ztoc = ZToc::from_tar_and_zinfo(&tar[..], zinfo).unwrap();
+        let checkpoints = parse_checkpoints(&ztoc.compression_info.checkpoints).unwrap();
+        assert!(checkpoints.len() > 2);
+
+        let plain = encode_ztoc(&ztoc).unwrap();
+        for stream_end_sentinel in [false, true] {
+            let encoded = encode_ztoc_with_options(
+                &ztoc,
+                &EncodeOptions {
+                    compress_windows: true,
+                    stream_end_sentinel,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert!(encoded.len() < plain.len() / 2);
+
+            let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+            let blob = decoded
+                .compression_info()
+                .unwrap()
+                .checkpoints()
+                .unwrap()
+                .bytes();
+            assert_eq!(parse_checkpoints(blob).unwrap(), checkpoints);
+            assert_eq!(
+                parse_stream_end(blob).unwrap().is_some(),
+                stream_end_sentinel
+            );
+
+            let data = fetch_file(&decoded, "text", |range| {
+                Ok(layer[range.start as usize..range.end as usize].to_vec())
+            })
+            .unwrap();
+            assert_eq!(data, text);
+        }
+    }
+
+    #[test]
+    fn test_varint_offsets() {
+        let tar = testutil::tar_of(&[("random", &testutil::random_bytes(1 << 20, 7))]);
+        let layer = testutil::gzip(&tar);
+        let zinfo = build_zinfo(&layer[..], 1 << 16).unwrap();
+        let ztoc = ZToc::from_tar_and_zinfo(&tar[..], zinfo).unwrap();
+        let checkpoints = parse_checkpoints(&ztoc.compression_info.checkpoints).unwrap();
+        assert!(checkpoints.len() > 2);
+
+        for compress_windows in [false, true] {
+            for stream_end_sentinel in [false, true] {
+                let encode = |varint_offsets| {
+                    let options = EncodeOptions {
+                        compress_windows,
+                        stream_end_sentinel,
+                        varint_offsets,
+                        ..Default::default()
+                    };
+                    encode_ztoc_with_options(&ztoc, &options).unwrap()
+                };
+                let (fixed, varint) = (encode(false), encode(true));
+                assert!(varint.len() < fixed.len());
+
+                for encoded in [&fixed, &varint] {
+                    let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(encoded).unwrap();
+                    let blob = decoded
+                        .compression_info()
+                        .unwrap()
+                        .checkpoints()
+                        .unwrap()
+                        .bytes();
+                    assert_eq!(parse_checkpoints(blob).unwrap(), checkpoints);
+                    assert_eq!(
+                        parse_stream_end(blob).unwrap().is_some(),
+                        stream_end_sentinel
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sort_by_offset() {
+        let mut ztoc = ZToc::new(&include_bytes!("testdata/layer.tar.gz")[..]).unwrap();
+        assert!(ztoc.toc.metadata.len() > 10);
+        // A TOC that is no longer in archive order, e.g. after editing it.
+        ztoc.toc.metadata.reverse();
+        let encoded_offsets = |options: &EncodeOptions| {
+            let encoded = encode_ztoc_with_options(&ztoc, options).unwrap();
+            let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+            let metadata = decoded.toc().unwrap().metadata().unwrap();
+            metadata
+                .iter()
+                .map(|entry| entry.uncompressed_offset())
+                .collect::<Vec<_>>()
+        };
+
+        let unsorted = encoded_offsets(&EncodeOptions::default());
+        assert!(unsorted.windows(2).any(|pair| pair[0] > pair[1]));
+        let sorted = encoded_offsets(&EncodeOptions {
+            sort_by_offset: true,
+            ..Default::default()
+        });
+        assert!(sorted.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(sorted.len(), unsorted.len());
+    }
+
+    #[test]
+    fn test_encode_ztoc_to() {
+        let ztoc = ZToc::new(&include_bytes!("testdata/layer.tar.gz")[..]).unwrap();
+        let mut written = Vec::new();
+        encode_ztoc_to(&ztoc, &mut written).unwrap();
+        assert_eq!(written, encode_ztoc(&ztoc).unwrap());
+
+        let mut full = [0; 16];
+        let err = encode_ztoc_to(&ztoc, &mut full[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_toc_fields() {
+        let ztoc = ZToc::new(&include_bytes!("testdata/layer.tar.gz")[..]).unwrap();
+        let full = encode_ztoc(&ztoc).unwrap();
+        let projected = encode_ztoc_with_options(
+            &ztoc,
+            &EncodeOptions {
+                toc_fields: Some(Vec::new()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(
+            projected.len() < full.len() * 3 / 4,
+            "{} vs {}",
+            projected.len(),
+            full.len()
+        );
+
+        let full = ztoc_flatbuffers::ztoc::root_as_ztoc(&full).unwrap();
+        let projected = ztoc_flatbuffers::ztoc::root_as_ztoc(&projected).unwrap();
+        let full = full.toc().unwrap().metadata().unwrap();
+        let projected = projected.toc().unwrap().metadata().unwrap();
+        assert_eq!(full.len(), projected.len());
+        for (full, projected) in full.iter().zip(projected.iter()) {
+            assert_eq!(full.name(), projected.name());
+            assert_eq!(full.uncompressed_offset(), projected.uncompressed_offset());
+            assert_eq!(full.uncompressed_size(), projected.uncompressed_size());
+            assert_eq!(projected.type_(), None);
+            assert_eq!(projected.mod_time(), None);
+            assert_eq!(projected.mode(), 0);
+            assert!(projected.xattrs().is_none());
+        }
+
+        // Listed fields are kept.
+        let encoded = encode_ztoc_with_options(
+            &ztoc,
+            &EncodeOptions {
+                toc_fields: Some(vec![TocField::Type, TocField::Mode]),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let entry = decoded.toc().unwrap().metadata().unwrap().get(0);
+        assert_eq!(entry.type_(), entry_to_string(&ztoc.toc.metadata[0].r#type));
+        assert_eq!(entry.mode(), ztoc.toc.metadata[0].mode as i64);
+        assert_eq!(entry.uname(), None);
+    }
+
+    #[test]
+    fn test_clamp_pre_epoch_mtimes() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let records = b"14 mtime=-100\n";
+        let mut header = testutil::header(EntryType::XHeader, records.len() as u64);
+        builder
+            .append_data(&mut header, "PaxHeaders/old", &records[..])
+            .unwrap();
+        let mut header = testutil::header(EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "old", &b"hello"[..])
+            .unwrap();
+        let ztoc = ZToc::new(&testutil::gzip(&builder.into_inner().unwrap())[..]).unwrap();
+
+        let mod_time = |clamp_pre_epoch_mtimes| {
+            let encoded = encode_ztoc_with_options(
+                &ztoc,
+                &EncodeOptions {
+                    clamp_pre_epoch_mtimes,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+            let entries = decoded.toc().unwrap().metadata().unwrap();
+            entries.get(0).mod_time().unwrap().to_string()
+        };
+        assert_eq!(mod_time(false), "1969-12-31T23:58:20+00:00");
+        assert_eq!(mod_time(true), "1970-01-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_extra_span_digests() {
+        let ztoc = ZToc::builder()
+            .extra_digest_algorithms(&[DigestAlgorithm::Sha512])
+            .build(&include_bytes!("testdata/test.tar.gz")[..])
+            .unwrap();
+        let encoded = encode_ztoc(&ztoc).unwrap();
+        let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let compression_info = decoded.compression_info().unwrap();
+
+        let sha256_digests = compression_info.span_digests().unwrap();
+        assert_eq!(
+            ztoc.compression_info.span_digests.len(),
+            sha256_digests.len()
+        );
+        for (expected, digest) in ztoc
+            .compression_info
+            .span_digests
+            .iter()
+            .zip(sha256_digests)
+        {
+            assert_eq!(expected, digest);
+        }
+
+        let lists = compression_info.extra_span_digests().unwrap();
+        assert_eq!(lists.len(), 1);
+        let sha512 = lists.get(0);
+        assert_eq!(sha512.algorithm(), Some("sha512"));
+        let expected = &ztoc.compression_info.extra_span_digests[&DigestAlgorithm::Sha512];
+        let digests = sha512.digests().unwrap();
+        assert_eq!(expected.len(), digests.len());
+        for (expected, digest) in expected.iter().zip(digests) {
+            assert!(digest.starts_with("sha512:"));
+            assert_eq!(expected, digest);
+        }
+    }
 
     #[test]
     fn test_compare_soci_snapshotter() {
         let layer = File::open("./src/testdata/layer.tar.gz").unwrap();
         let ztoc = ZToc::new(layer).unwrap();
-        let encoded = encode_ztoc(&ztoc);
+        let encoded = encode_ztoc(&ztoc).unwrap();
 
         let decoded = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
         let expected =
@@ -173,12 +1222,7 @@ mod test {
             .span_digests()
             .unwrap()
             .into_iter()
-            .zip(
-                expected_compression_info
-                    .span_digests()
-                    .unwrap()
-                    .into_iter(),
-            )
+            .zip(expected_compression_info.span_digests().unwrap())
             .enumerate()
         {
             assert_eq!(decoded_digest, expected_digest, "index {}", i);
@@ -194,12 +1238,12 @@ mod test {
             .metadata()
             .unwrap()
             .into_iter()
-            .zip(expected_toc.metadata().unwrap().into_iter())
+            .zip(expected_toc.metadata().unwrap())
         {
             let decoded_time =
-                DateTime::parse_from_rfc3339(decoded_entry.mod_time().take().unwrap()).unwrap();
+                DateTime::parse_from_rfc3339(decoded_entry.mod_time().unwrap()).unwrap();
             let expected_time =
-                DateTime::parse_from_rfc3339(expected_entry.mod_time().take().unwrap()).unwrap();
+                DateTime::parse_from_rfc3339(expected_entry.mod_time().unwrap()).unwrap();
             assert_eq!(decoded_time, expected_time);
             assert_eq!(decoded_entry.name(), expected_entry.name());
             assert_eq!(decoded_entry.type_(), expected_entry.type_());
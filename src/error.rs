@@ -0,0 +1,169 @@
+use std::{fmt, io, path::PathBuf};
+
+/// Errors that can occur while building a ztoc.
+///
+/// Errors raised below an [`io::Read`] or other `io::Result` API, such as by the zinfo
+/// decompressor, travel as an [`io::Error`] wrapping the `ZtocError` and are unwrapped again when
+/// converted back, so callers still see the specific variant.
+#[derive(Debug)]
+pub enum ZtocError {
+    /// An error from reading the archive.
+    Io(io::Error),
+    /// zlib failed, e.g. because the compressed stream is corrupt. `code` is the zlib return
+    /// code, such as `Z_DATA_ERROR`.
+    Zlib { code: i32, message: String },
+    /// An entry's modification time can't be represented.
+    InvalidMtime { path: PathBuf, mtime: u64 },
+    /// A field of an entry that must be UTF-8, such as `uname`, isn't.
+    NonUtf8 { path: PathBuf, field: &'static str },
+    /// The archive ended before all of an entry's declared data was available.
+    TruncatedEntry {
+        path: PathBuf,
+        expected: u64,
+        available: u64,
+    },
+    /// An entry's path or link target can't be represented in a ztoc.
+    InvalidPath { path: PathBuf, reason: String },
+    /// No entry with the given path exists in the ztoc.
+    NotFound(PathBuf),
+    /// The ztoc is missing required fields or is internally inconsistent.
+    InvalidZtoc(String),
+    /// A span's recomputed digest does not match the one recorded in the ztoc.
+    DigestMismatch {
+        span: usize,
+        expected: String,
+        actual: String,
+    },
+    /// An entry's data can't be decompressed from the archive with the ztoc's checkpoints.
+    UnreadableEntry { path: PathBuf, reason: String },
+    /// A span covers no compressed bytes of its own, because the next checkpoint starts at or
+    /// before its checkpoint. Its digest would be meaningless.
+    EmptySpan { span: usize },
+    /// The uncompressed size doesn't match the ISIZE field of the gzip trailer, which is `None`
+    /// if the stream has no trailer.
+    SizeMismatch { total_out: u64, isize: Option<u32> },
+    /// The archive has more entries than the build was configured to allow.
+    TooManyEntries { limit: usize },
+    /// The archive contains an entry of a type the build was configured to reject.
+    DisallowedEntryType {
+        path: PathBuf,
+        entry_type: tar::EntryType,
+    },
+    /// An entry has a type with no name in the ztoc format, such as a vendor extension.
+    UnsupportedEntryType {
+        path: PathBuf,
+        entry_type: tar::EntryType,
+    },
+}
+
+impl fmt::Display for ZtocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZtocError::Io(err) => write!(f, "{}", err),
+            ZtocError::Zlib { code, message } => write!(f, "zlib error {}: {}", code, message),
+            ZtocError::InvalidMtime { path, mtime } => {
+                write!(f, "invalid mtime {} for {}", mtime, path.display())
+            }
+            ZtocError::NonUtf8 { path, field } => {
+                write!(f, "{} of {} is not valid UTF-8", field, path.display())
+            }
+            ZtocError::TruncatedEntry {
+                path,
+                expected,
+                available,
+            } => write!(
+                f,
+                "archive ended before the data of {} was available (expected {} bytes, {} available)",
+                path.display(),
+                expected,
+                available
+            ),
+            ZtocError::InvalidPath { path, reason } => {
+                write!(f, "invalid path {:?}: {}", path, reason)
+            }
+            ZtocError::NotFound(path) => write!(f, "{} not found in ztoc", path.display()),
+            ZtocError::InvalidZtoc(msg) => write!(f, "invalid ztoc: {}", msg),
+            ZtocError::DigestMismatch {
+                span,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "digest of span {} is {}, expected {}",
+                span, actual, expected
+            ),
+            ZtocError::UnreadableEntry { path, reason } => {
+                write!(f, "can't read the data of {}: {}", path.display(), reason)
+            }
+            ZtocError::EmptySpan { span } => {
+                write!(f, "span {} covers no compressed bytes", span)
+            }
+            ZtocError::SizeMismatch {
+                total_out,
+                isize: Some(isize),
+            } => write!(
+                f,
+                "decompressed {} bytes but the gzip trailer records {} (mod 2^32)",
+                total_out, isize
+            ),
+            ZtocError::SizeMismatch {
+                total_out,
+                isize: None,
+            } => write!(
+                f,
+                "decompressed {} bytes but the gzip stream has no trailer",
+                total_out
+            ),
+            ZtocError::TooManyEntries { limit } => {
+                write!(f, "archive has more than {} entries", limit)
+            }
+            ZtocError::DisallowedEntryType { path, entry_type } => write!(
+                f,
+                "{} has disallowed entry type {:?}",
+                path.display(),
+                entry_type
+            ),
+            ZtocError::UnsupportedEntryType { path, entry_type } => write!(
+                f,
+                "{} has entry type {:?}, which a ztoc can't represent",
+                path.display(),
+                entry_type.as_byte() as char
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ZtocError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ZtocError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ZtocError {
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<ZtocError>()) {
+            return *err.into_inner().unwrap().downcast::<ZtocError>().unwrap();
+        }
+        ZtocError::Io(err)
+    }
+}
+
+impl From<ZtocError> for io::Error {
+    fn from(err: ZtocError) -> Self {
+        match err {
+            ZtocError::Io(err) => err,
+            err @ ZtocError::Zlib { code, .. } => {
+                let kind = match code {
+                    libz_sys::Z_DATA_ERROR => io::ErrorKind::InvalidData,
+                    libz_sys::Z_MEM_ERROR => io::ErrorKind::OutOfMemory,
+                    _ => io::ErrorKind::Other,
+                };
+                io::Error::new(kind, err)
+            }
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
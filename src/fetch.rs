@@ -0,0 +1,374 @@
+use std::{
+    cmp,
+    collections::VecDeque,
+    io::{self, Cursor},
+    ops::Range,
+    path::PathBuf,
+};
+
+use crate::{
+    error::ZtocError,
+    zinfo::{checkpoint_for_offset, inflate_from_checkpoint, GZipCheckpoint},
+    ztoc::parse_checkpoints,
+    ztoc_flatbuffers::ztoc::{FileMetadata, Ztoc},
+};
+
+/// Reads the contents of the file at `path` using only a decoded ztoc and `fetch`, which returns
+/// the bytes of the compressed layer in the requested range. Only the spans covering the file
+/// are fetched and decompressed.
+pub fn fetch_file<F>(ztoc: &Ztoc<'_>, path: &str, mut fetch: F) -> Result<Vec<u8>, ZtocError>
+where
+    F: FnMut(Range<u64>) -> io::Result<Vec<u8>>,
+{
+    let entry = find_entry(ztoc, path)?;
+    let offset = entry.uncompressed_offset() as usize;
+    let size = entry.uncompressed_size() as usize;
+    if size == 0 {
+        return Ok(Vec::new());
+    }
+
+    let checkpoints = checkpoints_of(ztoc)?;
+    let (start, end) = span_range(&checkpoints, offset, size)
+        .ok_or_else(|| ZtocError::InvalidZtoc(format!("no checkpoint covers {}", path)))?;
+    let checkpoint = &checkpoints[start];
+
+    // A checkpoint that starts mid-byte needs the preceding byte to prime the decompressor.
+    let compressed_start = (checkpoint.r#in - usize::from(checkpoint.bits > 0)) as u64;
+    let compressed_end = checkpoints
+        .get(end)
+        .map_or(ztoc.compressed_archive_size() as u64, |next| {
+            next.r#in as u64
+        });
+    let compressed = fetch(compressed_start..compressed_end)?;
+
+    let data = inflate_from_checkpoint(Cursor::new(compressed), checkpoint, offset, size)?;
+    if data.len() != size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("fetched data ended before the end of {}", path),
+        )
+        .into());
+    }
+    Ok(data)
+}
+
+/// Reads files from a layer like [`fetch_file`], keeping recently decompressed spans in memory.
+///
+/// Reading a file from a cold span fetches and decompresses the whole span, priming inflate from
+/// its checkpoint, and caches the decompressed span. Later reads touching a cached span are
+/// served from memory, so clustered reads, e.g. of the small files in a directory, pay for each
+/// span once. The cache holds at most `capacity` bytes of decompressed data and evicts the least
+/// recently used span first. A span larger than the capacity evicts everything else and is only
+/// kept until the next miss.
+pub struct CachedReader<'a> {
+    ztoc: Ztoc<'a>,
+    checkpoints: Vec<GZipCheckpoint>,
+    /// Cached spans by checkpoint index, least recently used first.
+    spans: VecDeque<(usize, Vec<u8>)>,
+    size: usize,
+    capacity: usize,
+    primes: usize,
+}
+
+impl<'a> CachedReader<'a> {
+    /// Creates a reader caching up to `capacity` bytes of decompressed spans.
+    pub fn new(ztoc: Ztoc<'a>, capacity: usize) -> Result<Self, ZtocError> {
+        Ok(CachedReader {
+            ztoc,
+            checkpoints: checkpoints_of(&ztoc)?,
+            spans: VecDeque::new(),
+            size: 0,
+            capacity,
+            primes: 0,
+        })
+    }
+
+    /// Reads the contents of the file at `path`, using `fetch` to read ranges of the compressed
+    /// layer for spans that aren't cached.
+    pub fn fetch_file<F>(&mut self, path: &str, mut fetch: F) -> Result<Vec<u8>, ZtocError>
+    where
+        F: FnMut(Range<u64>) -> io::Result<Vec<u8>>,
+    {
+        let entry = find_entry(&self.ztoc, path)?;
+        let offset = entry.uncompressed_offset() as usize;
+        let size = entry.uncompressed_size() as usize;
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+        let (start, end) = span_range(&self.checkpoints, offset, size)
+            .ok_or_else(|| ZtocError::InvalidZtoc(format!("no checkpoint covers {}", path)))?;
+
+        let mut data = Vec::with_capacity(size);
+        for span in start..end {
+            let span_start = self.checkpoints[span].out;
+            let span_data = self.span(span, &mut fetch)?;
+            let from = offset.saturating_sub(span_start);
+            let to = cmp::min(span_data.len(), offset + size - span_start);
+            data.extend_from_slice(&span_data[from..to]);
+        }
+        if data.len() != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("fetched data ended before the end of {}", path),
+            )
+            .into());
+        }
+        Ok(data)
+    }
+
+    /// The number of times inflate was primed from a checkpoint, i.e. span cache misses.
+    pub fn primes(&self) -> usize {
+        self.primes
+    }
+
+    /// Returns the decompressed data of span `span`, from the cache if possible.
+    fn span<F>(&mut self, span: usize, fetch: &mut F) -> Result<&[u8], ZtocError>
+    where
+        F: FnMut(Range<u64>) -> io::Result<Vec<u8>>,
+    {
+        if let Some(index) = self.spans.iter().position(|(cached, _)| *cached == span) {
+            let entry = self.spans.remove(index).unwrap();
+            self.spans.push_back(entry);
+        } else {
+            let checkpoint = &self.checkpoints[span];
+            let (compressed_end, uncompressed_end) = self.checkpoints.get(span + 1).map_or(
+                (
+                    self.ztoc.compressed_archive_size() as u64,
+                    self.ztoc.uncompressed_archive_size() as usize,
+                ),
+                |next| (next.r#in as u64, next.out),
+            );
+            let compressed_start = (checkpoint.r#in - usize::from(checkpoint.bits > 0)) as u64;
+            let compressed = fetch(compressed_start..compressed_end)?;
+            self.primes += 1;
+            let data = inflate_from_checkpoint(
+                Cursor::new(compressed),
+                checkpoint,
+                checkpoint.out,
+                uncompressed_end - checkpoint.out,
+            )?;
+
+            while self.size + data.len() > self.capacity {
+                let Some((_, evicted)) = self.spans.pop_front() else {
+                    break;
+                };
+                self.size -= evicted.len();
+            }
+            self.size += data.len();
+            self.spans.push_back((span, data));
+        }
+        Ok(&self.spans.back().unwrap().1)
+    }
+}
+
+fn find_entry<'a>(ztoc: &Ztoc<'a>, path: &str) -> Result<FileMetadata<'a>, ZtocError> {
+    ztoc.toc()
+        .and_then(|toc| toc.metadata())
+        .ok_or_else(|| ZtocError::InvalidZtoc("missing toc".into()))?
+        .iter()
+        .find(|entry| entry.name() == Some(path))
+        .ok_or_else(|| ZtocError::NotFound(PathBuf::from(path)))
+}
+
+fn checkpoints_of(ztoc: &Ztoc<'_>) -> Result<Vec<GZipCheckpoint>, ZtocError> {
+    Ok(parse_checkpoints(
+        ztoc.compression_info()
+            .and_then(|info| info.checkpoints())
+            .ok_or_else(|| ZtocError::InvalidZtoc("missing checkpoints".into()))?
+            .bytes(),
+    )?)
+}
+
+/// Returns the index of the checkpoint to resume from to read `size` bytes at `offset`, and the
+/// index of the first checkpoint past the end of that range (or `checkpoints.len()`).
+fn span_range(
+    checkpoints: &[GZipCheckpoint],
+    offset: usize,
+    size: usize,
+) -> Option<(usize, usize)> {
+    let start = checkpoint_for_offset(checkpoints, offset)?;
+    let end = checkpoints.partition_point(|checkpoint| checkpoint.out < offset + size);
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, io::Read};
+
+    use crate::{
+        encode::encode_ztoc,
+        testutil,
+        zinfo::{build_zinfo, GzipZInfoDecompressor, CHECKPOINTS_HEADER_SIZE, CHECKPOINT_SIZE},
+        ztoc::ZToc,
+        ztoc_flatbuffers,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_fetch_file() {
+        let layer = include_bytes!("testdata/layer.tar.gz");
+        let ztoc =
+            ztoc_flatbuffers::ztoc::root_as_ztoc(include_bytes!("testdata/expected")).unwrap();
+
+        // Read every file by fully decompressing the layer to compare against.
+        let decompressor = GzipZInfoDecompressor::new(&layer[..], 1 << 22).unwrap();
+        let mut archive = tar::Archive::new(decompressor);
+        let mut contents = HashMap::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            if entry.header().entry_type() == tar::EntryType::Regular && entry.size() > 0 {
+                let path = entry.path().unwrap().to_str().unwrap().to_string();
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).unwrap();
+                contents.insert(path, data);
+            }
+        }
+
+        // Check a file in the first span, and the largest and last files which are deep into
+        // later spans.
+        let mut paths = vec!["etc/adduser.conf".to_string()];
+        paths.extend(
+            contents
+                .iter()
+                .max_by_key(|(_, d)| d.len())
+                .map(|(p, _)| p.clone()),
+        );
+        let metadata = ztoc.toc().unwrap().metadata().unwrap();
+        paths.extend(
+            metadata
+                .iter()
+                .rev()
+                .find(|entry| entry.type_() == Some("reg") && entry.uncompressed_size() > 0)
+                .map(|entry| entry.name().unwrap().to_string()),
+        );
+
+        for path in paths {
+            let mut fetched = 0;
+            let data = fetch_file(&ztoc, &path, |range| {
+                fetched += range.end - range.start;
+                Ok(layer[range.start as usize..range.end as usize].to_vec())
+            })
+            .unwrap();
+            assert_eq!(data, contents[&path], "{}", path);
+            assert!(fetched < layer.len() as u64, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_cached_reader() {
+        let files = (0..200)
+            .map(|i| {
+                (
+                    format!("dir/file-{}", i),
+                    testutil::random_bytes(2000 + i, i as u64),
+                )
+            })
+            .collect::<Vec<_>>();
+        let tar = testutil::tar_of(
+            &files
+                .iter()
+                .map(|(path, data)| (path.as_str(), &data[..]))
+                .collect::<Vec<_>>(),
+        );
+        let layer = testutil::gzip(&tar);
+        let zinfo = build_zinfo(&layer[..], 1 << 16).unwrap();
+        let ztoc = ZToc::from_tar_and_zinfo(&tar[..], zinfo).unwrap();
+        let spans = ztoc.compression_info.max_span_id + 1;
+        assert!(spans > 3);
+        let encoded = encode_ztoc(&ztoc).unwrap();
+        let ztoc = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let fetch =
+            |range: Range<u64>| Ok(layer[range.start as usize..range.end as usize].to_vec());
+
+        // Reading every file in order primes each span once, where reading them one by one
+        // primes once per file.
+        let mut reader = CachedReader::new(ztoc, 1 << 20).unwrap();
+        for (path, data) in &files {
+            assert_eq!(reader.fetch_file(path, fetch).unwrap(), *data, "{}", path);
+        }
+        assert_eq!(reader.primes(), spans);
+        for (path, data) in files.iter().rev() {
+            assert_eq!(reader.fetch_file(path, fetch).unwrap(), *data, "{}", path);
+        }
+        assert_eq!(reader.primes(), spans);
+
+        // With the cache bounded to a single span, alternating between the first and last
+        // file primes on every read.
+        let (first, last) = (&files[0], &files[files.len() - 1]);
+        let mut reader = CachedReader::new(ztoc, 0).unwrap();
+        for _ in 0..3 {
+            for (path, data) in [first, last] {
+                assert_eq!(reader.fetch_file(path, fetch).unwrap(), *data, "{}", path);
+            }
+        }
+        assert_eq!(reader.primes(), 6);
+        assert_eq!(
+            reader
+                .fetch_file("does/not/exist", fetch)
+                .unwrap_err()
+                .to_string(),
+            "does/not/exist not found in ztoc"
+        );
+    }
+
+    #[test]
+    fn test_fetch_missing_file() {
+        let ztoc =
+            ztoc_flatbuffers::ztoc::root_as_ztoc(include_bytes!("testdata/expected")).unwrap();
+        let err = fetch_file(&ztoc, "does/not/exist", |_| unreachable!()).unwrap_err();
+        assert!(matches!(err, ZtocError::NotFound(_)));
+    }
+
+    #[test]
+    fn test_fetch_corrupted_checkpoints() {
+        let data = testutil::random_bytes(1 << 18, 7);
+        let layer = testutil::gzip(&testutil::tar_of(&[("file", &data)]));
+        let offset_of = |index: usize| CHECKPOINTS_HEADER_SIZE + index * CHECKPOINT_SIZE;
+        // Each corruption fills byte ranges of the blob: `in` is 8 bytes at the start of a
+        // record, followed by 8 bytes of `out` and a byte of `bits`.
+        let corruptions = [
+            (
+                "mid-byte at offset 0",
+                vec![
+                    (offset_of(0)..offset_of(0) + 8, 0),
+                    (offset_of(0) + 16..offset_of(0) + 17, 1),
+                ],
+            ),
+            ("bit count", vec![(offset_of(1) + 16..offset_of(1) + 17, 9)]),
+            (
+                "decreasing out",
+                vec![(offset_of(2) + 8..offset_of(2) + 16, 0)],
+            ),
+        ];
+        for (name, patches) in corruptions {
+            let mut ztoc = ZToc::builder()
+                .span_size(1 << 14)
+                .build(&layer[..])
+                .unwrap();
+            for (range, byte) in patches {
+                ztoc.compression_info.checkpoints[range].fill(byte);
+            }
+            let encoded = encode_ztoc(&ztoc).unwrap();
+            let ztoc = ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+
+            let err = fetch_file(&ztoc, "file", |range| {
+                Ok(layer[range.start as usize..range.end as usize].to_vec())
+            })
+            .unwrap_err();
+            assert!(
+                matches!(err, ZtocError::InvalidZtoc(_)),
+                "{}: {}",
+                name,
+                err
+            );
+            let err = CachedReader::new(ztoc, 1 << 20).err().unwrap();
+            assert!(
+                matches!(err, ZtocError::InvalidZtoc(_)),
+                "{}: {}",
+                name,
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,1545 @@
+// automatically generated by the FlatBuffers compiler, do not modify
+
+
+// @generated
+
+use core::mem;
+use core::cmp::Ordering;
+
+extern crate flatbuffers;
+use self::flatbuffers::{EndianScalar, Follow};
+
+#[allow(unused_imports, dead_code)]
+pub mod ztoc {
+
+  use core::mem;
+  use core::cmp::Ordering;
+
+  extern crate flatbuffers;
+  use self::flatbuffers::{EndianScalar, Follow};
+
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MIN_COMPRESSION_ALGORITHM: i8 = 1;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MAX_COMPRESSION_ALGORITHM: i8 = 1;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+#[allow(non_upper_case_globals)]
+pub const ENUM_VALUES_COMPRESSION_ALGORITHM: [CompressionAlgorithm; 1] = [
+  CompressionAlgorithm::Gzip,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(transparent)]
+pub struct CompressionAlgorithm(pub i8);
+#[allow(non_upper_case_globals)]
+impl CompressionAlgorithm {
+  pub const Gzip: Self = Self(1);
+
+  pub const ENUM_MIN: i8 = 1;
+  pub const ENUM_MAX: i8 = 1;
+  pub const ENUM_VALUES: &'static [Self] = &[
+    Self::Gzip,
+  ];
+  /// Returns the variant's name or "" if unknown.
+  pub fn variant_name(self) -> Option<&'static str> {
+    match self {
+      Self::Gzip => Some("Gzip"),
+      _ => None,
+    }
+  }
+}
+impl core::fmt::Debug for CompressionAlgorithm {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    if let Some(name) = self.variant_name() {
+      f.write_str(name)
+    } else {
+      f.write_fmt(format_args!("<UNKNOWN {:?}>", self.0))
+    }
+  }
+}
+impl<'a> flatbuffers::Follow<'a> for CompressionAlgorithm {
+  type Inner = Self;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    let b = flatbuffers::read_scalar_at::<i8>(buf, loc);
+    Self(b)
+  }
+}
+
+impl flatbuffers::Push for CompressionAlgorithm {
+    type Output = CompressionAlgorithm;
+    #[inline]
+    unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+        flatbuffers::emplace_scalar::<i8>(dst, self.0);
+    }
+}
+
+impl flatbuffers::EndianScalar for CompressionAlgorithm {
+  type Scalar = i8;
+  #[inline]
+  fn to_little_endian(self) -> i8 {
+    self.0.to_le()
+  }
+  #[inline]
+  #[allow(clippy::wrong_self_convention)]
+  fn from_little_endian(v: i8) -> Self {
+    let b = i8::from_le(v);
+    Self(b)
+  }
+}
+
+impl<'a> flatbuffers::Verifiable for CompressionAlgorithm {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    i8::run_verifier(v, pos)
+  }
+}
+
+impl flatbuffers::SimpleToVerifyInSlice for CompressionAlgorithm {}
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MIN_SPAN_DIGEST_KIND: i8 = 0;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+pub const ENUM_MAX_SPAN_DIGEST_KIND: i8 = 1;
+#[deprecated(since = "2.0.0", note = "Use associated constants instead. This will no longer be generated in 2021.")]
+#[allow(non_upper_case_globals)]
+pub const ENUM_VALUES_SPAN_DIGEST_KIND: [SpanDigestKind; 2] = [
+  SpanDigestKind::Compressed,
+  SpanDigestKind::Uncompressed,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(transparent)]
+pub struct SpanDigestKind(pub i8);
+#[allow(non_upper_case_globals)]
+impl SpanDigestKind {
+  pub const Compressed: Self = Self(0);
+  pub const Uncompressed: Self = Self(1);
+
+  pub const ENUM_MIN: i8 = 0;
+  pub const ENUM_MAX: i8 = 1;
+  pub const ENUM_VALUES: &'static [Self] = &[
+    Self::Compressed,
+    Self::Uncompressed,
+  ];
+  /// Returns the variant's name or "" if unknown.
+  pub fn variant_name(self) -> Option<&'static str> {
+    match self {
+      Self::Compressed => Some("Compressed"),
+      Self::Uncompressed => Some("Uncompressed"),
+      _ => None,
+    }
+  }
+}
+impl core::fmt::Debug for SpanDigestKind {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    if let Some(name) = self.variant_name() {
+      f.write_str(name)
+    } else {
+      f.write_fmt(format_args!("<UNKNOWN {:?}>", self.0))
+    }
+  }
+}
+impl<'a> flatbuffers::Follow<'a> for SpanDigestKind {
+  type Inner = Self;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    let b = flatbuffers::read_scalar_at::<i8>(buf, loc);
+    Self(b)
+  }
+}
+
+impl flatbuffers::Push for SpanDigestKind {
+    type Output = SpanDigestKind;
+    #[inline]
+    unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+        flatbuffers::emplace_scalar::<i8>(dst, self.0);
+    }
+}
+
+impl flatbuffers::EndianScalar for SpanDigestKind {
+  type Scalar = i8;
+  #[inline]
+  fn to_little_endian(self) -> i8 {
+    self.0.to_le()
+  }
+  #[inline]
+  #[allow(clippy::wrong_self_convention)]
+  fn from_little_endian(v: i8) -> Self {
+    let b = i8::from_le(v);
+    Self(b)
+  }
+}
+
+impl<'a> flatbuffers::Verifiable for SpanDigestKind {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    i8::run_verifier(v, pos)
+  }
+}
+
+impl flatbuffers::SimpleToVerifyInSlice for SpanDigestKind {}
+pub enum XattrOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct Xattr<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for Xattr<'a> {
+  type Inner = Xattr<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> Xattr<'a> {
+  pub const VT_KEY: flatbuffers::VOffsetT = 4;
+  pub const VT_VALUE: flatbuffers::VOffsetT = 6;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    Xattr { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args XattrArgs<'args>
+  ) -> flatbuffers::WIPOffset<Xattr<'bldr>> {
+    let mut builder = XattrBuilder::new(_fbb);
+    if let Some(x) = args.value { builder.add_value(x); }
+    if let Some(x) = args.key { builder.add_key(x); }
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn key(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(Xattr::VT_KEY, None)}
+  }
+  #[inline]
+  pub fn value(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(Xattr::VT_VALUE, None)}
+  }
+}
+
+impl flatbuffers::Verifiable for Xattr<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("key", Self::VT_KEY, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("value", Self::VT_VALUE, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct XattrArgs<'a> {
+    pub key: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub value: Option<flatbuffers::WIPOffset<&'a str>>,
+}
+impl<'a> Default for XattrArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    XattrArgs {
+      key: None,
+      value: None,
+    }
+  }
+}
+
+pub struct XattrBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> XattrBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_key(&mut self, key: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(Xattr::VT_KEY, key);
+  }
+  #[inline]
+  pub fn add_value(&mut self, value: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(Xattr::VT_VALUE, value);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> XattrBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    XattrBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<Xattr<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for Xattr<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("Xattr");
+      ds.field("key", &self.key());
+      ds.field("value", &self.value());
+      ds.finish()
+  }
+}
+pub enum SparseSegmentOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct SparseSegment<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for SparseSegment<'a> {
+  type Inner = SparseSegment<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> SparseSegment<'a> {
+  pub const VT_OFFSET: flatbuffers::VOffsetT = 4;
+  pub const VT_LENGTH: flatbuffers::VOffsetT = 6;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    SparseSegment { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args SparseSegmentArgs
+  ) -> flatbuffers::WIPOffset<SparseSegment<'bldr>> {
+    let mut builder = SparseSegmentBuilder::new(_fbb);
+    builder.add_length(args.length);
+    builder.add_offset(args.offset);
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn offset(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(SparseSegment::VT_OFFSET, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn length(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(SparseSegment::VT_LENGTH, Some(0)).unwrap()}
+  }
+}
+
+impl flatbuffers::Verifiable for SparseSegment<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<i64>("offset", Self::VT_OFFSET, false)?
+     .visit_field::<i64>("length", Self::VT_LENGTH, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct SparseSegmentArgs {
+    pub offset: i64,
+    pub length: i64,
+}
+impl<'a> Default for SparseSegmentArgs {
+  #[inline]
+  fn default() -> Self {
+    SparseSegmentArgs {
+      offset: 0,
+      length: 0,
+    }
+  }
+}
+
+pub struct SparseSegmentBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> SparseSegmentBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_offset(&mut self, offset: i64) {
+    self.fbb_.push_slot::<i64>(SparseSegment::VT_OFFSET, offset, 0);
+  }
+  #[inline]
+  pub fn add_length(&mut self, length: i64) {
+    self.fbb_.push_slot::<i64>(SparseSegment::VT_LENGTH, length, 0);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> SparseSegmentBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    SparseSegmentBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<SparseSegment<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for SparseSegment<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("SparseSegment");
+      ds.field("offset", &self.offset());
+      ds.field("length", &self.length());
+      ds.finish()
+  }
+}
+pub enum FileMetadataOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct FileMetadata<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for FileMetadata<'a> {
+  type Inner = FileMetadata<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> FileMetadata<'a> {
+  pub const VT_NAME: flatbuffers::VOffsetT = 4;
+  pub const VT_TYPE_: flatbuffers::VOffsetT = 6;
+  pub const VT_UNCOMPRESSED_OFFSET: flatbuffers::VOffsetT = 8;
+  pub const VT_UNCOMPRESSED_SIZE: flatbuffers::VOffsetT = 10;
+  pub const VT_LINKNAME: flatbuffers::VOffsetT = 12;
+  pub const VT_MODE: flatbuffers::VOffsetT = 14;
+  pub const VT_UID: flatbuffers::VOffsetT = 16;
+  pub const VT_GID: flatbuffers::VOffsetT = 18;
+  pub const VT_UNAME: flatbuffers::VOffsetT = 20;
+  pub const VT_GNAME: flatbuffers::VOffsetT = 22;
+  pub const VT_MOD_TIME: flatbuffers::VOffsetT = 24;
+  pub const VT_DEVMAJOR: flatbuffers::VOffsetT = 26;
+  pub const VT_DEVMINOR: flatbuffers::VOffsetT = 28;
+  pub const VT_XATTRS: flatbuffers::VOffsetT = 30;
+  pub const VT_COMPRESSED_SPAN_OFFSET: flatbuffers::VOffsetT = 32;
+  pub const VT_SPARSE: flatbuffers::VOffsetT = 34;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    FileMetadata { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args FileMetadataArgs<'args>
+  ) -> flatbuffers::WIPOffset<FileMetadata<'bldr>> {
+    let mut builder = FileMetadataBuilder::new(_fbb);
+    builder.add_compressed_span_offset(args.compressed_span_offset);
+    builder.add_devminor(args.devminor);
+    builder.add_devmajor(args.devmajor);
+    builder.add_mode(args.mode);
+    builder.add_uncompressed_size(args.uncompressed_size);
+    builder.add_uncompressed_offset(args.uncompressed_offset);
+    if let Some(x) = args.sparse { builder.add_sparse(x); }
+    if let Some(x) = args.xattrs { builder.add_xattrs(x); }
+    if let Some(x) = args.mod_time { builder.add_mod_time(x); }
+    if let Some(x) = args.gname { builder.add_gname(x); }
+    if let Some(x) = args.uname { builder.add_uname(x); }
+    builder.add_gid(args.gid);
+    builder.add_uid(args.uid);
+    if let Some(x) = args.linkname { builder.add_linkname(x); }
+    if let Some(x) = args.type_ { builder.add_type_(x); }
+    if let Some(x) = args.name { builder.add_name(x); }
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn name(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(FileMetadata::VT_NAME, None)}
+  }
+  #[inline]
+  pub fn type_(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(FileMetadata::VT_TYPE_, None)}
+  }
+  #[inline]
+  pub fn uncompressed_offset(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(FileMetadata::VT_UNCOMPRESSED_OFFSET, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn uncompressed_size(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(FileMetadata::VT_UNCOMPRESSED_SIZE, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn linkname(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(FileMetadata::VT_LINKNAME, None)}
+  }
+  #[inline]
+  pub fn mode(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(FileMetadata::VT_MODE, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn uid(&self) -> u32 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u32>(FileMetadata::VT_UID, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn gid(&self) -> u32 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<u32>(FileMetadata::VT_GID, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn uname(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(FileMetadata::VT_UNAME, None)}
+  }
+  #[inline]
+  pub fn gname(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(FileMetadata::VT_GNAME, None)}
+  }
+  #[inline]
+  pub fn mod_time(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(FileMetadata::VT_MOD_TIME, None)}
+  }
+  #[inline]
+  pub fn devmajor(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(FileMetadata::VT_DEVMAJOR, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn devminor(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(FileMetadata::VT_DEVMINOR, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn xattrs(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<Xattr<'a>>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<Xattr<'a>>>>>(FileMetadata::VT_XATTRS, None)}
+  }
+  #[inline]
+  pub fn compressed_span_offset(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(FileMetadata::VT_COMPRESSED_SPAN_OFFSET, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn sparse(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<SparseSegment<'a>>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<SparseSegment<'a>>>>>(FileMetadata::VT_SPARSE, None)}
+  }
+}
+
+impl flatbuffers::Verifiable for FileMetadata<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("name", Self::VT_NAME, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("type_", Self::VT_TYPE_, false)?
+     .visit_field::<i64>("uncompressed_offset", Self::VT_UNCOMPRESSED_OFFSET, false)?
+     .visit_field::<i64>("uncompressed_size", Self::VT_UNCOMPRESSED_SIZE, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("linkname", Self::VT_LINKNAME, false)?
+     .visit_field::<i64>("mode", Self::VT_MODE, false)?
+     .visit_field::<u32>("uid", Self::VT_UID, false)?
+     .visit_field::<u32>("gid", Self::VT_GID, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("uname", Self::VT_UNAME, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("gname", Self::VT_GNAME, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("mod_time", Self::VT_MOD_TIME, false)?
+     .visit_field::<i64>("devmajor", Self::VT_DEVMAJOR, false)?
+     .visit_field::<i64>("devminor", Self::VT_DEVMINOR, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<Xattr>>>>("xattrs", Self::VT_XATTRS, false)?
+     .visit_field::<i64>("compressed_span_offset", Self::VT_COMPRESSED_SPAN_OFFSET, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<SparseSegment>>>>("sparse", Self::VT_SPARSE, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct FileMetadataArgs<'a> {
+    pub name: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub type_: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub uncompressed_offset: i64,
+    pub uncompressed_size: i64,
+    pub linkname: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub mode: i64,
+    pub uid: u32,
+    pub gid: u32,
+    pub uname: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub gname: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub mod_time: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub devmajor: i64,
+    pub devminor: i64,
+    pub xattrs: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<Xattr<'a>>>>>,
+    pub compressed_span_offset: i64,
+    pub sparse: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<SparseSegment<'a>>>>>,
+}
+impl<'a> Default for FileMetadataArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    FileMetadataArgs {
+      name: None,
+      type_: None,
+      uncompressed_offset: 0,
+      uncompressed_size: 0,
+      linkname: None,
+      mode: 0,
+      uid: 0,
+      gid: 0,
+      uname: None,
+      gname: None,
+      mod_time: None,
+      devmajor: 0,
+      devminor: 0,
+      xattrs: None,
+      compressed_span_offset: 0,
+      sparse: None,
+    }
+  }
+}
+
+pub struct FileMetadataBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> FileMetadataBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_name(&mut self, name: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(FileMetadata::VT_NAME, name);
+  }
+  #[inline]
+  pub fn add_type_(&mut self, type_: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(FileMetadata::VT_TYPE_, type_);
+  }
+  #[inline]
+  pub fn add_uncompressed_offset(&mut self, uncompressed_offset: i64) {
+    self.fbb_.push_slot::<i64>(FileMetadata::VT_UNCOMPRESSED_OFFSET, uncompressed_offset, 0);
+  }
+  #[inline]
+  pub fn add_uncompressed_size(&mut self, uncompressed_size: i64) {
+    self.fbb_.push_slot::<i64>(FileMetadata::VT_UNCOMPRESSED_SIZE, uncompressed_size, 0);
+  }
+  #[inline]
+  pub fn add_linkname(&mut self, linkname: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(FileMetadata::VT_LINKNAME, linkname);
+  }
+  #[inline]
+  pub fn add_mode(&mut self, mode: i64) {
+    self.fbb_.push_slot::<i64>(FileMetadata::VT_MODE, mode, 0);
+  }
+  #[inline]
+  pub fn add_uid(&mut self, uid: u32) {
+    self.fbb_.push_slot::<u32>(FileMetadata::VT_UID, uid, 0);
+  }
+  #[inline]
+  pub fn add_gid(&mut self, gid: u32) {
+    self.fbb_.push_slot::<u32>(FileMetadata::VT_GID, gid, 0);
+  }
+  #[inline]
+  pub fn add_uname(&mut self, uname: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(FileMetadata::VT_UNAME, uname);
+  }
+  #[inline]
+  pub fn add_gname(&mut self, gname: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(FileMetadata::VT_GNAME, gname);
+  }
+  #[inline]
+  pub fn add_mod_time(&mut self, mod_time: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(FileMetadata::VT_MOD_TIME, mod_time);
+  }
+  #[inline]
+  pub fn add_devmajor(&mut self, devmajor: i64) {
+    self.fbb_.push_slot::<i64>(FileMetadata::VT_DEVMAJOR, devmajor, 0);
+  }
+  #[inline]
+  pub fn add_devminor(&mut self, devminor: i64) {
+    self.fbb_.push_slot::<i64>(FileMetadata::VT_DEVMINOR, devminor, 0);
+  }
+  #[inline]
+  pub fn add_xattrs(&mut self, xattrs: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<Xattr<'b >>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(FileMetadata::VT_XATTRS, xattrs);
+  }
+  #[inline]
+  pub fn add_compressed_span_offset(&mut self, compressed_span_offset: i64) {
+    self.fbb_.push_slot::<i64>(FileMetadata::VT_COMPRESSED_SPAN_OFFSET, compressed_span_offset, 0);
+  }
+  #[inline]
+  pub fn add_sparse(&mut self, sparse: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<SparseSegment<'b >>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(FileMetadata::VT_SPARSE, sparse);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> FileMetadataBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    FileMetadataBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<FileMetadata<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for FileMetadata<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("FileMetadata");
+      ds.field("name", &self.name());
+      ds.field("type_", &self.type_());
+      ds.field("uncompressed_offset", &self.uncompressed_offset());
+      ds.field("uncompressed_size", &self.uncompressed_size());
+      ds.field("linkname", &self.linkname());
+      ds.field("mode", &self.mode());
+      ds.field("uid", &self.uid());
+      ds.field("gid", &self.gid());
+      ds.field("uname", &self.uname());
+      ds.field("gname", &self.gname());
+      ds.field("mod_time", &self.mod_time());
+      ds.field("devmajor", &self.devmajor());
+      ds.field("devminor", &self.devminor());
+      ds.field("xattrs", &self.xattrs());
+      ds.field("compressed_span_offset", &self.compressed_span_offset());
+      ds.field("sparse", &self.sparse());
+      ds.finish()
+  }
+}
+pub enum SpanDigestListOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct SpanDigestList<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for SpanDigestList<'a> {
+  type Inner = SpanDigestList<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> SpanDigestList<'a> {
+  pub const VT_ALGORITHM: flatbuffers::VOffsetT = 4;
+  pub const VT_DIGESTS: flatbuffers::VOffsetT = 6;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    SpanDigestList { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args SpanDigestListArgs<'args>
+  ) -> flatbuffers::WIPOffset<SpanDigestList<'bldr>> {
+    let mut builder = SpanDigestListBuilder::new(_fbb);
+    if let Some(x) = args.digests { builder.add_digests(x); }
+    if let Some(x) = args.algorithm { builder.add_algorithm(x); }
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn algorithm(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(SpanDigestList::VT_ALGORITHM, None)}
+  }
+  #[inline]
+  pub fn digests(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>(SpanDigestList::VT_DIGESTS, None)}
+  }
+}
+
+impl flatbuffers::Verifiable for SpanDigestList<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("algorithm", Self::VT_ALGORITHM, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<&'_ str>>>>("digests", Self::VT_DIGESTS, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct SpanDigestListArgs<'a> {
+    pub algorithm: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub digests: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>,
+}
+impl<'a> Default for SpanDigestListArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    SpanDigestListArgs {
+      algorithm: None,
+      digests: None,
+    }
+  }
+}
+
+pub struct SpanDigestListBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> SpanDigestListBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_algorithm(&mut self, algorithm: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(SpanDigestList::VT_ALGORITHM, algorithm);
+  }
+  #[inline]
+  pub fn add_digests(&mut self, digests: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<&'b  str>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(SpanDigestList::VT_DIGESTS, digests);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> SpanDigestListBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    SpanDigestListBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<SpanDigestList<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for SpanDigestList<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("SpanDigestList");
+      ds.field("algorithm", &self.algorithm());
+      ds.field("digests", &self.digests());
+      ds.finish()
+  }
+}
+pub enum CompressionInfoOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct CompressionInfo<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for CompressionInfo<'a> {
+  type Inner = CompressionInfo<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> CompressionInfo<'a> {
+  pub const VT_COMPRESSION_ALGORITHM: flatbuffers::VOffsetT = 4;
+  pub const VT_MAX_SPAN_ID: flatbuffers::VOffsetT = 6;
+  pub const VT_SPAN_DIGESTS: flatbuffers::VOffsetT = 8;
+  pub const VT_CHECKPOINTS: flatbuffers::VOffsetT = 10;
+  pub const VT_EXTRA_SPAN_DIGESTS: flatbuffers::VOffsetT = 12;
+  pub const VT_SPAN_DIGEST_KIND: flatbuffers::VOffsetT = 14;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    CompressionInfo { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args CompressionInfoArgs<'args>
+  ) -> flatbuffers::WIPOffset<CompressionInfo<'bldr>> {
+    let mut builder = CompressionInfoBuilder::new(_fbb);
+    if let Some(x) = args.extra_span_digests { builder.add_extra_span_digests(x); }
+    if let Some(x) = args.checkpoints { builder.add_checkpoints(x); }
+    if let Some(x) = args.span_digests { builder.add_span_digests(x); }
+    builder.add_max_span_id(args.max_span_id);
+    builder.add_span_digest_kind(args.span_digest_kind);
+    builder.add_compression_algorithm(args.compression_algorithm);
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn compression_algorithm(&self) -> CompressionAlgorithm {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<CompressionAlgorithm>(CompressionInfo::VT_COMPRESSION_ALGORITHM, Some(CompressionAlgorithm::Gzip)).unwrap()}
+  }
+  #[inline]
+  pub fn max_span_id(&self) -> i32 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i32>(CompressionInfo::VT_MAX_SPAN_ID, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn span_digests(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>(CompressionInfo::VT_SPAN_DIGESTS, None)}
+  }
+  #[inline]
+  pub fn checkpoints(&self) -> Option<flatbuffers::Vector<'a, u8>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u8>>>(CompressionInfo::VT_CHECKPOINTS, None)}
+  }
+  #[inline]
+  pub fn extra_span_digests(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<SpanDigestList<'a>>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<SpanDigestList<'a>>>>>(CompressionInfo::VT_EXTRA_SPAN_DIGESTS, None)}
+  }
+  #[inline]
+  pub fn span_digest_kind(&self) -> SpanDigestKind {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<SpanDigestKind>(CompressionInfo::VT_SPAN_DIGEST_KIND, Some(SpanDigestKind::Compressed)).unwrap()}
+  }
+}
+
+impl flatbuffers::Verifiable for CompressionInfo<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<CompressionAlgorithm>("compression_algorithm", Self::VT_COMPRESSION_ALGORITHM, false)?
+     .visit_field::<i32>("max_span_id", Self::VT_MAX_SPAN_ID, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<&'_ str>>>>("span_digests", Self::VT_SPAN_DIGESTS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u8>>>("checkpoints", Self::VT_CHECKPOINTS, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<SpanDigestList>>>>("extra_span_digests", Self::VT_EXTRA_SPAN_DIGESTS, false)?
+     .visit_field::<SpanDigestKind>("span_digest_kind", Self::VT_SPAN_DIGEST_KIND, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct CompressionInfoArgs<'a> {
+    pub compression_algorithm: CompressionAlgorithm,
+    pub max_span_id: i32,
+    pub span_digests: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<&'a str>>>>,
+    pub checkpoints: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u8>>>,
+    pub extra_span_digests: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<SpanDigestList<'a>>>>>,
+    pub span_digest_kind: SpanDigestKind,
+}
+impl<'a> Default for CompressionInfoArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    CompressionInfoArgs {
+      compression_algorithm: CompressionAlgorithm::Gzip,
+      max_span_id: 0,
+      span_digests: None,
+      checkpoints: None,
+      extra_span_digests: None,
+      span_digest_kind: SpanDigestKind::Compressed,
+    }
+  }
+}
+
+pub struct CompressionInfoBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> CompressionInfoBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_compression_algorithm(&mut self, compression_algorithm: CompressionAlgorithm) {
+    self.fbb_.push_slot::<CompressionAlgorithm>(CompressionInfo::VT_COMPRESSION_ALGORITHM, compression_algorithm, CompressionAlgorithm::Gzip);
+  }
+  #[inline]
+  pub fn add_max_span_id(&mut self, max_span_id: i32) {
+    self.fbb_.push_slot::<i32>(CompressionInfo::VT_MAX_SPAN_ID, max_span_id, 0);
+  }
+  #[inline]
+  pub fn add_span_digests(&mut self, span_digests: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<&'b  str>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(CompressionInfo::VT_SPAN_DIGESTS, span_digests);
+  }
+  #[inline]
+  pub fn add_checkpoints(&mut self, checkpoints: flatbuffers::WIPOffset<flatbuffers::Vector<'b , u8>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(CompressionInfo::VT_CHECKPOINTS, checkpoints);
+  }
+  #[inline]
+  pub fn add_extra_span_digests(&mut self, extra_span_digests: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<SpanDigestList<'b >>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(CompressionInfo::VT_EXTRA_SPAN_DIGESTS, extra_span_digests);
+  }
+  #[inline]
+  pub fn add_span_digest_kind(&mut self, span_digest_kind: SpanDigestKind) {
+    self.fbb_.push_slot::<SpanDigestKind>(CompressionInfo::VT_SPAN_DIGEST_KIND, span_digest_kind, SpanDigestKind::Compressed);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> CompressionInfoBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    CompressionInfoBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<CompressionInfo<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for CompressionInfo<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("CompressionInfo");
+      ds.field("compression_algorithm", &self.compression_algorithm());
+      ds.field("max_span_id", &self.max_span_id());
+      ds.field("span_digests", &self.span_digests());
+      ds.field("checkpoints", &self.checkpoints());
+      ds.field("extra_span_digests", &self.extra_span_digests());
+      ds.field("span_digest_kind", &self.span_digest_kind());
+      ds.finish()
+  }
+}
+pub enum AnnotationOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct Annotation<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for Annotation<'a> {
+  type Inner = Annotation<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> Annotation<'a> {
+  pub const VT_KEY: flatbuffers::VOffsetT = 4;
+  pub const VT_VALUE: flatbuffers::VOffsetT = 6;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    Annotation { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args AnnotationArgs<'args>
+  ) -> flatbuffers::WIPOffset<Annotation<'bldr>> {
+    let mut builder = AnnotationBuilder::new(_fbb);
+    if let Some(x) = args.value { builder.add_value(x); }
+    if let Some(x) = args.key { builder.add_key(x); }
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn key(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(Annotation::VT_KEY, None)}
+  }
+  #[inline]
+  pub fn value(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(Annotation::VT_VALUE, None)}
+  }
+}
+
+impl flatbuffers::Verifiable for Annotation<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("key", Self::VT_KEY, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("value", Self::VT_VALUE, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct AnnotationArgs<'a> {
+    pub key: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub value: Option<flatbuffers::WIPOffset<&'a str>>,
+}
+impl<'a> Default for AnnotationArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    AnnotationArgs {
+      key: None,
+      value: None,
+    }
+  }
+}
+
+pub struct AnnotationBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> AnnotationBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_key(&mut self, key: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(Annotation::VT_KEY, key);
+  }
+  #[inline]
+  pub fn add_value(&mut self, value: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(Annotation::VT_VALUE, value);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> AnnotationBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    AnnotationBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<Annotation<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for Annotation<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("Annotation");
+      ds.field("key", &self.key());
+      ds.field("value", &self.value());
+      ds.finish()
+  }
+}
+pub enum TOCOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct TOC<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for TOC<'a> {
+  type Inner = TOC<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> TOC<'a> {
+  pub const VT_METADATA: flatbuffers::VOffsetT = 4;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    TOC { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args TOCArgs<'args>
+  ) -> flatbuffers::WIPOffset<TOC<'bldr>> {
+    let mut builder = TOCBuilder::new(_fbb);
+    if let Some(x) = args.metadata { builder.add_metadata(x); }
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn metadata(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<FileMetadata<'a>>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<FileMetadata<'a>>>>>(TOC::VT_METADATA, None)}
+  }
+}
+
+impl flatbuffers::Verifiable for TOC<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<FileMetadata>>>>("metadata", Self::VT_METADATA, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct TOCArgs<'a> {
+    pub metadata: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<FileMetadata<'a>>>>>,
+}
+impl<'a> Default for TOCArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    TOCArgs {
+      metadata: None,
+    }
+  }
+}
+
+pub struct TOCBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> TOCBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_metadata(&mut self, metadata: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<FileMetadata<'b >>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(TOC::VT_METADATA, metadata);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> TOCBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    TOCBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<TOC<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for TOC<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("TOC");
+      ds.field("metadata", &self.metadata());
+      ds.finish()
+  }
+}
+pub enum ZtocOffset {}
+#[derive(Copy, Clone, PartialEq)]
+
+pub struct Ztoc<'a> {
+  pub _tab: flatbuffers::Table<'a>,
+}
+
+impl<'a> flatbuffers::Follow<'a> for Ztoc<'a> {
+  type Inner = Ztoc<'a>;
+  #[inline]
+  unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+    Self { _tab: flatbuffers::Table::new(buf, loc) }
+  }
+}
+
+impl<'a> Ztoc<'a> {
+  pub const VT_VERSION: flatbuffers::VOffsetT = 4;
+  pub const VT_BUILD_TOOL_IDENTIFIER: flatbuffers::VOffsetT = 6;
+  pub const VT_COMPRESSED_ARCHIVE_SIZE: flatbuffers::VOffsetT = 8;
+  pub const VT_UNCOMPRESSED_ARCHIVE_SIZE: flatbuffers::VOffsetT = 10;
+  pub const VT_TOC: flatbuffers::VOffsetT = 12;
+  pub const VT_COMPRESSION_INFO: flatbuffers::VOffsetT = 14;
+  pub const VT_ANNOTATIONS: flatbuffers::VOffsetT = 16;
+
+  #[inline]
+  pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
+    Ztoc { _tab: table }
+  }
+  #[allow(unused_mut)]
+  pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: flatbuffers::Allocator + 'bldr>(
+    _fbb: &'mut_bldr mut flatbuffers::FlatBufferBuilder<'bldr, A>,
+    args: &'args ZtocArgs<'args>
+  ) -> flatbuffers::WIPOffset<Ztoc<'bldr>> {
+    let mut builder = ZtocBuilder::new(_fbb);
+    builder.add_uncompressed_archive_size(args.uncompressed_archive_size);
+    builder.add_compressed_archive_size(args.compressed_archive_size);
+    if let Some(x) = args.annotations { builder.add_annotations(x); }
+    if let Some(x) = args.compression_info { builder.add_compression_info(x); }
+    if let Some(x) = args.toc { builder.add_toc(x); }
+    if let Some(x) = args.build_tool_identifier { builder.add_build_tool_identifier(x); }
+    if let Some(x) = args.version { builder.add_version(x); }
+    builder.finish()
+  }
+
+
+  #[inline]
+  pub fn version(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(Ztoc::VT_VERSION, None)}
+  }
+  #[inline]
+  pub fn build_tool_identifier(&self) -> Option<&'a str> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<&str>>(Ztoc::VT_BUILD_TOOL_IDENTIFIER, None)}
+  }
+  #[inline]
+  pub fn compressed_archive_size(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(Ztoc::VT_COMPRESSED_ARCHIVE_SIZE, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn uncompressed_archive_size(&self) -> i64 {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<i64>(Ztoc::VT_UNCOMPRESSED_ARCHIVE_SIZE, Some(0)).unwrap()}
+  }
+  #[inline]
+  pub fn toc(&self) -> Option<TOC<'a>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<TOC>>(Ztoc::VT_TOC, None)}
+  }
+  #[inline]
+  pub fn compression_info(&self) -> Option<CompressionInfo<'a>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<CompressionInfo>>(Ztoc::VT_COMPRESSION_INFO, None)}
+  }
+  #[inline]
+  pub fn annotations(&self) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<Annotation<'a>>>> {
+    // Safety:
+    // Created from valid Table for this object
+    // which contains a valid value in this slot
+    unsafe { self._tab.get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<Annotation<'a>>>>>(Ztoc::VT_ANNOTATIONS, None)}
+  }
+}
+
+impl flatbuffers::Verifiable for Ztoc<'_> {
+  #[inline]
+  fn run_verifier(
+    v: &mut flatbuffers::Verifier, pos: usize
+  ) -> Result<(), flatbuffers::InvalidFlatbuffer> {
+    use self::flatbuffers::Verifiable;
+    v.visit_table(pos)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("version", Self::VT_VERSION, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<&str>>("build_tool_identifier", Self::VT_BUILD_TOOL_IDENTIFIER, false)?
+     .visit_field::<i64>("compressed_archive_size", Self::VT_COMPRESSED_ARCHIVE_SIZE, false)?
+     .visit_field::<i64>("uncompressed_archive_size", Self::VT_UNCOMPRESSED_ARCHIVE_SIZE, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<TOC>>("toc", Self::VT_TOC, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<CompressionInfo>>("compression_info", Self::VT_COMPRESSION_INFO, false)?
+     .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<Annotation>>>>("annotations", Self::VT_ANNOTATIONS, false)?
+     .finish();
+    Ok(())
+  }
+}
+pub struct ZtocArgs<'a> {
+    pub version: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub build_tool_identifier: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub compressed_archive_size: i64,
+    pub uncompressed_archive_size: i64,
+    pub toc: Option<flatbuffers::WIPOffset<TOC<'a>>>,
+    pub compression_info: Option<flatbuffers::WIPOffset<CompressionInfo<'a>>>,
+    pub annotations: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<Annotation<'a>>>>>,
+}
+impl<'a> Default for ZtocArgs<'a> {
+  #[inline]
+  fn default() -> Self {
+    ZtocArgs {
+      version: None,
+      build_tool_identifier: None,
+      compressed_archive_size: 0,
+      uncompressed_archive_size: 0,
+      toc: None,
+      compression_info: None,
+      annotations: None,
+    }
+  }
+}
+
+pub struct ZtocBuilder<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> {
+  fbb_: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+  start_: flatbuffers::WIPOffset<flatbuffers::TableUnfinishedWIPOffset>,
+}
+impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> ZtocBuilder<'a, 'b, A> {
+  #[inline]
+  pub fn add_version(&mut self, version: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(Ztoc::VT_VERSION, version);
+  }
+  #[inline]
+  pub fn add_build_tool_identifier(&mut self, build_tool_identifier: flatbuffers::WIPOffset<&'b  str>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(Ztoc::VT_BUILD_TOOL_IDENTIFIER, build_tool_identifier);
+  }
+  #[inline]
+  pub fn add_compressed_archive_size(&mut self, compressed_archive_size: i64) {
+    self.fbb_.push_slot::<i64>(Ztoc::VT_COMPRESSED_ARCHIVE_SIZE, compressed_archive_size, 0);
+  }
+  #[inline]
+  pub fn add_uncompressed_archive_size(&mut self, uncompressed_archive_size: i64) {
+    self.fbb_.push_slot::<i64>(Ztoc::VT_UNCOMPRESSED_ARCHIVE_SIZE, uncompressed_archive_size, 0);
+  }
+  #[inline]
+  pub fn add_toc(&mut self, toc: flatbuffers::WIPOffset<TOC<'b >>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<TOC>>(Ztoc::VT_TOC, toc);
+  }
+  #[inline]
+  pub fn add_compression_info(&mut self, compression_info: flatbuffers::WIPOffset<CompressionInfo<'b >>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<CompressionInfo>>(Ztoc::VT_COMPRESSION_INFO, compression_info);
+  }
+  #[inline]
+  pub fn add_annotations(&mut self, annotations: flatbuffers::WIPOffset<flatbuffers::Vector<'b , flatbuffers::ForwardsUOffset<Annotation<'b >>>>) {
+    self.fbb_.push_slot_always::<flatbuffers::WIPOffset<_>>(Ztoc::VT_ANNOTATIONS, annotations);
+  }
+  #[inline]
+  pub fn new(_fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>) -> ZtocBuilder<'a, 'b, A> {
+    let start = _fbb.start_table();
+    ZtocBuilder {
+      fbb_: _fbb,
+      start_: start,
+    }
+  }
+  #[inline]
+  pub fn finish(self) -> flatbuffers::WIPOffset<Ztoc<'a>> {
+    let o = self.fbb_.end_table(self.start_);
+    flatbuffers::WIPOffset::new(o.value())
+  }
+}
+
+impl core::fmt::Debug for Ztoc<'_> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut ds = f.debug_struct("Ztoc");
+      ds.field("version", &self.version());
+      ds.field("build_tool_identifier", &self.build_tool_identifier());
+      ds.field("compressed_archive_size", &self.compressed_archive_size());
+      ds.field("uncompressed_archive_size", &self.uncompressed_archive_size());
+      ds.field("toc", &self.toc());
+      ds.field("compression_info", &self.compression_info());
+      ds.field("annotations", &self.annotations());
+      ds.finish()
+  }
+}
+#[inline]
+/// Verifies that a buffer of bytes contains a `Ztoc`
+/// and returns it.
+/// Note that verification is still experimental and may not
+/// catch every error, or be maximally performant. For the
+/// previous, unchecked, behavior use
+/// `root_as_ztoc_unchecked`.
+pub fn root_as_ztoc(buf: &[u8]) -> Result<Ztoc, flatbuffers::InvalidFlatbuffer> {
+  flatbuffers::root::<Ztoc>(buf)
+}
+#[inline]
+/// Verifies that a buffer of bytes contains a size prefixed
+/// `Ztoc` and returns it.
+/// Note that verification is still experimental and may not
+/// catch every error, or be maximally performant. For the
+/// previous, unchecked, behavior use
+/// `size_prefixed_root_as_ztoc_unchecked`.
+pub fn size_prefixed_root_as_ztoc(buf: &[u8]) -> Result<Ztoc, flatbuffers::InvalidFlatbuffer> {
+  flatbuffers::size_prefixed_root::<Ztoc>(buf)
+}
+#[inline]
+/// Verifies, with the given options, that a buffer of bytes
+/// contains a `Ztoc` and returns it.
+/// Note that verification is still experimental and may not
+/// catch every error, or be maximally performant. For the
+/// previous, unchecked, behavior use
+/// `root_as_ztoc_unchecked`.
+pub fn root_as_ztoc_with_opts<'b, 'o>(
+  opts: &'o flatbuffers::VerifierOptions,
+  buf: &'b [u8],
+) -> Result<Ztoc<'b>, flatbuffers::InvalidFlatbuffer> {
+  flatbuffers::root_with_opts::<Ztoc<'b>>(opts, buf)
+}
+#[inline]
+/// Verifies, with the given verifier options, that a buffer of
+/// bytes contains a size prefixed `Ztoc` and returns
+/// it. Note that verification is still experimental and may not
+/// catch every error, or be maximally performant. For the
+/// previous, unchecked, behavior use
+/// `root_as_ztoc_unchecked`.
+pub fn size_prefixed_root_as_ztoc_with_opts<'b, 'o>(
+  opts: &'o flatbuffers::VerifierOptions,
+  buf: &'b [u8],
+) -> Result<Ztoc<'b>, flatbuffers::InvalidFlatbuffer> {
+  flatbuffers::size_prefixed_root_with_opts::<Ztoc<'b>>(opts, buf)
+}
+#[inline]
+/// Assumes, without verification, that a buffer of bytes contains a Ztoc and returns it.
+/// # Safety
+/// Callers must trust the given bytes do indeed contain a valid `Ztoc`.
+pub unsafe fn root_as_ztoc_unchecked(buf: &[u8]) -> Ztoc {
+  flatbuffers::root_unchecked::<Ztoc>(buf)
+}
+#[inline]
+/// Assumes, without verification, that a buffer of bytes contains a size prefixed Ztoc and returns it.
+/// # Safety
+/// Callers must trust the given bytes do indeed contain a valid size prefixed `Ztoc`.
+pub unsafe fn size_prefixed_root_as_ztoc_unchecked(buf: &[u8]) -> Ztoc {
+  flatbuffers::size_prefixed_root_unchecked::<Ztoc>(buf)
+}
+#[inline]
+pub fn finish_ztoc_buffer<'a, 'b, A: flatbuffers::Allocator + 'a>(
+    fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
+    root: flatbuffers::WIPOffset<Ztoc<'a>>) {
+  fbb.finish(root, None);
+}
+
+#[inline]
+pub fn finish_size_prefixed_ztoc_buffer<'a, 'b, A: flatbuffers::Allocator + 'a>(fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>, root: flatbuffers::WIPOffset<Ztoc<'a>>) {
+  fbb.finish_size_prefixed(root, None);
+}
+}  // pub mod ztoc
+
@@ -0,0 +1,260 @@
+//! A JSON rendering of a [`ZToc`] for inspecting it by eye or with tools like `jq`.
+//!
+//! Checkpoint windows are left out, as they would dwarf everything else; each checkpoint is
+//! listed with its offsets and the length of its window only. Keys of xattrs and annotations are
+//! sorted, so the output is stable for the same ztoc.
+
+use std::{collections::HashMap, fmt::Write};
+
+use chrono::Utc;
+
+use crate::{
+    encode::entry_type_name,
+    error::ZtocError,
+    zinfo::CHECKPOINTS_HEADER_SIZE,
+    ztoc::{parse_checkpoints, FileMetadata, ZToc},
+};
+
+/// A JSON value.
+enum Json {
+    Null,
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+    /// An object with keys that aren't known up front, such as xattrs.
+    Map(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn string(value: impl Into<String>) -> Json {
+        Json::String(value.into())
+    }
+
+    fn optional<T>(value: Option<T>, f: impl FnOnce(T) -> Json) -> Json {
+        value.map_or(Json::Null, f)
+    }
+
+    fn map(map: &HashMap<String, String>) -> Json {
+        let mut entries: Vec<_> = map
+            .iter()
+            .map(|(key, value)| (key.clone(), Json::string(value)))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Json::Map(entries)
+    }
+
+    /// Writes the value indented by `indent` levels of two spaces.
+    fn write(&self, out: &mut String, indent: usize) {
+        fn members<'a, K: AsRef<str> + 'a>(
+            out: &mut String,
+            indent: usize,
+            (open, close): (char, char),
+            members: impl ExactSizeIterator<Item = (Option<K>, &'a Json)>,
+        ) {
+            if members.len() == 0 {
+                out.push(open);
+                out.push(close);
+                return;
+            }
+            out.push(open);
+            for (i, (key, value)) in members.enumerate() {
+                out.push_str(if i == 0 { "\n" } else { ",\n" });
+                out.push_str(&"  ".repeat(indent + 1));
+                if let Some(key) = key {
+                    write_string(out, key.as_ref());
+                    out.push_str(": ");
+                }
+                value.write(out, indent + 1);
+            }
+            out.push('\n');
+            out.push_str(&"  ".repeat(indent));
+            out.push(close);
+        }
+
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Number(value) => write!(out, "{}", value).unwrap(),
+            Json::String(value) => write_string(out, value),
+            Json::Array(values) => members(
+                out,
+                indent,
+                ('[', ']'),
+                values.iter().map(|value| (None::<&str>, value)),
+            ),
+            Json::Object(entries) => members(
+                out,
+                indent,
+                ('{', '}'),
+                entries.iter().map(|(key, value)| (Some(*key), value)),
+            ),
+            Json::Map(entries) => members(
+                out,
+                indent,
+                ('{', '}'),
+                entries
+                    .iter()
+                    .map(|(key, value)| (Some(key.as_str()), value)),
+            ),
+        }
+    }
+}
+
+/// Writes `value` as a JSON string, escaping quotes, backslashes and control characters.
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn entry_to_json(entry: &FileMetadata) -> Result<Json, ZtocError> {
+    Ok(Json::Object(vec![
+        ("name", Json::string(entry.name.to_string_lossy())),
+        (
+            "type",
+            Json::string(entry_type_name(&entry.name, entry.r#type)?),
+        ),
+        (
+            "uncompressed_offset",
+            Json::Number(entry.uncompressed_offset.0),
+        ),
+        ("uncompressed_size", Json::Number(entry.uncompressed_size.0)),
+        (
+            "link_name",
+            Json::optional(entry.link_name.as_ref(), |link| {
+                Json::string(link.to_string_lossy())
+            }),
+        ),
+        ("mode", Json::Number(entry.mode.into())),
+        ("uid", Json::Number(entry.uid)),
+        ("gid", Json::Number(entry.gid)),
+        ("uname", Json::optional(entry.uname.as_ref(), Json::string)),
+        ("gname", Json::optional(entry.gname.as_ref(), Json::string)),
+        (
+            "mod_time",
+            Json::string(entry.mod_time.and_local_timezone(Utc).unwrap().to_rfc3339()),
+        ),
+        (
+            "dev_major",
+            Json::optional(entry.dev_major, |dev| Json::Number(dev.into())),
+        ),
+        (
+            "dev_minor",
+            Json::optional(entry.dev_minor, |dev| Json::Number(dev.into())),
+        ),
+        ("xattrs", Json::map(&entry.x_attrs)),
+    ]))
+}
+
+/// Renders `ztoc` as pretty-printed JSON: its sizes, every TOC entry and a summary of the
+/// checkpoints. Fails if the checkpoints blob can't be parsed.
+pub fn to_json(ztoc: &ZToc) -> Result<String, ZtocError> {
+    let info = &ztoc.compression_info;
+    let checkpoints = parse_checkpoints(&info.checkpoints)?;
+    let span_size = info
+        .checkpoints
+        .get(4..CHECKPOINTS_HEADER_SIZE)
+        .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()));
+    let json = Json::Object(vec![
+        ("version", Json::string(&ztoc.version)),
+        (
+            "build_tool_identifier",
+            Json::string(&ztoc.build_tool_identifier),
+        ),
+        (
+            "compressed_archive_size",
+            Json::Number(ztoc.compressed_achrive_size.0),
+        ),
+        (
+            "uncompressed_archive_size",
+            Json::Number(ztoc.uncompressed_archive_size.0),
+        ),
+        (
+            "files",
+            Json::Array(
+                ztoc.toc
+                    .metadata
+                    .iter()
+                    .map(entry_to_json)
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+        (
+            "checkpoints",
+            Json::Object(vec![
+                ("count", Json::Number(checkpoints.len() as u64)),
+                ("span_size", Json::Number(span_size)),
+                (
+                    "offsets",
+                    Json::Array(
+                        checkpoints
+                            .iter()
+                            .map(|checkpoint| {
+                                Json::Object(vec![
+                                    ("in", Json::Number(checkpoint.r#in as u64)),
+                                    ("out", Json::Number(checkpoint.out as u64)),
+                                    ("bits", Json::Number(checkpoint.bits.into())),
+                                    (
+                                        "window_length",
+                                        Json::Number(checkpoint.window.len() as u64),
+                                    ),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+            ]),
+        ),
+        (
+            "span_digests",
+            Json::Array(info.span_digests.iter().map(Json::string).collect()),
+        ),
+        ("annotations", Json::map(&ztoc.annotations)),
+    ]);
+    let mut out = String::new();
+    json.write(&mut out, 0);
+    out.push('\n');
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let ztoc = ZToc::new(&include_bytes!("testdata/test.tar.gz")[..]).unwrap();
+        let json = to_json(&ztoc).unwrap();
+        for name in ["src/", "src/zinfo.rs", "src/main.rs", "src/testdata/"] {
+            assert!(
+                json.contains(&format!("\"name\": \"{}\"", name)),
+                "{}",
+                name
+            );
+        }
+        assert!(json.starts_with("{\n  \"version\": \"0.9\",\n"));
+        assert!(json.contains("\"count\": 1,"));
+        assert!(json.contains("\"window_length\": 32768"));
+        assert!(json.contains("\"link_name\": null"));
+        assert!(json.contains("\"xattrs\": {}"));
+        // The windows themselves are left out.
+        assert!(json.len() < 32768);
+    }
+
+    #[test]
+    fn test_write_string() {
+        let mut out = String::new();
+        write_string(&mut out, "a \"b\"\\\n\u{1}é");
+        assert_eq!(out, r#""a \"b\"\\\n\u0001é""#);
+    }
+}
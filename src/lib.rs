@@ -0,0 +1,32 @@
+//! Builds soci ztocs for gzip-compressed tar layers: a table of contents of the layer's entries
+//! together with the checkpoints needed to decompress any of them without reading the whole layer.
+
+pub mod archive;
+pub mod decode;
+pub mod diff;
+pub mod encode;
+pub mod error;
+pub mod fetch;
+pub mod json;
+#[cfg(unix)]
+mod mmap;
+#[cfg(test)]
+mod testutil;
+pub mod verify;
+pub mod zinfo;
+pub mod zran;
+pub mod ztoc;
+
+#[allow(
+    non_snake_case,
+    unused_imports,
+    unknown_lints,
+    mismatched_lifetime_syntaxes,
+    clippy::all
+)]
+#[path = "../target/flatbuffers/ztoc_generated.rs"]
+pub mod ztoc_flatbuffers;
+
+pub use encode::encode_ztoc;
+pub use zinfo::{GZipCheckpoint, GzipZInfoDecompressor, ZInfo};
+pub use ztoc::ZToc;
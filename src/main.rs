@@ -1,16 +1,230 @@
-use std::io::{self, Cursor};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
 
-mod encode;
-mod zinfo;
-mod ztoc;
+use ztoc::{
+    encode, json,
+    zinfo::{self, CompressionAlgorithm},
+    ztoc::ZtocBuilder,
+    ZToc,
+};
 
-#[allow(non_snake_case, unused_imports, clippy::all)]
-#[path = "../target/flatbuffers/ztoc_generated.rs"]
-pub mod ztoc_flatbuffers;
+/// Capacity of the buffer in front of stdout.
+const OUTPUT_BUFFER_SIZE: usize = 1 << 16;
+
+const USAGE: &str = "usage: ztoc [--no-span-digests] [--span-size <bytes>] [--input <path>] \
+                     [--output <path>] [--json] [inspect --oneline]";
+
+/// Command line options. Input and output default to stdin and stdout.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Args {
+    no_span_digests: bool,
+    span_size: Option<usize>,
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    /// Write the ztoc as JSON instead of the encoded flatbuffer.
+    json: bool,
+    inspect: bool,
+}
+
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> io::Result<Args> {
+    let usage = || io::Error::new(io::ErrorKind::InvalidInput, USAGE);
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-span-digests" => parsed.no_span_digests = true,
+            "--span-size" => {
+                let size = args.next().ok_or_else(usage)?;
+                parsed.span_size = Some(parse_size(&size).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid span size {:?}", size),
+                    )
+                })?);
+            }
+            "--input" => parsed.input = Some(args.next().ok_or_else(usage)?.into()),
+            "--output" => parsed.output = Some(args.next().ok_or_else(usage)?.into()),
+            "--json" => parsed.json = true,
+            "inspect" if args.next().as_deref() == Some("--oneline") => parsed.inspect = true,
+            _ => return Err(usage()),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parses a size in bytes with an optional binary suffix, e.g. `1048576`, `4M` or `4MiB`.
+fn parse_size(size: &str) -> Option<usize> {
+    let digits = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(digits);
+    let shift = match unit {
+        "" | "B" => 0,
+        "K" | "KiB" => 10,
+        "M" | "MiB" => 20,
+        "G" | "GiB" => 30,
+        _ => return None,
+    };
+    number.parse::<usize>().ok()?.checked_mul(1 << shift)
+}
 
 fn main() -> io::Result<()> {
-    let ztoc = ztoc::ZToc::new(std::io::stdin())?;
-    let encoded = encode::encode_ztoc(&ztoc);
-    std::io::copy(&mut Cursor::new(encoded), &mut std::io::stdout())?;
-    Ok(())
+    let args = parse_args(std::env::args().skip(1))?;
+    let mut builder = ZToc::builder().span_digests(!args.no_span_digests);
+    if let Some(span_size) = args.span_size {
+        builder = builder.span_size(span_size);
+    }
+    let ztoc = match &args.input {
+        Some(path) => build(&builder, BufReader::new(File::open(path)?))?,
+        None => build(&builder, std::io::stdin().lock())?,
+    };
+    if args.inspect {
+        println!("{}", ztoc.describe());
+        return Ok(());
+    }
+    let write = |sink: &mut dyn Write| match args.json {
+        true => write_json(&ztoc, sink),
+        false => write_output(&ztoc, sink, OUTPUT_BUFFER_SIZE),
+    };
+    match &args.output {
+        Some(path) => write(&mut File::create(path)?),
+        None => write(&mut std::io::stdout().lock()),
+    }
+}
+
+/// Builds the ztoc of the layer in `reader`, after checking from its magic bytes that it isn't a
+/// compression the builder can't index, such as zstd.
+fn build<R: BufRead>(builder: &ZtocBuilder, mut reader: R) -> io::Result<ZToc> {
+    match zinfo::detect(&mut reader) {
+        Ok(algorithm @ CompressionAlgorithm::Zstd) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} compressed layers are not supported", algorithm.name()),
+        )),
+        // Magic bytes `detect` doesn't know, such as a zlib header, are left for the
+        // decompressor to recognize or reject.
+        Ok(CompressionAlgorithm::Gzip) => Ok(builder.build(reader)?),
+        Err(err) if err.kind() == io::ErrorKind::InvalidData => Ok(builder.build(reader)?),
+        Err(err) => Err(err),
+    }
+}
+
+/// Encodes `ztoc` to `sink` through a buffer of `capacity` bytes, flushing explicitly so write
+/// errors are reported instead of being dropped with the buffer.
+fn write_output<W: Write>(ztoc: &ZToc, sink: W, capacity: usize) -> io::Result<()> {
+    let mut writer = BufWriter::with_capacity(capacity, sink);
+    encode::encode_ztoc_to(ztoc, &mut writer)?;
+    writer.flush()
+}
+
+/// Writes `ztoc` to `sink` as JSON.
+fn write_json<W: Write>(ztoc: &ZToc, mut sink: W) -> io::Result<()> {
+    sink.write_all(json::to_json(ztoc)?.as_bytes())?;
+    sink.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1048576"), Some(1 << 20));
+        assert_eq!(parse_size("4M"), Some(4 << 20));
+        assert_eq!(parse_size("4MiB"), Some(4 << 20));
+        assert_eq!(parse_size("64K"), Some(64 << 10));
+        assert_eq!(parse_size("1G"), Some(1 << 30));
+        assert_eq!(parse_size("512B"), Some(512));
+        assert_eq!(parse_size("0"), Some(0));
+        for invalid in ["", "M", "4m", "4 M", "-1", "1.5M", "99999999999999999999G"] {
+            assert_eq!(parse_size(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parse = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(parse(&[]).unwrap(), Args::default());
+        assert_eq!(
+            parse(&[
+                "--span-size",
+                "1M",
+                "--input",
+                "layer.tar.gz",
+                "--output",
+                "layer.ztoc",
+                "--no-span-digests",
+                "--json",
+            ])
+            .unwrap(),
+            Args {
+                no_span_digests: true,
+                span_size: Some(1 << 20),
+                input: Some("layer.tar.gz".into()),
+                output: Some("layer.ztoc".into()),
+                json: true,
+                inspect: false,
+            }
+        );
+        assert!(parse(&["inspect", "--oneline"]).unwrap().inspect);
+
+        for invalid in [
+            &["--span-size"][..],
+            &["--span-size", "lots"],
+            &["--input"],
+            &["inspect"],
+            &["--verbose"],
+        ] {
+            assert!(parse(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_write_output() {
+        let ztoc = ZToc::new(&include_bytes!("testdata/test.tar.gz")[..]).unwrap();
+        let encoded = encode::encode_ztoc(&ztoc).unwrap();
+
+        let mut sink = Vec::new();
+        write_output(&ztoc, &mut sink, 16).unwrap();
+        assert_eq!(sink, encoded);
+    }
+
+    #[test]
+    fn test_write_json() {
+        let ztoc = ZToc::new(&include_bytes!("testdata/test.tar.gz")[..]).unwrap();
+        let mut sink = Vec::new();
+        write_json(&ztoc, &mut sink).unwrap();
+        let json = String::from_utf8(sink).unwrap();
+        assert!(json.contains("\"name\": \"src/main.rs\""), "{}", json);
+    }
+
+    #[test]
+    fn test_build() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let ztoc = build(&ZToc::builder(), layer).unwrap();
+        assert_eq!(ztoc.compressed_achrive_size.0, layer.len() as u64);
+
+        let zstd = &[0x28, 0xb5, 0x2f, 0xfd, 0x24, 0x05, 0x29, 0x00][..];
+        let err = build(&ZToc::builder(), zstd).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(err.to_string(), "zstd compressed layers are not supported");
+
+        // zlib-wrapped layers are indexed like gzip ones.
+        let tar = &include_bytes!("testdata/test.tar")[..];
+        let mut zlib = vec![0u8; unsafe { libz_sys::compressBound(tar.len() as _) } as usize];
+        let mut len = zlib.len() as _;
+        let status = unsafe {
+            libz_sys::compress(zlib.as_mut_ptr(), &mut len, tar.as_ptr(), tar.len() as _)
+        };
+        assert_eq!(status, libz_sys::Z_OK);
+        zlib.truncate(len as usize);
+        let ztoc = build(&ZToc::builder(), &zlib[..]).unwrap();
+        assert_eq!(ztoc.uncompressed_archive_size.0, tar.len() as u64);
+        assert_eq!(ztoc.compressed_achrive_size.0, zlib.len() as u64);
+
+        let err = build(&ZToc::builder(), &b"not compressed"[..]).unwrap_err();
+        assert_ne!(err.kind(), io::ErrorKind::Unsupported);
+    }
 }
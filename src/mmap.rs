@@ -0,0 +1,62 @@
+//! Read-only memory maps of local files, so a layer on disk can be indexed without copying it
+//! through read buffers.
+
+use std::{fs::File, io, ops::Deref, os::unix::io::AsRawFd, ptr, slice};
+
+use libc::{c_void, MAP_FAILED, MAP_PRIVATE, PROT_READ};
+
+/// A read-only mapping of a whole file. The file must not be truncated while it is mapped.
+pub(crate) struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Maps `file`. An empty file can't be mapped, so it yields an empty mapping without a call
+    /// to `mmap`.
+    pub(crate) fn map(file: &File) -> io::Result<Mmap> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file is too large to map"))?;
+        if len == 0 {
+            return Ok(Mmap {
+                ptr: ptr::null_mut(),
+                len,
+            });
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ,
+                MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap { ptr, len })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
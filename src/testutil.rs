@@ -0,0 +1,171 @@
+//! Helpers for building synthetic archives in tests.
+
+use std::{mem, ptr};
+
+use libc::c_int;
+use libz_sys::{
+    crc32, deflate, deflateEnd, deflateInit2_, z_stream, zlibVersion, Z_DEFAULT_COMPRESSION,
+    Z_DEFAULT_STRATEGY, Z_DEFLATED, Z_FINISH, Z_FULL_FLUSH, Z_OK, Z_STREAM_END,
+};
+
+use crate::zinfo::{zalloc, zfree};
+
+/// Compresses `data` into a single gzip member.
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    deflate_with(data, 31)
+}
+
+/// Compresses `data` with the given zlib `window_bits` (31 for gzip, -15 for raw deflate).
+pub fn deflate_with(data: &[u8], window_bits: c_int) -> Vec<u8> {
+    deflate_flushing(data, window_bits, None)
+}
+
+/// Compresses `data` into a single gzip member, resetting the compressor every `interval` bytes
+/// like `gzip --rsyncable`. This produces many small deflate blocks.
+pub fn gzip_rsyncable(data: &[u8], interval: usize) -> Vec<u8> {
+    deflate_flushing(data, 31, Some(interval))
+}
+
+/// Compresses `data` into a single gzip member whose deflate stream starts with `count` empty
+/// stored blocks, so the first blocks all end at uncompressed offset 0.
+pub fn gzip_with_empty_blocks(data: &[u8], count: usize) -> Vec<u8> {
+    let mut output = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    for _ in 0..count {
+        // A non-final stored block: three zero header bits padded to a byte, LEN and NLEN.
+        output.extend_from_slice(&[0, 0, 0, 0xff, 0xff]);
+    }
+    output.extend_from_slice(&deflate_with(data, -15));
+    let crc = unsafe { crc32(0, data.as_ptr(), data.len() as u32) };
+    output.extend_from_slice(&(crc as u32).to_le_bytes());
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    output
+}
+
+/// Compresses `data` into a single gzip member whose header carries the FEXTRA, FNAME,
+/// FCOMMENT and FHCRC fields. The deflate stream is the same as with [`gzip_with_empty_blocks`]
+/// and no empty blocks, so the two differ only in their header.
+pub fn gzip_with_header_fields(data: &[u8]) -> Vec<u8> {
+    const FHCRC: u8 = 2;
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+    let mut output = vec![
+        0x1f,
+        0x8b,
+        8,
+        FHCRC | FEXTRA | FNAME | FCOMMENT,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0xff,
+    ];
+    // A single extra subfield with a made-up ID.
+    let subfield = [b'Z', b't', 5, 0, 1, 2, 3, 4, 5];
+    output.extend_from_slice(&(subfield.len() as u16).to_le_bytes());
+    output.extend_from_slice(&subfield);
+    output.extend_from_slice(b"layer.tar\0");
+    output.extend_from_slice(b"a comment\0");
+    let header_crc = unsafe { crc32(0, output.as_ptr(), output.len() as u32) };
+    output.extend_from_slice(&(header_crc as u16).to_le_bytes());
+    output.extend_from_slice(&gzip_with_empty_blocks(data, 0)[10..]);
+    output
+}
+
+fn deflate_flushing(data: &[u8], window_bits: c_int, interval: Option<usize>) -> Vec<u8> {
+    let mut stream = Box::new(z_stream {
+        next_in: ptr::null_mut(),
+        avail_in: 0,
+        total_in: 0,
+        next_out: ptr::null_mut(),
+        avail_out: 0,
+        total_out: 0,
+        msg: ptr::null_mut(),
+        state: ptr::null_mut(),
+        opaque: ptr::null_mut(),
+        data_type: 0,
+        adler: 0,
+        reserved: 0,
+        zalloc,
+        zfree,
+    });
+    let mut input = data.to_vec();
+    let flushes = interval.map_or(0, |interval| data.len() / interval + 1);
+    let mut output = vec![0u8; data.len() + data.len() / 100 + 1024 + 16 * flushes];
+    unsafe {
+        assert_eq!(
+            deflateInit2_(
+                stream.as_mut(),
+                Z_DEFAULT_COMPRESSION,
+                Z_DEFLATED,
+                window_bits,
+                8,
+                Z_DEFAULT_STRATEGY,
+                zlibVersion(),
+                mem::size_of::<z_stream>() as c_int,
+            ),
+            Z_OK
+        );
+        stream.next_out = output.as_mut_ptr();
+        stream.avail_out = output.len() as u32;
+        if let Some(interval) = interval {
+            for chunk in input.chunks_mut(interval) {
+                stream.next_in = chunk.as_mut_ptr();
+                stream.avail_in = chunk.len() as u32;
+                assert_eq!(deflate(stream.as_mut(), Z_FULL_FLUSH), Z_OK);
+            }
+        } else {
+            stream.next_in = input.as_mut_ptr();
+            stream.avail_in = input.len() as u32;
+        }
+        assert_eq!(deflate(stream.as_mut(), Z_FINISH), Z_STREAM_END);
+        output.truncate(stream.total_out as usize);
+        deflateEnd(stream.as_mut());
+    }
+    output
+}
+
+/// Returns a GNU header with fixed ownership, mode and mtime.
+pub fn header(entry_type: tar::EntryType, size: u64) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(entry_type);
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_uid(1000);
+    header.set_gid(1000);
+    header.set_mtime(1_700_000_000);
+    header
+}
+
+/// Builds a tar archive from `(path, contents)` pairs of regular files.
+pub fn tar_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (path, contents) in files {
+        let mut header = header(tar::EntryType::Regular, contents.len() as u64);
+        builder.append_data(&mut header, path, *contents).unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+/// Generates `len` deterministic, incompressible bytes.
+pub fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+/// Generates `len` deterministic bytes that compress well but need back-references far into the
+/// preceding output to decompress.
+pub fn text_like(len: usize) -> Vec<u8> {
+    let words = random_bytes(len / 8, 7);
+    (0..len)
+        .map(|i| b'a' + (words[(i / 5) % words.len()] ^ (i / 997) as u8) % 26)
+        .collect()
+}
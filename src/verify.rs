@@ -0,0 +1,391 @@
+use std::{
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::ZtocError,
+    zinfo::{
+        checkpoint_for_offset, inflate_from_checkpoint, GZipCheckpoint, SpanDigestKind, ZInfo,
+        WINSIZE,
+    },
+    ztoc::{parse_checkpoints, ZToc},
+};
+
+/// Checks `ztoc` against the compressed `archive` it was built from. Every entry's data is
+/// decompressed from the checkpoint covering it, and every span is checked against its digest
+/// as with [`SpanVerifier`], in that order. Fails with the first entry that can't be read, as
+/// [`ZtocError::UnreadableEntry`], or the first span that doesn't match.
+///
+/// Entry contents are only checked to decompress to their recorded size, since the ztoc holds
+/// no per-file digests. Span digests are skipped if the ztoc was built without them.
+pub fn verify<R: Read + Seek>(ztoc: &ZToc, mut archive: R) -> Result<(), ZtocError> {
+    let info = &ztoc.compression_info;
+    info.validate_consistency()?;
+    let checkpoints = parse_checkpoints(&info.checkpoints)?;
+
+    for entry in &ztoc.toc.metadata {
+        // The archive only stores the data segments of a sparse file.
+        let stored = entry.stored_size() as usize;
+        if stored == 0 {
+            continue;
+        }
+        let unreadable = |reason: String| ZtocError::UnreadableEntry {
+            path: entry.name.clone(),
+            reason,
+        };
+        let offset = entry.uncompressed_offset.0 as usize;
+        let checkpoint = checkpoint_for_offset(&checkpoints, offset)
+            .map(|index| &checkpoints[index])
+            .ok_or_else(|| unreadable(format!("no checkpoint at or before offset {}", offset)))?;
+        let start = checkpoint.r#in - usize::from(checkpoint.bits > 0);
+        archive.seek(SeekFrom::Start(start as u64))?;
+        let data = inflate_from_checkpoint(&mut archive, checkpoint, offset, stored)
+            .map_err(|err| unreadable(err.to_string()))?;
+        if data.len() != stored {
+            return Err(unreadable(format!(
+                "the stream ended after {} of {} bytes",
+                data.len(),
+                stored
+            )));
+        }
+    }
+
+    if info.span_digests.is_empty() {
+        return Ok(());
+    }
+    let verifier = SpanVerifier::new(
+        checkpoints.iter().map(Into::into).collect(),
+        &info.span_digests,
+        info.span_digest_kind,
+        ztoc.compressed_achrive_size.0 as usize,
+        ztoc.uncompressed_archive_size.0 as usize,
+    );
+    for span in 0..checkpoints.len() {
+        verifier.verify_span(span, |range| {
+            archive.seek(SeekFrom::Start(range.start))?;
+            let mut compressed = vec![0u8; (range.end - range.start) as usize];
+            archive.read_exact(&mut compressed)?;
+            Ok(compressed)
+        })?;
+    }
+    Ok(())
+}
+
+/// A checkpoint to verify spans from. `window` is `None` when the checkpoint's window was not
+/// stored, in which case decompression has to start from an earlier checkpoint.
+#[derive(Debug, Clone, Copy)]
+pub struct SpanCheckpoint<'a> {
+    pub r#in: usize,
+    pub out: usize,
+    pub bits: u8,
+    pub window: Option<&'a [u8; WINSIZE]>,
+}
+
+impl<'a> From<&'a GZipCheckpoint> for SpanCheckpoint<'a> {
+    fn from(checkpoint: &'a GZipCheckpoint) -> Self {
+        SpanCheckpoint {
+            r#in: checkpoint.r#in,
+            out: checkpoint.out,
+            bits: checkpoint.bits,
+            window: Some(&checkpoint.window),
+        }
+    }
+}
+
+/// Verifies individual spans of a compressed archive against their SHA-256 span digests.
+///
+/// Each span is decompressed from its checkpoint to check the checkpoint is usable, and its
+/// digest is recomputed over the compressed or uncompressed bytes according to the digest kind.
+/// When a span's checkpoint has no window, decompression starts from the nearest preceding
+/// checkpoint that has one and runs forward through the span. That costs fetching and
+/// decompressing every span in between, so verifying a span `k` checkpoints past the last window
+/// is roughly `k + 1` times as expensive.
+#[derive(Debug)]
+pub struct SpanVerifier<'a> {
+    checkpoints: Vec<SpanCheckpoint<'a>>,
+    span_digests: &'a [String],
+    span_digest_kind: SpanDigestKind,
+    compressed_size: usize,
+    uncompressed_size: usize,
+}
+
+impl<'a> SpanVerifier<'a> {
+    pub fn new(
+        checkpoints: Vec<SpanCheckpoint<'a>>,
+        span_digests: &'a [String],
+        span_digest_kind: SpanDigestKind,
+        compressed_size: usize,
+        uncompressed_size: usize,
+    ) -> Self {
+        SpanVerifier {
+            checkpoints,
+            span_digests,
+            span_digest_kind,
+            compressed_size,
+            uncompressed_size,
+        }
+    }
+
+    /// Creates a verifier for the spans of `zinfo`, where every checkpoint has a window.
+    pub fn from_zinfo(zinfo: &'a ZInfo) -> Self {
+        SpanVerifier::new(
+            zinfo.checkpoints.iter().map(Into::into).collect(),
+            &zinfo.span_digests,
+            zinfo.span_digest_kind,
+            zinfo.total_in,
+            zinfo.total_out,
+        )
+    }
+
+    /// Verifies span `span`, using `fetch` to read the requested range of the compressed archive.
+    /// A span that ends at or before its checkpoint's compressed offset is rejected as
+    /// [`ZtocError::EmptySpan`] without fetching anything.
+    pub fn verify_span<F>(&self, span: usize, mut fetch: F) -> Result<(), ZtocError>
+    where
+        F: FnMut(Range<u64>) -> io::Result<Vec<u8>>,
+    {
+        let expected = self
+            .span_digests
+            .get(span)
+            .ok_or_else(|| ZtocError::InvalidZtoc(format!("no span digest for span {}", span)))?;
+        let checkpoint = &self.checkpoints[span];
+        let compressed_span = self.compressed_range(span);
+        if compressed_span.end <= checkpoint.r#in {
+            return Err(ZtocError::EmptySpan { span });
+        }
+
+        // The first checkpoint has no preceding output, so it never needs a window.
+        let empty_window = [0u8; WINSIZE];
+        let (start, window) = match (0..=span)
+            .rev()
+            .find_map(|i| Some((i, self.checkpoints[i].window?)))
+        {
+            Some(found) => found,
+            None if self.checkpoints[0].out == 0 => (0, &empty_window),
+            None => {
+                return Err(ZtocError::InvalidZtoc(format!(
+                    "no checkpoint with a window at or before span {}",
+                    span
+                )))
+            }
+        };
+        let start = &self.checkpoints[start];
+
+        let fetch_start = start.r#in - usize::from(start.bits > 0);
+        let compressed = fetch(fetch_start as u64..compressed_span.end as u64)?;
+
+        let uncompressed_span = checkpoint.out..self.uncompressed_end(span);
+        let resume = GZipCheckpoint {
+            r#in: start.r#in,
+            out: start.out,
+            bits: start.bits,
+            window: *window,
+        };
+        let uncompressed = inflate_from_checkpoint(
+            Cursor::new(&compressed),
+            &resume,
+            uncompressed_span.start,
+            uncompressed_span.len(),
+        )?;
+        if uncompressed.len() != uncompressed_span.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("span {} ended before its end", span),
+            )
+            .into());
+        }
+
+        let digested = match self.span_digest_kind {
+            SpanDigestKind::Compressed => {
+                &compressed[compressed_span.start - fetch_start..compressed_span.end - fetch_start]
+            }
+            SpanDigestKind::Uncompressed => &uncompressed[..],
+        };
+        let actual = format!("sha256:{:x}", Sha256::digest(digested));
+        if actual != *expected {
+            return Err(ZtocError::DigestMismatch {
+                span,
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// The compressed bytes covered by span `span`'s digest.
+    fn compressed_range(&self, span: usize) -> Range<usize> {
+        let checkpoint = &self.checkpoints[span];
+        let end = self
+            .checkpoints
+            .get(span + 1)
+            .map_or(self.compressed_size, |next| next.r#in);
+        checkpoint.r#in - usize::from(checkpoint.bits > 0)..end
+    }
+
+    fn uncompressed_end(&self, span: usize) -> usize {
+        self.checkpoints
+            .get(span + 1)
+            .map_or(self.uncompressed_size, |next| next.out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{testutil, zinfo::GzipZInfoDecompressor};
+
+    use super::*;
+
+    fn zinfo_of(layer: &[u8], kind: SpanDigestKind) -> ZInfo {
+        GzipZInfoDecompressor::new(layer, 1 << 16)
+            .unwrap()
+            .with_span_digest_kind(kind)
+            .finish()
+            .unwrap()
+    }
+
+    fn fetch(layer: &[u8]) -> impl FnMut(Range<u64>) -> io::Result<Vec<u8>> + '_ {
+        |range| Ok(layer[range.start as usize..range.end as usize].to_vec())
+    }
+
+    #[test]
+    fn test_verify_spans() {
+        let layer = &testutil::gzip(&testutil::text_like(1 << 20))[..];
+        for kind in [SpanDigestKind::Compressed, SpanDigestKind::Uncompressed] {
+            let zinfo = zinfo_of(layer, kind);
+            assert!(zinfo.checkpoints.len() > 2);
+            let verifier = SpanVerifier::from_zinfo(&zinfo);
+            for span in 0..zinfo.checkpoints.len() {
+                verifier.verify_span(span, fetch(layer)).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_ztoc() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let ztoc = ZToc::new(layer).unwrap();
+        verify(&ztoc, Cursor::new(layer)).unwrap();
+
+        let text = testutil::text_like(1 << 20);
+        let tar = testutil::tar_of(&[("text", &text), ("after", b"bye")]);
+        let layer = &testutil::gzip(&tar)[..];
+        let mut ztoc = ZToc::builder().span_size(1 << 16).build(layer).unwrap();
+        assert!(ztoc.compression_info.max_span_id > 1);
+        verify(&ztoc, Cursor::new(layer)).unwrap();
+
+        // A corrupted span either fails to decompress the file it holds or fails its digest.
+        let checkpoints = parse_checkpoints(&ztoc.compression_info.checkpoints).unwrap();
+        let mut corrupted = layer.to_vec();
+        corrupted[checkpoints[1].r#in + 100] ^= 0xff;
+        let err = verify(&ztoc, Cursor::new(&corrupted)).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                ZtocError::UnreadableEntry { path, .. } if path.as_os_str() == "text"
+            ) || matches!(err, ZtocError::DigestMismatch { span: 1, .. }),
+            "{}",
+            err
+        );
+
+        ztoc.compression_info.span_digests[2] = format!("sha256:{:x}", Sha256::digest(b""));
+        let err = verify(&ztoc, Cursor::new(layer)).unwrap_err();
+        assert!(
+            matches!(err, ZtocError::DigestMismatch { span: 2, .. }),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_verify_empty_span() {
+        let layer = &testutil::gzip(&testutil::text_like(1 << 20))[..];
+        let zinfo = zinfo_of(layer, SpanDigestKind::Compressed);
+        assert!(zinfo.checkpoints.len() > 3);
+
+        // Checkpoint 2 duplicates checkpoint 1's compressed offset, leaving span 1 empty.
+        let mut checkpoints: Vec<SpanCheckpoint> =
+            zinfo.checkpoints.iter().map(Into::into).collect();
+        checkpoints[2].r#in = checkpoints[1].r#in;
+        checkpoints[2].bits = checkpoints[1].bits;
+        let verifier = SpanVerifier::new(
+            checkpoints,
+            &zinfo.span_digests,
+            zinfo.span_digest_kind,
+            zinfo.total_in,
+            zinfo.total_out,
+        );
+        let err = verifier
+            .verify_span(1, |_| panic!("empty span was fetched"))
+            .unwrap_err();
+        assert!(matches!(err, ZtocError::EmptySpan { span: 1 }), "{}", err);
+        verifier.verify_span(0, fetch(layer)).unwrap();
+    }
+
+    #[test]
+    fn test_verify_windowless_span() {
+        let layer = &testutil::gzip(&testutil::text_like(1 << 20))[..];
+        let zinfo = zinfo_of(layer, SpanDigestKind::Uncompressed);
+        let span = 2;
+
+        // Without windows for spans 1 and 2, span 2 is decompressed from checkpoint 0.
+        let mut checkpoints: Vec<SpanCheckpoint> =
+            zinfo.checkpoints.iter().map(Into::into).collect();
+        checkpoints[1].window = None;
+        checkpoints[span].window = None;
+        let verifier = SpanVerifier::new(
+            checkpoints,
+            &zinfo.span_digests,
+            zinfo.span_digest_kind,
+            zinfo.total_in,
+            zinfo.total_out,
+        );
+        let mut fetched = Vec::new();
+        verifier
+            .verify_span(span, |range| {
+                fetched.push(range.clone());
+                fetch(layer)(range)
+            })
+            .unwrap();
+        assert_eq!(fetched[0].start, zinfo.checkpoints[0].r#in as u64);
+
+        // A corrupted span is still caught through the fallback path.
+        let mut corrupted = layer.to_vec();
+        let corrupt_at = zinfo.checkpoints[span].r#in + 100;
+        corrupted[corrupt_at] ^= 0xff;
+        let err = verifier.verify_span(span, fetch(&corrupted)).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ZtocError::DigestMismatch { span: 2, .. } | ZtocError::Io(_)
+            ),
+            "{}",
+            err
+        );
+
+        // Sanity check the fixture: the span really depends on data before it.
+        let mut decompressor = GzipZInfoDecompressor::new(layer, 1 << 16).unwrap();
+        let mut data = Vec::new();
+        decompressor.read_to_end(&mut data).unwrap();
+        let mut no_window = zinfo.checkpoints[span].window;
+        no_window.fill(0);
+        let checkpoint = &zinfo.checkpoints[span];
+        let resume = GZipCheckpoint {
+            r#in: checkpoint.r#in,
+            out: checkpoint.out,
+            bits: checkpoint.bits,
+            window: no_window,
+        };
+        let start = checkpoint.r#in - usize::from(checkpoint.bits > 0);
+        let end = zinfo.checkpoints[span + 1].out;
+        let without_window = inflate_from_checkpoint(
+            Cursor::new(&layer[start..]),
+            &resume,
+            checkpoint.out,
+            end - checkpoint.out,
+        );
+        assert!(without_window.map_or(true, |out| out != data[checkpoint.out..end]));
+    }
+}
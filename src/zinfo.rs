@@ -40,20 +40,31 @@
 use std::{
     alloc::{self, Layout},
     cmp,
+    collections::BTreeMap,
     ffi::CStr,
-    io::{self, Read, Result},
+    io::{self, BufRead, Read, Result, Seek, SeekFrom},
     mem, ptr,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
 
 use libc::{c_int, c_void};
 use libz_sys::{
-    inflate, inflateInit2_, uInt, z_stream, zlibVersion, Z_BLOCK, Z_BUF_ERROR, Z_DATA_ERROR,
-    Z_MEM_ERROR, Z_NEED_DICT, Z_STREAM_END, Z_STREAM_ERROR, Z_VERSION_ERROR,
+    compress2, compressBound, inflate, inflateInit2_, inflatePrime, inflateReset2,
+    inflateSetDictionary, uInt, uLong, uncompress, z_stream, zlibVersion, Z_BLOCK, Z_BUF_ERROR,
+    Z_DATA_ERROR, Z_DEFAULT_COMPRESSION, Z_MEM_ERROR, Z_NEED_DICT, Z_NO_FLUSH, Z_STREAM_END,
+    Z_STREAM_ERROR, Z_VERSION_ERROR,
+};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{
+    error::ZtocError,
+    ztoc::{compress_checkpoint_windows, plain_checkpoints, COMPRESSED_WINDOWS},
 };
-use sha2::{Digest, Sha256};
 
 // Since gzip is compressed with 32 KiB window size, WINDOW_SIZE is fixed
-const WINSIZE: usize = 32768;
+pub(crate) const WINSIZE: usize = 32768;
 const CHUNK: usize = 1 << 14;
 
 /// A checkpoint includes information about the current state of the decompressor at specific
@@ -77,18 +88,433 @@ impl std::fmt::Debug for GZipCheckpoint {
     }
 }
 
+impl GZipCheckpoint {
+    /// Checks that decompression resumes cleanly from this checkpoint: that with its window and
+    /// bit offset the compressed stream `archive` inflates without error up to the uncompressed
+    /// offset `next_checkpoint_out`, which is the next checkpoint's `out`, or the total size for
+    /// the last one. Catches a checkpoint recorded at the wrong bit or with the wrong window.
+    pub fn validate<R: Read + Seek>(
+        &self,
+        mut archive: R,
+        next_checkpoint_out: usize,
+    ) -> Result<()> {
+        if next_checkpoint_out <= self.out {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "next checkpoint at {} is not after checkpoint at {}",
+                    next_checkpoint_out, self.out
+                ),
+            ));
+        }
+        let start = self.r#in - usize::from(self.bits > 0);
+        archive.seek(SeekFrom::Start(start as u64))?;
+        // Everything before the last byte of the span is inflated and dropped along the way.
+        let mut last = [0u8];
+        if inflate_from_checkpoint_into(archive, self, next_checkpoint_out - 1, &mut last)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "stream ended before offset {} resuming from checkpoint at {}",
+                    next_checkpoint_out, self.out
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Checks what resuming from any of `checkpoints` relies on: a bit count below 8, a byte before
+/// `in` to prime the decompressor with when starting mid-byte, and offsets that strictly
+/// increase. Returns the index of the first checkpoint that breaks this, and how.
+pub(crate) fn check_checkpoint_offsets(
+    checkpoints: &[GZipCheckpoint],
+) -> std::result::Result<(), (usize, String)> {
+    for (index, checkpoint) in checkpoints.iter().enumerate() {
+        if checkpoint.bits >= 8 {
+            return Err((index, format!("invalid bit count {}", checkpoint.bits)));
+        }
+        if checkpoint.bits > 0 && checkpoint.r#in == 0 {
+            return Err((index, "starts mid-byte at offset 0".into()));
+        }
+        if let Some(previous) = index.checked_sub(1).map(|i| &checkpoints[i]) {
+            if checkpoint.r#in <= previous.r#in || checkpoint.out <= previous.out {
+                return Err((
+                    index,
+                    format!(
+                        "offsets in {} out {} do not follow in {} out {}",
+                        checkpoint.r#in, checkpoint.out, previous.r#in, previous.out
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the index of the last checkpoint at or before the uncompressed `offset`, which is the
+/// one to resume decompression from to read at `offset`. `checkpoints` must be sorted by `out`.
+pub fn checkpoint_for_offset(checkpoints: &[GZipCheckpoint], offset: usize) -> Option<usize> {
+    checkpoints
+        .partition_point(|checkpoint| checkpoint.out <= offset)
+        .checked_sub(1)
+}
+
+// Size of the serialized zinfo header: the checkpoint count and the span size.
+pub(crate) const CHECKPOINTS_HEADER_SIZE: usize = 4 + 8;
+// Size of a single serialized checkpoint: in, out, bits and the window.
+pub(crate) const CHECKPOINT_SIZE: usize = 8 + 8 + 1 + WINSIZE;
+
 /// Information about the compressed payload. Includes checkpoints which allow for quickly
 /// decompressing subets of the compressed payload.
 #[derive(Debug, PartialEq, Eq)]
 pub struct ZInfo {
     pub version: i32,
     pub checkpoints: Vec<GZipCheckpoint>,
+    /// SHA-256 digests of each span, as expected by the soci-snapshotter.
     pub span_digests: Vec<String>,
+    /// Whether the span digests cover the compressed or the uncompressed span data.
+    pub span_digest_kind: SpanDigestKind,
+    /// Digests of each compressed span computed with any additional algorithms, covering the same
+    /// byte ranges as `span_digests`.
+    pub extra_span_digests: BTreeMap<DigestAlgorithm, Vec<String>>,
+    /// SHA-256 digest of the whole uncompressed stream, if requested with
+    /// [`GzipZInfoDecompressor::with_uncompressed_digest`]. Only set once the end of the stream
+    /// has been reached.
+    pub uncompressed_digest: Option<String>,
     pub span_size: usize,
     pub total_in: usize,
     pub total_out: usize,
 }
 
+impl ZInfo {
+    /// Serializes the checkpoints in the zinfo layout soci stores as a ztoc's checkpoints blob:
+    ///
+    /// | field         | bytes | type  |
+    /// |---------------|-------|-------|
+    /// | count         | 4     | `u32` |
+    /// | span size     | 8     | `u64` |
+    /// | `in`          | 8     | `u64` |
+    /// | `out`         | 8     | `u64` |
+    /// | `bits`        | 1     | `u8`  |
+    /// | `window`      | 32768 |       |
+    ///
+    /// The checkpoint fields repeat `count` times. Integers are little-endian. Span digests and
+    /// the stream totals are not part of the layout.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(CHECKPOINTS_HEADER_SIZE + self.checkpoints.len() * CHECKPOINT_SIZE);
+        bytes.extend_from_slice(&(self.checkpoints.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.span_size as u64).to_le_bytes());
+        for checkpoint in &self.checkpoints {
+            bytes.extend_from_slice(&(checkpoint.r#in as u64).to_le_bytes());
+            bytes.extend_from_slice(&(checkpoint.out as u64).to_le_bytes());
+            bytes.push(checkpoint.bits);
+            bytes.extend_from_slice(&checkpoint.window);
+        }
+        bytes
+    }
+
+    /// Serializes the checkpoints like [`ZInfo::to_bytes`], but with every window
+    /// zlib-compressed in the layout of [`compress_checkpoint_windows`]. Windows usually compress
+    /// well, so this is typically several times smaller.
+    pub fn to_compressed_bytes(&self) -> Result<Vec<u8>> {
+        compress_checkpoint_windows(&self.to_bytes())
+    }
+
+    /// Parses checkpoints serialized by [`ZInfo::to_bytes`] or [`ZInfo::to_compressed_bytes`],
+    /// failing unless `bytes` holds exactly the declared number of checkpoints. As the layout
+    /// doesn't record them, span digests are left empty and `total_in` and `total_out` are the
+    /// offsets of the last checkpoint; set them if the stream's sizes are known.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ZInfo> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let plain;
+        let bytes = match bytes.get(..4) {
+            Some(count)
+                if u32::from_le_bytes(count.try_into().unwrap()) & COMPRESSED_WINDOWS != 0 =>
+            {
+                plain = plain_checkpoints(bytes)?;
+                &plain[..]
+            }
+            _ => bytes,
+        };
+        let (header, mut records) = bytes
+            .split_at_checked(CHECKPOINTS_HEADER_SIZE)
+            .ok_or_else(|| invalid("zinfo is missing its header".into()))?;
+        let count = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let span_size = u64::from_le_bytes(header[4..].try_into().unwrap());
+        if count.checked_mul(CHECKPOINT_SIZE) != Some(records.len()) {
+            return Err(invalid(format!(
+                "zinfo declares {} checkpoints but holds {} bytes of them",
+                count,
+                records.len()
+            )));
+        }
+        let to_usize = |value: u64| {
+            usize::try_from(value).map_err(|_| invalid(format!("offset {} is too large", value)))
+        };
+
+        let mut checkpoints = Vec::with_capacity(count);
+        while let Some((record, rest)) = records.split_at_checked(CHECKPOINT_SIZE) {
+            records = rest;
+            checkpoints.push(GZipCheckpoint {
+                r#in: to_usize(u64::from_le_bytes(record[..8].try_into().unwrap()))?,
+                out: to_usize(u64::from_le_bytes(record[8..16].try_into().unwrap()))?,
+                bits: record[16],
+                window: record[17..].try_into().unwrap(),
+            });
+        }
+        let (total_in, total_out) = checkpoints
+            .last()
+            .map_or((0, 0), |last| (last.r#in, last.out));
+        Ok(ZInfo {
+            version: 2,
+            checkpoints,
+            span_digests: Vec::new(),
+            span_digest_kind: SpanDigestKind::Compressed,
+            extra_span_digests: BTreeMap::new(),
+            uncompressed_digest: None,
+            span_size: to_usize(span_size)?,
+            total_in,
+            total_out,
+        })
+    }
+
+    /// Returns the last checkpoint at or before the uncompressed `offset`, the one to resume
+    /// decompression from to read at `offset`, or `None` if `offset` precedes every checkpoint.
+    pub fn checkpoint_for_offset(&self, offset: usize) -> Option<&GZipCheckpoint> {
+        self.span_index_for_offset(offset)
+            .map(|index| &self.checkpoints[index])
+    }
+
+    /// Returns the id of the span holding the uncompressed `offset`, which is also the index of
+    /// its digest in `span_digests`.
+    pub fn span_index_for_offset(&self, offset: usize) -> Option<usize> {
+        checkpoint_for_offset(&self.checkpoints, offset)
+    }
+
+    /// Checks that checkpoint offsets are strictly increasing and within the stream, that `bits`
+    /// is a valid bit count and that windows of checkpoints near the start of the stream don't
+    /// hold data from before it.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |index: usize, msg: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("checkpoint {}: {}", index, msg),
+            )
+        };
+        check_checkpoint_offsets(&self.checkpoints).map_err(|(index, msg)| invalid(index, msg))?;
+        for (index, checkpoint) in self.checkpoints.iter().enumerate() {
+            if checkpoint.r#in > self.total_in || checkpoint.out > self.total_out {
+                return Err(invalid(index, "offset past the end of the stream".into()));
+            }
+            // Only the last `out` bytes of the window can have been written.
+            let unwritten = WINSIZE.saturating_sub(checkpoint.out);
+            if checkpoint.window[..unwritten].iter().any(|&b| b != 0) {
+                return Err(invalid(
+                    index,
+                    "window holds data from before the stream".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An algorithm that span digests can be computed with.
+///
+/// This is meant to grow a `Blake3` variant, named `"blake3"` in `SpanDigestList.algorithm`,
+/// once the blake3 crate can be depended on. Until then SHA-512 is the only extra algorithm, and
+/// stands in for it as the second digest list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    /// Returns the name used to prefix digests, e.g. `sha256` in `sha256:<hex>`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "sha256",
+            DigestAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// Returns the algorithm with the given [`name`](Self::name), if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [DigestAlgorithm::Sha256, DigestAlgorithm::Sha512]
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+    }
+}
+
+/// A compression format a layer can be in, see [`detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionAlgorithm {
+    Gzip,
+    /// Recognized so callers can report it, but there is no zstd decompressor to index it with.
+    Zstd,
+}
+
+impl CompressionAlgorithm {
+    /// Returns the conventional lowercase name, e.g. `gzip`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionAlgorithm::Gzip => "gzip",
+            CompressionAlgorithm::Zstd => "zstd",
+        }
+    }
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Detects the compression of the stream in `reader` from its magic bytes, without consuming
+/// them. Only what `reader` buffers on its first fill is looked at, which is enough unless the
+/// underlying reader returns fewer than 4 bytes at a time.
+pub fn detect(reader: &mut impl BufRead) -> Result<CompressionAlgorithm> {
+    let magic = reader.fill_buf()?;
+    if magic.starts_with(GZIP_MAGIC) {
+        Ok(CompressionAlgorithm::Gzip)
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Ok(CompressionAlgorithm::Zstd)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "unknown compression, stream starts with {:02x?}",
+                &magic[..magic.len().min(ZSTD_MAGIC.len())]
+            ),
+        ))
+    }
+}
+
+/// The data that span digests are computed over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpanDigestKind {
+    /// The compressed bytes between consecutive checkpoints' `in` offsets. This is what the
+    /// soci-snapshotter verifies against.
+    #[default]
+    Compressed,
+    /// The decompressed bytes between consecutive checkpoints' `out` offsets, which does not
+    /// depend on how the layer was compressed.
+    Uncompressed,
+}
+
+/// An in-progress digest of a span.
+enum SpanHasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl SpanHasher {
+    fn new(algorithm: DigestAlgorithm) -> Self {
+        match algorithm {
+            DigestAlgorithm::Sha256 => SpanHasher::Sha256(Sha256::new()),
+            DigestAlgorithm::Sha512 => SpanHasher::Sha512(Sha512::new()),
+        }
+    }
+
+    fn algorithm(&self) -> DigestAlgorithm {
+        match self {
+            SpanHasher::Sha256(_) => DigestAlgorithm::Sha256,
+            SpanHasher::Sha512(_) => DigestAlgorithm::Sha512,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            SpanHasher::Sha256(hasher) => hasher.update(data),
+            SpanHasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            SpanHasher::Sha256(hasher) => Digest::reset(hasher),
+            SpanHasher::Sha512(hasher) => Digest::reset(hasher),
+        }
+    }
+
+    /// Returns the prefixed digest of the span so far and starts a new span.
+    fn finalize_reset(&mut self) -> String {
+        let name = self.algorithm().name();
+        match self {
+            SpanHasher::Sha256(hasher) => format!("{}:{:x}", name, hasher.finalize_reset()),
+            SpanHasher::Sha512(hasher) => format!("{}:{:x}", name, hasher.finalize_reset()),
+        }
+    }
+}
+
+/// Hashes finished spans on worker threads, see
+/// [`GzipZInfoDecompressor::with_parallel_digests`].
+struct DigestPool {
+    jobs: Option<mpsc::SyncSender<(usize, Vec<u8>)>>,
+    results: mpsc::Receiver<(usize, Vec<String>)>,
+    workers: Vec<thread::JoinHandle<()>>,
+    // The number of spans sent so far, which is the index of the next one.
+    spans: usize,
+}
+
+impl DigestPool {
+    fn new(threads: usize, algorithms: Vec<DigestAlgorithm>) -> Self {
+        // Bounds how many spans are buffered ahead of the workers, and so the memory they hold.
+        let (jobs, queue) = mpsc::sync_channel::<(usize, Vec<u8>)>(threads * 2);
+        let queue = Arc::new(Mutex::new(queue));
+        let (done, results) = mpsc::channel();
+        let workers = (0..threads)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let done = done.clone();
+                let algorithms = algorithms.clone();
+                thread::spawn(move || loop {
+                    // The lock is released before hashing, so the workers hash concurrently.
+                    let Ok((index, span)) = queue.lock().unwrap().recv() else {
+                        return;
+                    };
+                    let digests = algorithms
+                        .iter()
+                        .map(|&algorithm| {
+                            let mut hasher = SpanHasher::new(algorithm);
+                            hasher.update(&span);
+                            hasher.finalize_reset()
+                        })
+                        .collect();
+                    if done.send((index, digests)).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+        DigestPool {
+            jobs: Some(jobs),
+            results,
+            workers,
+            spans: 0,
+        }
+    }
+
+    fn send(&mut self, span: Vec<u8>) {
+        // Sending only fails if every worker panicked, which `join` reports.
+        let _ = self.jobs.as_ref().unwrap().send((self.spans, span));
+        self.spans += 1;
+    }
+
+    /// Waits for every span sent to be hashed and returns their digests in span order.
+    fn join(mut self) -> Vec<Vec<String>> {
+        drop(self.jobs.take());
+        for worker in self.workers.drain(..) {
+            if let Err(panic) = worker.join() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+        let mut digests: Vec<_> = self.results.try_iter().collect();
+        digests.sort_unstable_by_key(|&(index, _)| index);
+        digests.into_iter().map(|(_, digests)| digests).collect()
+    }
+}
+
 /// A wrapper around the underlying [`z_stream`].
 struct ZStream {
     stream: Box<z_stream>,
@@ -128,43 +554,86 @@ impl ZStream {
         Ok(Self { stream })
     }
 
-    /// Returns the amount of bytes available for the stream to read from the input buffer.
-    fn available_in(&self) -> u32 {
-        self.stream.avail_in
-    }
-
-    /// Returns the amount of bytes available for the stream to write to in the output buffer.
-    fn available_out(&self) -> u32 {
-        self.stream.avail_out
-    }
-
     /// Returns the current data type of the stream.
     fn data_type(&self) -> i32 {
         self.stream.data_type
     }
 
-    /// Sets the input buffer that the stream will read from.
-    // TODO: This is really sketchy, we are not following ownership rules properly...
-    unsafe fn next_in(&mut self, r#in: &mut [u8]) {
-        self.stream.avail_in = r#in.len() as u32;
-        self.stream.next_in = r#in.as_mut_ptr() as *mut u8;
+    /// Inflates the next part of the stream from `input` into `output`. The stream only points
+    /// into the two slices for the duration of the call, so they are free to change or go away
+    /// afterwards. Input that isn't consumed has to be passed again on the next call.
+    fn inflate(&mut self, input: &[u8], output: &mut [u8], flush: c_int) -> Result<Inflated> {
+        let avail_in = cmp::min(input.len(), uInt::MAX as usize) as uInt;
+        let avail_out = cmp::min(output.len(), uInt::MAX as usize) as uInt;
+        // inflate never writes through `next_in`, it is only declared mutable.
+        self.stream.next_in = input.as_ptr() as *mut u8;
+        self.stream.avail_in = avail_in;
+        self.stream.next_out = output.as_mut_ptr();
+        self.stream.avail_out = avail_out;
+        let ret = unsafe { inflate(self.stream.as_mut() as *mut z_stream, flush) };
+        let consumed = (avail_in - self.stream.avail_in) as usize;
+        let produced = (avail_out - self.stream.avail_out) as usize;
+        self.stream.next_in = ptr::null_mut();
+        self.stream.avail_in = 0;
+        self.stream.next_out = ptr::null_mut();
+        self.stream.avail_out = 0;
+        Ok(Inflated {
+            status: check_error(ret, Some(&self.stream))?,
+            consumed,
+            produced,
+        })
     }
 
-    /// Sets the output butter that the stream will write to.
-    // TODO: This is really sketchy, we are not following ownership rules properly...
-    unsafe fn next_out(&mut self, out: &mut [u8]) {
-        self.stream.avail_out = out.len() as u32;
-        self.stream.next_out = out.as_mut_ptr() as *mut u8;
+    /// Resets the stream to decompress a new stream of the format selected by `window_bits`,
+    /// keeping its allocated state.
+    fn reset(&mut self, window_bits: c_int) -> Result<()> {
+        check_error(
+            unsafe { inflateReset2(self.stream.as_mut() as *mut z_stream, window_bits) },
+            Some(&self.stream),
+        )?;
+        Ok(())
     }
 
-    /// Inflates the next part of the stream. Input will be read from the input buffer and output
-    /// will be placed into the output buffer.
-    fn inflate(&mut self, flush: c_int) -> Result<c_int> {
+    /// Inserts the low `bits` bits of `value` into the input stream, used to resume
+    /// decompression from a position that is not byte aligned.
+    fn prime(&mut self, bits: u8, value: u8) -> Result<()> {
         check_error(
-            unsafe { inflate(self.stream.as_mut() as *mut z_stream, flush) },
+            unsafe {
+                inflatePrime(
+                    self.stream.as_mut() as *mut z_stream,
+                    bits as c_int,
+                    value as c_int,
+                )
+            },
             Some(&self.stream),
-        )
+        )?;
+        Ok(())
     }
+
+    /// Sets the sliding window used to resolve back-references to data that came before the
+    /// point decompression is resumed from.
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<()> {
+        check_error(
+            unsafe {
+                inflateSetDictionary(
+                    self.stream.as_mut() as *mut z_stream,
+                    dictionary.as_ptr(),
+                    dictionary.len() as uInt,
+                )
+            },
+            Some(&self.stream),
+        )?;
+        Ok(())
+    }
+}
+
+/// The outcome of a successful [`ZStream::inflate`] call.
+struct Inflated {
+    status: c_int,
+    /// Bytes consumed from the input.
+    consumed: usize,
+    /// Bytes written to the output.
+    produced: usize,
 }
 
 impl Drop for ZStream {
@@ -175,7 +644,7 @@ impl Drop for ZStream {
     }
 }
 
-/// A helper to convert zlib errors into [`io::Error`]s.
+/// A helper to convert zlib errors into [`io::Error`]s wrapping a [`ZtocError::Zlib`].
 fn check_error(ret: c_int, stream: Option<&z_stream>) -> Result<c_int> {
     let msg = stream.and_then(|stream| {
         if !stream.msg.is_null() {
@@ -184,49 +653,194 @@ fn check_error(ret: c_int, stream: Option<&z_stream>) -> Result<c_int> {
             None
         }
     });
-    match ret {
-        Z_STREAM_ERROR => Err(io::Error::new(
-            io::ErrorKind::Other,
-            msg.unwrap_or_else(|| "zlib stream error".into()),
-        )),
-        Z_DATA_ERROR => Err(io::Error::new(
-            io::ErrorKind::Other,
-            msg.unwrap_or_else(|| "zlib data error".into()),
-        )),
-        Z_MEM_ERROR => Err(io::Error::new(
-            io::ErrorKind::Other,
-            msg.unwrap_or_else(|| "zlib mem error".into()),
-        )),
-        Z_BUF_ERROR => Err(io::Error::new(
-            io::ErrorKind::Other,
-            msg.unwrap_or_else(|| "zlib buf error".into()),
-        )),
-        Z_VERSION_ERROR => Err(io::Error::new(
-            io::ErrorKind::Other,
-            msg.unwrap_or_else(|| "zlib version error".into()),
-        )),
-        ret if ret < 0 => Err(io::Error::new(
-            io::ErrorKind::Other,
-            msg.unwrap_or_else(|| "zlib unknown error".into()),
-        )),
-        ret => Ok(ret),
+    let default = match ret {
+        ret if ret >= 0 => return Ok(ret),
+        Z_STREAM_ERROR => "zlib stream error",
+        Z_DATA_ERROR => "zlib data error",
+        Z_MEM_ERROR => "zlib mem error",
+        Z_BUF_ERROR => "zlib buf error",
+        Z_VERSION_ERROR => "zlib version error",
+        _ => "zlib unknown error",
+    };
+    Err(zlib_error(ret, msg.unwrap_or_else(|| default.into())))
+}
+
+fn zlib_error(code: c_int, message: String) -> io::Error {
+    ZtocError::Zlib { code, message }.into()
+}
+
+/// Compresses a checkpoint window into a zlib stream.
+pub(crate) fn compress_window(window: &[u8; WINSIZE]) -> Result<Vec<u8>> {
+    let mut len = unsafe { compressBound(WINSIZE as uLong) };
+    let mut compressed = vec![0u8; len as usize];
+    check_error(
+        unsafe {
+            compress2(
+                compressed.as_mut_ptr(),
+                &mut len,
+                window.as_ptr(),
+                WINSIZE as uLong,
+                Z_DEFAULT_COMPRESSION,
+            )
+        },
+        None,
+    )?;
+    compressed.truncate(len as usize);
+    Ok(compressed)
+}
+
+/// Decompresses a window compressed with [`compress_window`].
+pub(crate) fn decompress_window(compressed: &[u8]) -> Result<[u8; WINSIZE]> {
+    let mut window = [0u8; WINSIZE];
+    let mut len = WINSIZE as uLong;
+    check_error(
+        unsafe {
+            uncompress(
+                window.as_mut_ptr(),
+                &mut len,
+                compressed.as_ptr(),
+                compressed.len() as uLong,
+            )
+        },
+        None,
+    )?;
+    if len as usize != WINSIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "compressed window holds {} bytes instead of {}",
+                len, WINSIZE
+            ),
+        ));
+    }
+    Ok(window)
+}
+
+/// How a [`GzipZInfoDecompressor`] buffers the compressed input it reads from its source.
+pub trait InputBuffer<R> {
+    /// Returns the buffered input not consumed yet, reading more from `reader` if there is none.
+    /// An empty slice means the source is exhausted.
+    fn fill<'a>(&'a mut self, reader: &'a mut R) -> Result<&'a [u8]>;
+
+    /// Marks the first `amount` bytes returned by the last `fill` as consumed.
+    fn consume(&mut self, reader: &mut R, amount: usize);
+
+    /// Discards any buffered input, before reading from a new source.
+    fn clear(&mut self) {}
+}
+
+/// Reads the source in chunks into a buffer owned by the decompressor. Works with any reader.
+pub struct Chunked {
+    buf: [u8; CHUNK],
+    start: usize,
+    end: usize,
+}
+
+impl<R: Read> InputBuffer<R> for Chunked {
+    fn fill<'a>(&'a mut self, reader: &'a mut R) -> Result<&'a [u8]> {
+        if self.start == self.end {
+            self.end = reader.read(&mut self.buf)?;
+            self.start = 0;
+        }
+        Ok(&self.buf[self.start..self.end])
+    }
+
+    fn consume(&mut self, _: &mut R, amount: usize) {
+        self.start += amount;
+    }
+
+    fn clear(&mut self) {
+        self.start = 0;
+        self.end = 0;
+    }
+}
+
+/// Inflates directly out of the source's own buffer, for sources that already are [`BufRead`],
+/// so the compressed input isn't copied a second time.
+pub struct Direct;
+
+impl<R: BufRead> InputBuffer<R> for Direct {
+    fn fill<'a>(&'a mut self, reader: &'a mut R) -> Result<&'a [u8]> {
+        reader.fill_buf()
+    }
+
+    fn consume(&mut self, reader: &mut R, amount: usize) {
+        reader.consume(amount);
     }
 }
 
+/// Returns the zinfo of a stream nothing was read from yet.
+fn empty_zinfo(span_size: usize, window_bits: c_int) -> ZInfo {
+    let mut zinfo = ZInfo {
+        version: 2,
+        checkpoints: Vec::new(),
+        span_digests: Vec::new(),
+        span_digest_kind: SpanDigestKind::Compressed,
+        extra_span_digests: BTreeMap::new(),
+        uncompressed_digest: None,
+        span_size,
+        total_in: 0,
+        total_out: 0,
+    };
+    // zlib only stops at the first block boundary after a gzip or zlib header, so a raw stream
+    // gets its initial checkpoint up front.
+    if window_bits < 0 {
+        zinfo.checkpoints.push(GZipCheckpoint {
+            r#in: 0,
+            out: 0,
+            bits: 0,
+            window: [0u8; WINSIZE],
+        });
+    }
+    zinfo
+}
+
 /// A Gzip decompressor that also generates compression metadata which can be used to read
 /// parts of the compressed payload without needing to decompress everything.
-pub struct GzipZInfoDecompressor<R> {
+///
+/// By default the compressed input is read in chunks into an internal buffer. A source that
+/// is already buffered can be read through [`GzipZInfoDecompressor::from_buf_read`] instead,
+/// which yields the same checkpoints without the extra copy.
+pub struct GzipZInfoDecompressor<R, B = Chunked> {
     reader: R,
+    input: B,
 
     stream: ZStream,
     zinfo: ZInfo,
 
     window: RingBuffer<u8, WINSIZE>,
-    input: [u8; CHUNK],
-    input_size: usize,
     last_block: usize,
+    // Whether input was consumed since the last span digest was pushed, so one has to be pushed
+    // when the stream ends. Kept across reads, as the stream can end in a read that consumes no
+    // input.
+    pending_span: bool,
+
+    // The first hasher is always SHA-256 and produces `span_digests`.
+    hashers: Vec<SpanHasher>,
+    // With parallel digests, the data of the current span, which is handed to `digest_pool`
+    // instead of `hashers` once the span ends.
+    span_data: Option<Vec<u8>>,
+    digest_threads: usize,
+    digest_pool: Option<DigestPool>,
+    // Hashes all uncompressed output, if the uncompressed digest is enabled.
+    uncompressed_hasher: Option<Sha256>,
 
-    hasher: Sha256,
+    // Time spent inflating each finished span, and the span in progress, if timing is enabled.
+    span_durations: Option<Vec<Duration>>,
+    span_time: Duration,
+
+    // Called with `total_in` and `total_out` whenever a checkpoint is recorded.
+    progress: Option<Box<dyn FnMut(usize, usize) + Send>>,
+
+    // The last compressed bytes consumed, and the gzip ISIZE trailer they hold once the stream
+    // has ended.
+    tail: RingBuffer<u8, 4>,
+    isize: Option<u32>,
+    // Whether the stream is gzip, and so ends in an ISIZE trailer. With auto-detected window
+    // bits this is only known once the first byte has been consumed.
+    gzip: bool,
+    // The zlib window bits the stream was initialized with, to reset it with.
+    window_bits: c_int,
 }
 
 impl<R> GzipZInfoDecompressor<R>
@@ -236,139 +850,550 @@ where
     /// Creates a new Gzip zinfo Decompressor. The span size specifies the minimum size of a span
     /// recording in the zinfo.
     pub fn new(reader: R, span_size: usize) -> Result<Self> {
-        let stream = ZStream::new(47)?;
-        let zinfo = ZInfo {
-            version: 2,
-            checkpoints: Vec::new(),
-            span_digests: Vec::new(),
-            span_size,
-            total_in: 0,
-            total_out: 0,
+        GzipZInfoDecompressor::with_window_bits(reader, span_size, 47)
+    }
+
+    /// Like [`GzipZInfoDecompressor::new`], but with zlib's `window_bits` selecting the stream
+    /// format: 31 for gzip, 15 for zlib, 47 to detect either (the default) or -15 for a raw
+    /// deflate stream. Checkpoint windows are always 32 KiB, so any other window size fails.
+    pub fn with_window_bits(reader: R, span_size: usize, window_bits: i32) -> Result<Self> {
+        let input = Chunked {
+            buf: [0u8; CHUNK],
+            start: 0,
+            end: 0,
         };
+        GzipZInfoDecompressor::with_input(reader, input, span_size, window_bits)
+    }
+}
+
+impl<R> GzipZInfoDecompressor<R, Direct>
+where
+    R: BufRead,
+{
+    /// Like [`GzipZInfoDecompressor::new`], but inflates straight out of `reader`'s buffer.
+    pub fn from_buf_read(reader: R, span_size: usize) -> Result<Self> {
+        GzipZInfoDecompressor::with_input(reader, Direct, span_size, 47)
+    }
+}
+
+impl<R, B> GzipZInfoDecompressor<R, B>
+where
+    B: InputBuffer<R>,
+{
+    fn with_input(reader: R, input: B, span_size: usize, window_bits: i32) -> Result<Self> {
+        if !matches!(window_bits, -15 | 15 | 31 | 47) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "window bits {} do not select a {} byte window",
+                    window_bits, WINSIZE
+                ),
+            ));
+        }
+        let stream = ZStream::new(window_bits)?;
 
         Ok(Self {
             reader,
+            input,
             stream,
-            zinfo,
+            zinfo: empty_zinfo(span_size, window_bits),
             window: RingBuffer::new(),
-            input: [0u8; CHUNK],
-            input_size: 0,
             last_block: 0,
-            hasher: Sha256::new(),
+            pending_span: false,
+            hashers: vec![SpanHasher::new(DigestAlgorithm::Sha256)],
+            span_data: None,
+            digest_threads: 0,
+            digest_pool: None,
+            uncompressed_hasher: None,
+            span_durations: None,
+            progress: None,
+            span_time: Duration::ZERO,
+            tail: RingBuffer::new(),
+            isize: None,
+            gzip: window_bits > 15,
+            window_bits,
         })
     }
 
+    /// Starts over on a new compressed stream read from `reader`, keeping every setting and
+    /// reusing the inflate state and buffers. Returns the zinfo of the previous stream, which is
+    /// only complete if it was read to EOF.
+    pub fn reset(&mut self, reader: R) -> Result<ZInfo> {
+        self.stream.reset(self.window_bits)?;
+        self.collect_span_digests();
+        self.reader = reader;
+        self.input.clear();
+
+        let mut zinfo = empty_zinfo(self.zinfo.span_size, self.window_bits);
+        zinfo.span_digest_kind = self.zinfo.span_digest_kind;
+        zinfo.extra_span_digests = self
+            .zinfo
+            .extra_span_digests
+            .keys()
+            .map(|&algorithm| (algorithm, Vec::new()))
+            .collect();
+        self.window = RingBuffer::new();
+        self.last_block = 0;
+        self.pending_span = false;
+        self.hashers.iter_mut().for_each(SpanHasher::reset);
+        if let Some(span) = &mut self.span_data {
+            span.clear();
+        }
+        // The hasher is taken once the stream ends, leaving the digest to tell it was enabled.
+        if self.uncompressed_hasher.is_some() || self.zinfo.uncompressed_digest.is_some() {
+            self.uncompressed_hasher = Some(Sha256::new());
+        }
+        if let Some(durations) = &mut self.span_durations {
+            durations.clear();
+        }
+        self.span_time = Duration::ZERO;
+        self.tail = RingBuffer::new();
+        self.isize = None;
+        Ok(mem::replace(&mut self.zinfo, zinfo))
+    }
+
+    /// Additionally computes span digests with each of the given algorithms. SHA-256 digests are
+    /// always computed, so it is ignored here. Has no effect once span digests are disabled.
+    pub fn with_extra_digest_algorithms(mut self, algorithms: &[DigestAlgorithm]) -> Self {
+        if self.hashers.is_empty() {
+            return self;
+        }
+        for &algorithm in algorithms {
+            if self.hashers.iter().any(|h| h.algorithm() == algorithm) {
+                continue;
+            }
+            self.hashers.push(SpanHasher::new(algorithm));
+            self.zinfo.extra_span_digests.insert(algorithm, Vec::new());
+        }
+        self
+    }
+
     /// Consumes the decompressor to return the zinfo compression metadata. The index is only complete
     /// once EOF is reached.
-    pub fn into_zinfo(self) -> ZInfo {
+    pub fn into_zinfo(mut self) -> ZInfo {
+        self.collect_span_digests();
         self.zinfo
     }
+
+    /// Decompresses the rest of the stream, discarding the output, and returns the completed
+    /// zinfo.
+    pub fn finish(mut self) -> Result<ZInfo> {
+        let mut scratch = vec![0u8; CHUNK];
+        loop {
+            match self.read(&mut scratch) {
+                Ok(0) => return Ok(self.into_zinfo()),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Enables or disables computing span digests. Without them `span_digests` (and any extra
+    /// digests) stay empty, so spans can't be verified by digest.
+    pub fn with_span_digests(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.hashers.clear();
+            self.zinfo.extra_span_digests.clear();
+            self.span_data = None;
+        }
+        self
+    }
+
+    /// Hashes finished spans on `threads` worker threads rather than on the thread reading, so
+    /// digesting doesn't hold up inflating a large layer. Each span is buffered until it ends,
+    /// and the digests only show up in the zinfo, in span order, once it is taken with
+    /// [`Self::into_zinfo`] or [`Self::finish`]. 0, the default, hashes while reading.
+    pub fn with_parallel_digests(mut self, threads: usize) -> Self {
+        self.digest_threads = threads;
+        self.span_data = (threads > 0 && !self.hashers.is_empty()).then(Vec::new);
+        self
+    }
+
+    /// Enables or disables computing [`ZInfo::uncompressed_digest`] over all decompressed output.
+    /// It is off by default, since hashing every uncompressed byte is costly.
+    pub fn with_uncompressed_digest(mut self, enabled: bool) -> Self {
+        self.uncompressed_hasher = enabled.then(Sha256::new);
+        self
+    }
+
+    /// Records the wall-clock time spent inflating each span, see [`Self::span_durations`].
+    pub fn with_span_timing(mut self, enabled: bool) -> Self {
+        self.span_durations = enabled.then(Vec::new);
+        self
+    }
+
+    /// Calls `progress` with the compressed and uncompressed bytes processed so far each time a
+    /// checkpoint is recorded, e.g. to report progress on a large layer. With the default span
+    /// size that is every 4 MiB of output or so.
+    pub fn with_progress(mut self, progress: impl FnMut(usize, usize) + Send + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Returns the time spent inflating each span finished so far, if span timing is enabled.
+    /// Once EOF is reached there is one duration per span, in span order.
+    pub fn span_durations(&self) -> Option<&[Duration]> {
+        self.span_durations.as_deref()
+    }
+
+    /// Returns the uncompressed size modulo 2^32 recorded in the gzip trailer (ISIZE), once the
+    /// end of the stream has been reached. Always `None` for zlib and raw deflate streams,
+    /// including zlib streams read with auto-detected window bits.
+    pub fn gzip_isize(&self) -> Option<u32> {
+        self.isize
+    }
+
+    /// Returns whether the stream is gzip rather than zlib or raw deflate. With auto-detected
+    /// window bits this is only known once some input has been read.
+    pub(crate) fn is_gzip(&self) -> bool {
+        self.gzip
+    }
+
+    /// Returns the number of checkpoints recorded so far.
+    pub fn checkpoint_count(&self) -> usize {
+        self.zinfo.checkpoints.len()
+    }
+
+    /// Returns the size of the checkpoints recorded so far when serialized with
+    /// [`ZInfo::to_bytes`], which is the size of the checkpoints blob of a ztoc.
+    pub fn estimated_zinfo_bytes(&self) -> usize {
+        CHECKPOINTS_HEADER_SIZE + self.checkpoint_count() * CHECKPOINT_SIZE
+    }
+
+    /// Selects whether span digests are computed over compressed or uncompressed data.
+    pub fn with_span_digest_kind(mut self, kind: SpanDigestKind) -> Self {
+        self.zinfo.span_digest_kind = kind;
+        self
+    }
+
+    /// Finishes the current span, recording its digest for every algorithm and its duration.
+    fn push_span_digests(&mut self) {
+        self.pending_span = false;
+        if let Some(durations) = &mut self.span_durations {
+            durations.push(mem::take(&mut self.span_time));
+        }
+        if let Some(span) = &mut self.span_data {
+            let (threads, hashers) = (self.digest_threads, &self.hashers);
+            self.digest_pool
+                .get_or_insert_with(|| {
+                    DigestPool::new(threads, hashers.iter().map(SpanHasher::algorithm).collect())
+                })
+                .send(mem::take(span));
+            return;
+        }
+        let digests: Vec<_> = self
+            .hashers
+            .iter_mut()
+            .map(SpanHasher::finalize_reset)
+            .collect();
+        record_span_digests(&mut self.zinfo, &self.hashers, digests);
+    }
+
+    /// Waits for the digests of the spans handed to worker threads and records them.
+    fn collect_span_digests(&mut self) {
+        if let Some(pool) = self.digest_pool.take() {
+            for digests in pool.join() {
+                record_span_digests(&mut self.zinfo, &self.hashers, digests);
+            }
+        }
+    }
+}
+
+/// Records one span's digests, given in the order of `hashers`.
+fn record_span_digests(zinfo: &mut ZInfo, hashers: &[SpanHasher], digests: Vec<String>) {
+    for (i, (hasher, digest)) in hashers.iter().zip(digests).enumerate() {
+        if i == 0 {
+            zinfo.span_digests.push(digest);
+        } else {
+            zinfo
+                .extra_span_digests
+                .entry(hasher.algorithm())
+                .or_default()
+                .push(digest);
+        }
+    }
 }
 
-impl<R> Read for GzipZInfoDecompressor<R>
+impl<R, B> Read for GzipZInfoDecompressor<R, B>
 where
-    R: Read,
+    B: InputBuffer<R>,
 {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        unsafe {
-            self.stream.next_out(buf);
-        }
         // Total read tracks how many bytes have been placed into the buffer provided by the
         // caller.
         let mut total_read = 0;
 
-        // Pending span tracks if there is a span digest that needs to be pushed when the stream
-        // ends.
-        let mut pending_span = false;
-
-        while self.stream.available_out() > 0 {
-            if self.stream.available_in() == 0 {
-                let count = self.reader.read(&mut self.input)?;
-                self.input_size = count;
-                unsafe {
-                    self.stream.next_in(&mut self.input[..count]);
-                }
-            }
+        // Output from `window_start` onwards has not been copied into the sliding window yet.
+        // Copying is deferred until a checkpoint needs the window, since with many small blocks
+        // only the last `WINSIZE` bytes of a read ever matter.
+        let mut window_start = 0;
 
-            let input_start = self.input_size - self.stream.available_in() as usize;
+        while total_read < buf.len() {
+            let input = self.input.fill(&mut self.reader)?;
+            let input_eof = input.is_empty();
             let last_out = total_read;
 
-            let mut input_read = self.stream.available_in();
-            let mut output_read = self.stream.available_out();
-            let status = self.stream.inflate(Z_BLOCK)?;
-            input_read -= self.stream.available_in();
-            output_read -= self.stream.available_out();
+            let started = self.span_durations.is_some().then(Instant::now);
+            let result = self.stream.inflate(input, &mut buf[total_read..], Z_BLOCK);
+            if let Some(started) = started {
+                self.span_time += started.elapsed();
+            }
+            let Inflated {
+                status,
+                consumed: input_read,
+                produced: output_read,
+            } = match result {
+                // The compressed input ran out before the end of the stream. Hand back whatever
+                // was decompressed so far; the next read reports the truncation.
+                Err(_) if input_eof && total_read > 0 => {
+                    self.window.write(&buf[window_start..total_read]);
+                    return Ok(total_read);
+                }
+                Err(_) if input_eof => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "compressed stream ended unexpectedly",
+                    ))
+                }
+                inflated => inflated?,
+            };
 
-            self.zinfo.total_in += input_read as usize;
-            self.zinfo.total_out += output_read as usize;
-            total_read += output_read as usize;
+            let consumed = &input[..input_read];
+            // A zlib header's first byte has 8 in its low nibble, so it can't be gzip's 0x1f.
+            if self.window_bits == 47 && self.zinfo.total_in == 0 {
+                if let Some(&first) = consumed.first() {
+                    self.gzip = first == GZIP_MAGIC[0];
+                }
+            }
+            self.zinfo.total_in += consumed.len();
+            self.zinfo.total_out += output_read;
+            self.tail.write(consumed);
+            total_read += output_read;
             if input_read != 0 {
-                pending_span = true;
+                self.pending_span = true;
+            }
+            let digested = match self.zinfo.span_digest_kind {
+                SpanDigestKind::Compressed => consumed,
+                SpanDigestKind::Uncompressed => &buf[last_out..last_out + output_read],
+            };
+            match &mut self.span_data {
+                Some(span) => span.extend_from_slice(digested),
+                None => self.hashers.iter_mut().for_each(|h| h.update(digested)),
+            }
+            if let Some(hasher) = &mut self.uncompressed_hasher {
+                hasher.update(&buf[last_out..last_out + output_read]);
             }
-            self.hasher
-                .update(&self.input[input_start..input_start + input_read as usize]);
+            self.input.consume(&mut self.reader, input_read);
 
             if status == Z_NEED_DICT {
-                return Err(io::Error::new(io::ErrorKind::Other, "unexpected need dict"));
+                return Err(zlib_error(Z_NEED_DICT, "unexpected need dict".into()));
             }
             if status == Z_STREAM_END {
+                self.isize = self
+                    .gzip
+                    .then(|| u32::from_le_bytes(self.tail.read_contiguous()));
+                if let Some(hasher) = self.uncompressed_hasher.take() {
+                    self.zinfo.uncompressed_digest =
+                        Some(format!("sha256:{:x}", hasher.finalize()));
+                }
                 // Push last span digest, if there is one pending.
-                if pending_span {
-                    self.zinfo
-                        .span_digests
-                        .push(format!("sha256:{:x}", self.hasher.finalize_reset()));
+                if self.pending_span {
+                    self.push_span_digests();
                 }
                 return Ok(total_read);
             }
 
-            // Copy the read data into the sliding window.
-            self.window
-                .write(&buf[last_out..last_out + output_read as usize]);
-
             // 128 indicates end of block, 64 indicates end of stream.
-            if (self.stream.data_type() & 128) != 0
-                && (self.stream.data_type() & 64) == 0
+            let data_type = self.stream.data_type();
+            if (data_type & 128) != 0
+                && (data_type & 64) == 0
+                // The first block boundary always gets a checkpoint, at offset 0, whatever the
+                // span size.
                 && (self.zinfo.total_out == 0
                     || self.zinfo.total_out - self.last_block > self.zinfo.span_size)
+                // Empty blocks at the start of the stream would all end at offset 0. Keep only
+                // the first, so checkpoints stay strictly increasing in `out`.
+                && self
+                    .zinfo
+                    .checkpoints
+                    .last()
+                    .is_none_or(|last| last.out < self.zinfo.total_out)
             {
-                let unused_bits = (self.stream.data_type() & 7) as u8;
+                let unused_bits = (data_type & 7) as u8;
                 // Only push this after the first digest?
                 if !self.zinfo.checkpoints.is_empty() {
-                    pending_span = false;
-                    self.zinfo
-                        .span_digests
-                        .push(format!("sha256:{:x}", self.hasher.finalize_reset()));
+                    self.push_span_digests();
                 } else {
-                    self.hasher.reset();
+                    for hasher in &mut self.hashers {
+                        hasher.reset();
+                    }
+                    if let Some(span) = &mut self.span_data {
+                        span.clear();
+                    }
+                    self.span_time = Duration::ZERO;
                 }
                 // If we're staddling a byte from the input, we'll include the full byte
                 // in the next digest.
-                if unused_bits > 0 {
+                if unused_bits > 0 && self.zinfo.span_digest_kind == SpanDigestKind::Compressed {
                     // Not sure if this will happen in the wild.
-                    assert!(input_start + input_read as usize > 0);
-                    self.hasher.update(
-                        &self.input[input_start + input_read as usize - 1
-                            ..input_start + input_read as usize],
-                    );
+                    assert!(self.zinfo.total_in > 0);
+                    // The straddled byte is the last one consumed.
+                    let (left, right) = self.tail.read();
+                    let byte = *right.last().or(left.last()).unwrap();
+                    match &mut self.span_data {
+                        Some(span) => span.push(byte),
+                        None => self.hashers.iter_mut().for_each(|h| h.update(&[byte])),
+                    }
                 }
-                let mut checkpoint = GZipCheckpoint {
+                self.window.write(&buf[window_start..total_read]);
+                window_start = total_read;
+                self.zinfo.checkpoints.push(GZipCheckpoint {
                     bits: unused_bits,
                     r#in: self.zinfo.total_in,
                     out: self.zinfo.total_out,
-                    window: [0u8; WINSIZE],
-                };
-                let (left, right) = self.window.read();
-                checkpoint.window[..left.len()].copy_from_slice(left);
-                checkpoint.window[left.len()..].copy_from_slice(right);
-                self.zinfo.checkpoints.push(checkpoint);
+                    window: self.window.read_contiguous(),
+                });
                 self.last_block = self.zinfo.total_out;
+                if let Some(progress) = &mut self.progress {
+                    progress(self.zinfo.total_in, self.zinfo.total_out);
+                }
             }
         }
 
+        self.window.write(&buf[window_start..total_read]);
         Ok(total_read)
     }
 }
 
+/// Builds the zinfo of the whole gzip stream read from `reader`, for callers that only need the
+/// index and not the uncompressed data. See [`GzipZInfoDecompressor::new`] for `span_size`.
+pub fn build_zinfo<R: Read>(reader: R, span_size: usize) -> Result<ZInfo> {
+    GzipZInfoDecompressor::new(reader, span_size)?.finish()
+}
+
+/// Reads `len` bytes of the uncompressed stream starting at `offset`, resuming from the last
+/// checkpoint of `zinfo` at or before `offset`. `reader` is the compressed stream `zinfo` was
+/// built from. Fewer than `len` bytes are returned if the stream ends first.
+pub fn extract<R>(reader: R, zinfo: &ZInfo, offset: usize, len: usize) -> Result<Vec<u8>>
+where
+    R: Read + Seek,
+{
+    let mut out = vec![0u8; cmp::min(len, zinfo.total_out.saturating_sub(offset))];
+    let written = extract_into(reader, zinfo, offset, &mut out)?;
+    out.truncate(written);
+    Ok(out)
+}
+
+/// Like [`extract`], but decompresses into `buf` and returns the number of bytes written, so one
+/// buffer can be reused across many reads. As with [`Read::read`], fewer bytes are written if
+/// the stream ends first, and 0 once `offset` is at or past its end.
+pub fn extract_into<R>(mut reader: R, zinfo: &ZInfo, offset: usize, buf: &mut [u8]) -> Result<usize>
+where
+    R: Read + Seek,
+{
+    if offset >= zinfo.total_out || buf.is_empty() {
+        return Ok(0);
+    }
+    let checkpoint = zinfo.checkpoint_for_offset(offset).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no checkpoint at or before offset {}", offset),
+        )
+    })?;
+    let start = checkpoint.r#in - usize::from(checkpoint.bits > 0);
+    reader.seek(SeekFrom::Start(start as u64))?;
+    inflate_from_checkpoint_into(reader, checkpoint, offset, buf)
+}
+
+/// Decompresses up to `len` bytes starting at the uncompressed `offset` by resuming from
+/// `checkpoint`, which must be at or before `offset`. `input` must be positioned at the
+/// checkpoint's compressed offset, or one byte before it if the checkpoint starts mid-byte. Fewer
+/// than `len` bytes are returned if the deflate stream or `input` ends first.
+pub(crate) fn inflate_from_checkpoint<R>(
+    input: R,
+    checkpoint: &GZipCheckpoint,
+    offset: usize,
+    len: usize,
+) -> Result<Vec<u8>>
+where
+    R: Read,
+{
+    let mut out = vec![0u8; len];
+    let written = inflate_from_checkpoint_into(input, checkpoint, offset, &mut out)?;
+    out.truncate(written);
+    Ok(out)
+}
+
+/// Like [`inflate_from_checkpoint`], but decompresses into `out` and returns the number of bytes
+/// written.
+fn inflate_from_checkpoint_into<R>(
+    mut input: R,
+    checkpoint: &GZipCheckpoint,
+    offset: usize,
+    out: &mut [u8],
+) -> Result<usize>
+where
+    R: Read,
+{
+    assert!(checkpoint.out <= offset);
+
+    let mut stream = ZStream::new(-15)?;
+    if checkpoint.bits > 0 {
+        let mut byte = [0u8];
+        input.read_exact(&mut byte)?;
+        stream.prime(checkpoint.bits, byte[0] >> (8 - checkpoint.bits))?;
+    }
+    // Nothing precedes the start of the stream, so its window is empty.
+    if checkpoint.out > 0 {
+        stream.set_dictionary(&checkpoint.window)?;
+    }
+
+    let mut input_buf = [0u8; CHUNK];
+    // The part of `input_buf` not consumed yet.
+    let (mut input_start, mut input_end) = (0, 0);
+    let mut discard = [0u8; WINSIZE];
+    let mut skip = offset - checkpoint.out;
+    let mut written = 0;
+    while written < out.len() {
+        if input_start == input_end {
+            input_end = input.read(&mut input_buf)?;
+            input_start = 0;
+            if input_end == 0 {
+                break;
+            }
+        }
+
+        // Bytes before `offset` are decompressed into a scratch buffer and dropped.
+        let output = if skip > 0 {
+            let size = cmp::min(skip, discard.len());
+            &mut discard[..size]
+        } else {
+            &mut out[written..]
+        };
+        let Inflated {
+            status,
+            consumed,
+            produced,
+        } = stream.inflate(&input_buf[input_start..input_end], output, Z_NO_FLUSH)?;
+        input_start += consumed;
+        if skip > 0 {
+            skip -= produced;
+        } else {
+            written += produced;
+        }
+
+        if status == Z_NEED_DICT {
+            return Err(zlib_error(Z_NEED_DICT, "unexpected need dict".into()));
+        }
+        if status == Z_STREAM_END {
+            break;
+        }
+    }
+
+    Ok(written)
+}
+
 /// A fixed-size ring buffer. Writes are pushed onto the back of the buffer.
 struct RingBuffer<T, const N: usize> {
     buffer: [T; N],
@@ -411,6 +1436,13 @@ where
     fn read(&self) -> (&[T], &[T]) {
         (&self.buffer[self.index..], &self.buffer[..self.index])
     }
+
+    /// Returns a copy of the contents of the ring buffer in order, front first.
+    fn read_contiguous(&self) -> [T; N] {
+        let mut contents = self.buffer;
+        contents.rotate_left(self.index);
+        contents
+    }
 }
 
 const ALIGN: usize = std::mem::align_of::<usize>();
@@ -420,7 +1452,11 @@ fn align_up(size: usize, align: usize) -> usize {
     (size + align - 1) & !(align - 1)
 }
 
-extern "C" fn zalloc(_ptr: *mut c_void, items: AllocSize, item_size: AllocSize) -> *mut c_void {
+pub(crate) extern "C" fn zalloc(
+    _ptr: *mut c_void,
+    items: AllocSize,
+    item_size: AllocSize,
+) -> *mut c_void {
     // We need to multiply `items` and `item_size` to get the actual desired
     // allocation size. Since `zfree` doesn't receive a size argument we
     // also need to allocate space for a `usize` as a header so we can store
@@ -453,7 +1489,7 @@ extern "C" fn zalloc(_ptr: *mut c_void, items: AllocSize, item_size: AllocSize)
     }
 }
 
-extern "C" fn zfree(_ptr: *mut c_void, address: *mut c_void) {
+pub(crate) extern "C" fn zfree(_ptr: *mut c_void, address: *mut c_void) {
     unsafe {
         // Move our address being freed back one pointer, read the size we
         // stored in `zalloc`, and then free it using the standard Rust
@@ -469,6 +1505,8 @@ extern "C" fn zfree(_ptr: *mut c_void, address: *mut c_void) {
 mod test {
     use std::io::Cursor;
 
+    use crate::testutil;
+
     use super::*;
 
     #[test]
@@ -478,7 +1516,683 @@ mod test {
         let mut buf = [0u8; 1 << 14];
         while decoder.read(&mut buf).unwrap() > 0 {}
         // TODO: Test with a larger tarball and add assertions on the zinfo index.
-        let _new_info = decoder.into_zinfo();
+        let zinfo = decoder.into_zinfo();
+        // The first block boundary is recorded regardless of the span size, with an empty window.
+        assert_eq!(zinfo.checkpoints[0].out, 0);
+        assert!(zinfo.checkpoints[0].window.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_extra_span_digests() {
+        let compressed = testutil::gzip(&testutil::random_bytes(1 << 18, 1));
+        let mut decoder = GzipZInfoDecompressor::new(Cursor::new(&compressed), 1 << 14)
+            .unwrap()
+            .with_extra_digest_algorithms(&[DigestAlgorithm::Sha512]);
+        let mut buf = [0u8; 1 << 14];
+        while decoder.read(&mut buf).unwrap() > 0 {}
+        let zinfo = decoder.into_zinfo();
+
+        let sha512_digests = &zinfo.extra_span_digests[&DigestAlgorithm::Sha512];
+        assert!(zinfo.checkpoints.len() > 1);
+        assert_eq!(zinfo.span_digests.len(), zinfo.checkpoints.len());
+        assert_eq!(sha512_digests.len(), zinfo.checkpoints.len());
+        for (i, checkpoint) in zinfo.checkpoints.iter().enumerate() {
+            let start = checkpoint.r#in - usize::from(checkpoint.bits > 0);
+            let end = zinfo
+                .checkpoints
+                .get(i + 1)
+                .map_or(zinfo.total_in, |next| next.r#in);
+            let span = &compressed[start..end];
+            assert_eq!(
+                zinfo.span_digests[i],
+                format!("sha256:{:x}", Sha256::digest(span))
+            );
+            assert_eq!(
+                sha512_digests[i],
+                format!("sha512:{:x}", Sha512::digest(span))
+            );
+        }
+    }
+
+    #[test]
+    fn test_parallel_digests() {
+        let compressed = testutil::gzip(&testutil::text_like(1 << 21));
+        for kind in [SpanDigestKind::Compressed, SpanDigestKind::Uncompressed] {
+            let build = |threads| {
+                GzipZInfoDecompressor::new(&compressed[..], 1 << 14)
+                    .unwrap()
+                    .with_span_digest_kind(kind)
+                    .with_parallel_digests(threads)
+                    .with_extra_digest_algorithms(&[DigestAlgorithm::Sha512])
+                    .finish()
+                    .unwrap()
+            };
+            let serial = build(0);
+            assert!(serial.checkpoints.len() > 8);
+            for threads in [1, 4] {
+                let parallel = build(threads);
+                assert_eq!(parallel.span_digests, serial.span_digests, "{:?}", kind);
+                assert_eq!(parallel.extra_span_digests, serial.extra_span_digests);
+                assert_eq!(parallel.checkpoints, serial.checkpoints);
+            }
+        }
+    }
+
+    #[test]
+    fn test_uncompressed_span_digests() {
+        let data = testutil::random_bytes(1 << 18, 2);
+        let compressed = testutil::gzip(&data);
+        let mut decoder = GzipZInfoDecompressor::new(Cursor::new(&compressed), 1 << 14)
+            .unwrap()
+            .with_span_digest_kind(SpanDigestKind::Uncompressed);
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        let zinfo = decoder.into_zinfo();
+
+        assert_eq!(decompressed, data);
+        assert_eq!(zinfo.span_digest_kind, SpanDigestKind::Uncompressed);
+        assert!(zinfo.checkpoints.len() > 1);
+        assert_eq!(zinfo.span_digests.len(), zinfo.checkpoints.len());
+        for (i, checkpoint) in zinfo.checkpoints.iter().enumerate() {
+            let end = zinfo
+                .checkpoints
+                .get(i + 1)
+                .map_or(zinfo.total_out, |next| next.out);
+            assert!(end - checkpoint.out > zinfo.span_size || end == zinfo.total_out);
+            let span = &decompressed[checkpoint.out..end];
+            assert_eq!(
+                zinfo.span_digests[i],
+                format!("sha256:{:x}", Sha256::digest(span))
+            );
+        }
+    }
+
+    fn zinfo_reading_with(
+        compressed: &[u8],
+        span_size: usize,
+        buf_size: usize,
+    ) -> (ZInfo, Vec<u8>) {
+        let mut decoder = GzipZInfoDecompressor::new(compressed, span_size).unwrap();
+        let mut out = Vec::new();
+        let mut buf = vec![0u8; buf_size];
+        loop {
+            let read = decoder.read(&mut buf).unwrap();
+            if read == 0 {
+                break;
+            }
+            out.extend_from_slice(&buf[..read]);
+        }
+        (decoder.into_zinfo(), out)
+    }
+
+    #[test]
+    fn test_many_small_blocks() {
+        let data = testutil::text_like(1 << 22);
+        let compressed = testutil::gzip_rsyncable(&data, 4096);
+
+        // The index must not depend on how the output is read.
+        let (zinfo, out) = zinfo_reading_with(&compressed, 1 << 16, 1000);
+        assert_eq!(out, data);
+        for buf_size in [1, 1 << 14, 1 << 20] {
+            assert_eq!(zinfo_reading_with(&compressed, 1 << 16, buf_size).0, zinfo);
+        }
+
+        assert!(zinfo.checkpoints.len() > 60);
+        for checkpoint in &zinfo.checkpoints {
+            let start = checkpoint.r#in - usize::from(checkpoint.bits > 0);
+            let resumed = inflate_from_checkpoint(
+                Cursor::new(&compressed[start..]),
+                checkpoint,
+                checkpoint.out,
+                1 << 12,
+            )
+            .unwrap();
+            assert_eq!(
+                resumed,
+                data[checkpoint.out..checkpoint.out + resumed.len()]
+            );
+        }
+    }
+
+    #[test]
+    fn test_leading_empty_blocks() {
+        let data = testutil::text_like(1 << 20);
+        let compressed = testutil::gzip_with_empty_blocks(&data, 5);
+        let (zinfo, out) = zinfo_reading_with(&compressed, 1 << 16, 1 << 14);
+        assert_eq!(out, data);
+
+        assert_eq!(zinfo.checkpoints[0].out, 0);
+        assert!(zinfo.checkpoints.len() > 2);
+        assert!(zinfo
+            .checkpoints
+            .windows(2)
+            .all(|pair| pair[0].out < pair[1].out));
+        assert_eq!(zinfo.span_digests.len(), zinfo.checkpoints.len());
+        zinfo.validate().unwrap();
+    }
+
+    /// Compares building the index against plain inflate on rsyncable-style input. Run with
+    /// `cargo test --release bench_many_small_blocks -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_many_small_blocks() {
+        let compressed = testutil::gzip_rsyncable(&testutil::text_like(1 << 26), 4096);
+        let mut buf = vec![0u8; 1 << 16];
+
+        let start = std::time::Instant::now();
+        let mut stream = ZStream::new(47).unwrap();
+        let mut input = &compressed[..];
+        loop {
+            let inflated = stream.inflate(input, &mut buf, Z_NO_FLUSH).unwrap();
+            input = &input[inflated.consumed..];
+            if inflated.status == Z_STREAM_END {
+                break;
+            }
+        }
+        eprintln!("plain inflate: {:?}", start.elapsed());
+
+        let start = std::time::Instant::now();
+        let mut decoder = GzipZInfoDecompressor::new(&compressed[..], 1 << 22).unwrap();
+        while decoder.read(&mut buf).unwrap() > 0 {}
+        eprintln!("zinfo: {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_zstream_inflate() {
+        let data = testutil::text_like(1 << 16);
+        let compressed = testutil::gzip(&data);
+        let mut stream = ZStream::new(31).unwrap();
+        let mut out = Vec::new();
+        let mut offset = 0;
+        loop {
+            // Both buffers are dropped after every call, which the stream must not notice.
+            let input = compressed[offset..cmp::min(offset + 100, compressed.len())].to_vec();
+            let mut output = vec![0u8; 1000];
+            let inflated = stream.inflate(&input, &mut output, Z_NO_FLUSH).unwrap();
+            offset += inflated.consumed;
+            out.extend_from_slice(&output[..inflated.produced]);
+            if inflated.status == Z_STREAM_END {
+                break;
+            }
+        }
+        assert_eq!(offset, compressed.len());
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn test_build_zinfo() {
+        let data = testutil::text_like(1 << 20);
+        let compressed = testutil::gzip(&data);
+        let mut decoder = GzipZInfoDecompressor::new(&compressed[..], 1 << 16).unwrap();
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        let expected = decoder.into_zinfo();
+        assert!(expected.checkpoints.len() > 2);
+        assert_eq!(build_zinfo(&compressed[..], 1 << 16).unwrap(), expected);
+
+        // Finishing picks up wherever the caller stopped reading.
+        let mut decoder = GzipZInfoDecompressor::new(&compressed[..], 1 << 16).unwrap();
+        decoder.read_exact(&mut [0u8; 1000]).unwrap();
+        assert_eq!(decoder.finish().unwrap(), expected);
+
+        let err = build_zinfo(&compressed[..compressed.len() / 2], 1 << 16).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_zinfo_bytes() {
+        let compressed = testutil::gzip(&testutil::text_like(1 << 20));
+        let zinfo = build_zinfo(&compressed[..], 1 << 16).unwrap();
+        assert!(zinfo.checkpoints.len() > 2);
+        let bytes = zinfo.to_bytes();
+        assert_eq!(
+            bytes.len(),
+            CHECKPOINTS_HEADER_SIZE + zinfo.checkpoints.len() * CHECKPOINT_SIZE
+        );
+
+        let parsed = ZInfo::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.checkpoints, zinfo.checkpoints);
+        assert_eq!(parsed.span_size, zinfo.span_size);
+        assert!(parsed.span_digests.is_empty());
+        parsed.validate().unwrap();
+        assert_eq!(parsed.to_bytes(), bytes);
+
+        for truncated in [
+            &bytes[..4],
+            &bytes[..bytes.len() - 1],
+            &bytes[..bytes.len() - CHECKPOINT_SIZE],
+        ] {
+            let err = ZInfo::from_bytes(truncated).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(ZInfo::from_bytes(&trailing).is_err());
+
+        let compressed_bytes = zinfo.to_compressed_bytes().unwrap();
+        assert!(compressed_bytes.len() * 4 < bytes.len());
+        let parsed = ZInfo::from_bytes(&compressed_bytes).unwrap();
+        assert_eq!(parsed.checkpoints, zinfo.checkpoints);
+        assert_eq!(parsed.to_bytes(), bytes);
+        let err = ZInfo::from_bytes(&compressed_bytes[..compressed_bytes.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_raw_deflate() {
+        let data = testutil::text_like(1 << 20);
+        let compressed = testutil::deflate_with(&data, -15);
+        let mut decoder =
+            GzipZInfoDecompressor::with_window_bits(&compressed[..], 1 << 16, -15).unwrap();
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, data);
+        assert_eq!(decoder.gzip_isize(), None);
+        let zinfo = decoder.into_zinfo();
+        zinfo.validate().unwrap();
+        assert_eq!(zinfo.total_in, compressed.len());
+        assert!(zinfo.checkpoints.len() > 2);
+        assert_eq!(
+            (zinfo.checkpoints[0].r#in, zinfo.checkpoints[0].out),
+            (0, 0)
+        );
+        assert_eq!(zinfo.span_digests.len(), zinfo.checkpoints.len());
+
+        for checkpoint in &zinfo.checkpoints {
+            let start = checkpoint.r#in - usize::from(checkpoint.bits > 0);
+            let out = inflate_from_checkpoint(
+                Cursor::new(&compressed[start..]),
+                checkpoint,
+                checkpoint.out,
+                1000,
+            )
+            .unwrap();
+            assert_eq!(out, data[checkpoint.out..checkpoint.out + out.len()]);
+        }
+
+        for window_bits in [-9, 9, 14, 30, 0] {
+            let err =
+                GzipZInfoDecompressor::with_window_bits(&compressed[..], 1 << 16, window_bits)
+                    .err()
+                    .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", window_bits);
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let gzip = testutil::gzip(b"hello");
+        let mut reader = &gzip[..];
+        assert_eq!(detect(&mut reader).unwrap(), CompressionAlgorithm::Gzip);
+        // Nothing was consumed, so the stream still decompresses.
+        assert!(build_zinfo(reader, 1 << 16).is_ok());
+
+        let zstd = [0x28, 0xb5, 0x2f, 0xfd, 0x24, 0x05, 0x29, 0x00];
+        assert_eq!(detect(&mut &zstd[..]).unwrap(), CompressionAlgorithm::Zstd);
+
+        for unknown in [&b"PK\x03\x04"[..], &b"\x1f"[..], &[]] {
+            let err = detect(&mut &unknown[..]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", unknown);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let compressed = testutil::gzip(&testutil::text_like(1 << 20));
+        let mut zinfo = build_zinfo(&compressed[..], 1 << 16).unwrap();
+        zinfo.validate().unwrap();
+        assert!(zinfo.checkpoints.len() > 2);
+
+        zinfo.checkpoints.swap(1, 2);
+        let err = zinfo.validate().unwrap_err();
+        assert!(err.to_string().contains("checkpoint 2"), "{}", err);
+        zinfo.checkpoints.swap(1, 2);
+
+        zinfo.checkpoints[1].bits = 8;
+        assert!(zinfo.validate().is_err());
+        zinfo.checkpoints[1].bits = 0;
+
+        zinfo.checkpoints[0].window[0] = 1;
+        assert!(zinfo.validate().is_err());
+    }
+
+    #[test]
+    fn test_span_timing() {
+        let compressed = testutil::gzip(&testutil::text_like(1 << 20));
+        let mut decoder = GzipZInfoDecompressor::new(&compressed[..], 1 << 16)
+            .unwrap()
+            .with_span_timing(true);
+        io::copy(&mut decoder, &mut io::sink()).unwrap();
+        let durations = decoder.span_durations().unwrap().to_vec();
+        let zinfo = decoder.into_zinfo();
+        assert!(zinfo.checkpoints.len() > 2);
+        assert_eq!(durations.len(), zinfo.checkpoints.len());
+        assert!(durations.iter().any(|duration| !duration.is_zero()));
+    }
+
+    #[test]
+    fn test_progress() {
+        let compressed = testutil::gzip(&testutil::text_like(1 << 20));
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = calls.clone();
+        let zinfo = GzipZInfoDecompressor::new(&compressed[..], 1 << 16)
+            .unwrap()
+            .with_progress(move |total_in, total_out| {
+                recorded.lock().unwrap().push((total_in, total_out))
+            })
+            .finish()
+            .unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert!(!calls.is_empty());
+        assert!(calls.windows(2).all(|pair| pair[0].1 < pair[1].1));
+        let offsets: Vec<_> = zinfo
+            .checkpoints
+            .iter()
+            .map(|checkpoint| (checkpoint.r#in, checkpoint.out))
+            .collect();
+        assert_eq!(*calls, offsets);
+    }
+
+    #[test]
+    fn test_checkpoint_count() {
+        let compressed = testutil::gzip(&testutil::text_like(1 << 20));
+        let mut decompressor = GzipZInfoDecompressor::new(&compressed[..], 1 << 16).unwrap();
+        assert_eq!(decompressor.checkpoint_count(), 0);
+        assert_eq!(
+            decompressor.estimated_zinfo_bytes(),
+            CHECKPOINTS_HEADER_SIZE
+        );
+
+        let mut counts = Vec::new();
+        let mut buf = vec![0; 1 << 16];
+        while decompressor.read(&mut buf).unwrap() > 0 {
+            counts.push(decompressor.checkpoint_count());
+        }
+        assert!(counts.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(counts[0] < counts[counts.len() - 1]);
+
+        let estimated = decompressor.estimated_zinfo_bytes();
+        let zinfo = decompressor.into_zinfo();
+        assert_eq!(*counts.last().unwrap(), zinfo.checkpoints.len());
+        assert_eq!(estimated, zinfo.to_bytes().len());
+    }
+
+    #[test]
+    fn test_reset() {
+        let first = include_bytes!("testdata/test.tar.gz");
+        let second = testutil::gzip(&testutil::text_like(1 << 20));
+        let mut decompressor = GzipZInfoDecompressor::new(&first[..], 1 << 16)
+            .unwrap()
+            .with_extra_digest_algorithms(&[DigestAlgorithm::Sha512])
+            .with_uncompressed_digest(true);
+        let mut data = Vec::new();
+        decompressor.read_to_end(&mut data).unwrap();
+
+        let zinfo = decompressor.reset(&second[..]).unwrap();
+        assert_eq!(zinfo.total_in, first.len());
+        assert_eq!(decompressor.checkpoint_count(), 0);
+        data.clear();
+        decompressor.read_to_end(&mut data).unwrap();
+        assert_eq!(decompressor.gzip_isize(), Some(data.len() as u32));
+        let zinfo = decompressor.into_zinfo();
+        zinfo.validate().unwrap();
+
+        let fresh = GzipZInfoDecompressor::new(&second[..], 1 << 16)
+            .unwrap()
+            .with_extra_digest_algorithms(&[DigestAlgorithm::Sha512])
+            .with_uncompressed_digest(true)
+            .finish()
+            .unwrap();
+        assert_eq!(zinfo, fresh);
+    }
+
+    #[test]
+    fn test_totals_with_tiny_reads() {
+        /// Returns at most a few bytes per read.
+        struct Trickle<'a>(&'a [u8], usize);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+                self.1 += 1;
+                let len = cmp::min(self.1 % 7 + 1, buf.len());
+                self.0.read(&mut buf[..len])
+            }
+        }
+
+        let data = testutil::text_like(1 << 18);
+        let compressed = testutil::gzip(&data);
+        let mut decompressor =
+            GzipZInfoDecompressor::new(Trickle(&compressed, 0), 1 << 14).unwrap();
+        let mut decompressed = Vec::new();
+        let mut buf = [0u8; 5];
+        loop {
+            match decompressor.read(&mut buf).unwrap() {
+                0 => break,
+                n => decompressed.extend_from_slice(&buf[..n]),
+            }
+        }
+        assert_eq!(decompressed, data);
+        let zinfo = decompressor.into_zinfo();
+        assert_eq!(zinfo.total_in, compressed.len());
+        assert_eq!(zinfo.total_out, data.len());
+        assert_eq!(
+            zinfo.checkpoints,
+            build_zinfo(&compressed[..], 1 << 14).unwrap().checkpoints
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_windows() {
+        let data = testutil::text_like(1 << 20);
+        let compressed = testutil::gzip(&data);
+        // Reads both shorter and far longer than a window, so the window is filled from a single
+        // read's tail as well as across many reads.
+        for buf_size in [1000, WINSIZE, 1 << 18] {
+            let (zinfo, out) = zinfo_reading_with(&compressed, 1 << 16, buf_size);
+            assert_eq!(out, data);
+            assert!(zinfo.checkpoints.len() > 2, "{}", buf_size);
+            for checkpoint in &zinfo.checkpoints {
+                let mut want = [0u8; WINSIZE];
+                let start = checkpoint.out.saturating_sub(WINSIZE);
+                want[WINSIZE - (checkpoint.out - start)..]
+                    .copy_from_slice(&data[start..checkpoint.out]);
+                assert!(
+                    checkpoint.window[..] == want[..],
+                    "window at {} with reads of {}",
+                    checkpoint.out,
+                    buf_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_gzip_isize() {
+        let data = testutil::text_like(1 << 20);
+        let compressed = testutil::gzip(&data);
+        // Feed the stream in small reads so the trailer straddles input chunks.
+        let mut decoder =
+            GzipZInfoDecompressor::new(io::BufReader::with_capacity(3, &compressed[..]), 1 << 16)
+                .unwrap();
+        assert_eq!(decoder.gzip_isize(), None);
+        io::copy(&mut decoder, &mut io::sink()).unwrap();
+        assert_eq!(decoder.gzip_isize(), Some(data.len() as u32));
+
+        // An auto-detected zlib stream ends in an adler32 checksum, not an ISIZE.
+        let compressed = testutil::deflate_with(&data, 15);
+        let mut decoder = GzipZInfoDecompressor::new(&compressed[..], 1 << 16).unwrap();
+        io::copy(&mut decoder, &mut io::sink()).unwrap();
+        assert_eq!(decoder.zinfo.total_out, data.len());
+        assert_eq!(decoder.gzip_isize(), None);
+    }
+
+    #[test]
+    fn test_uncompressed_digest() {
+        let data = testutil::text_like(1 << 20);
+        let compressed = testutil::gzip(&data);
+        let zinfo_with = |enabled| {
+            // Small output reads exercise hashing across many calls.
+            let mut decoder = io::BufReader::with_capacity(
+                1000,
+                GzipZInfoDecompressor::new(&compressed[..], 1 << 16)
+                    .unwrap()
+                    .with_uncompressed_digest(enabled),
+            );
+            io::copy(&mut decoder, &mut io::sink()).unwrap();
+            decoder.into_inner().into_zinfo()
+        };
+        assert_eq!(zinfo_with(false).uncompressed_digest, None);
+        assert_eq!(
+            zinfo_with(true).uncompressed_digest,
+            Some(format!("sha256:{:x}", Sha256::digest(&data)))
+        );
+    }
+
+    #[test]
+    fn test_extract() {
+        let last = testutil::random_bytes(1 << 16, 5);
+        let files = [
+            ("first", testutil::random_bytes(1 << 18, 4)),
+            ("last", last.clone()),
+        ];
+        let files = files
+            .iter()
+            .map(|(name, data)| (*name, &data[..]))
+            .collect::<Vec<_>>();
+        let tar = testutil::tar_of(&files);
+        let compressed = testutil::gzip(&tar);
+        let mut decoder = GzipZInfoDecompressor::new(&compressed[..], 1 << 16).unwrap();
+        io::copy(&mut decoder, &mut io::sink()).unwrap();
+        let zinfo = decoder.into_zinfo();
+
+        // The last file starts after several checkpoints, so extraction seeks past the start.
+        let mut archive = tar::Archive::new(&tar[..]);
+        let entry = archive.entries().unwrap().nth(1).unwrap().unwrap();
+        let (offset, len) = (entry.raw_file_position() as usize, entry.size() as usize);
+        assert!(checkpoint_for_offset(&zinfo.checkpoints, offset).unwrap() > 1);
+        let data = extract(Cursor::new(&compressed), &zinfo, offset, len).unwrap();
+        assert_eq!(data, last);
+
+        // Reads past the end are cut short.
+        let data = extract(Cursor::new(&compressed), &zinfo, tar.len() - 10, 100).unwrap();
+        assert_eq!(data, tar[tar.len() - 10..]);
+        assert!(extract(Cursor::new(&compressed), &zinfo, tar.len(), 1)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_checkpoints() {
+        let data = testutil::text_like(1 << 20);
+        let compressed = testutil::gzip(&data);
+        let zinfo = build_zinfo(&compressed[..], 1 << 16).unwrap();
+        assert!(zinfo.checkpoints.len() > 4);
+        for (i, checkpoint) in zinfo.checkpoints.iter().enumerate() {
+            let next = zinfo
+                .checkpoints
+                .get(i + 1)
+                .map_or(zinfo.total_out, |next| next.out);
+            checkpoint.validate(Cursor::new(&compressed), next).unwrap();
+        }
+
+        // Resuming can't go past the end of the stream, or stop before the checkpoint itself.
+        let last = zinfo.checkpoints.last().unwrap();
+        let err = last
+            .validate(Cursor::new(&compressed), zinfo.total_out + 1)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let err = last
+            .validate(Cursor::new(&compressed), last.out)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // A checkpoint off by a bit doesn't resume at a block boundary.
+        let checkpoint = zinfo.checkpoints.iter().find(|c| c.bits > 0).unwrap();
+        let shifted = GZipCheckpoint {
+            bits: checkpoint.bits - 1,
+            ..*checkpoint
+        };
+        assert!(shifted
+            .validate(Cursor::new(&compressed), zinfo.total_out)
+            .is_err());
+    }
+
+    #[test]
+    fn test_extract_into() {
+        let tar = testutil::text_like(1 << 20);
+        let compressed = testutil::gzip(&tar);
+        let zinfo = build_zinfo(&compressed[..], 1 << 16).unwrap();
+        assert!(zinfo.checkpoints.len() > 4);
+
+        // Read a region spanning several checkpoints through one small reused buffer, up to and
+        // past the end of the stream.
+        let start = 100_000;
+        let mut buf = [0u8; 10_000];
+        let mut data = Vec::new();
+        loop {
+            let offset = start + data.len();
+            match extract_into(Cursor::new(&compressed), &zinfo, offset, &mut buf).unwrap() {
+                0 => break,
+                n => data.extend_from_slice(&buf[..n]),
+            }
+        }
+        assert_eq!(data, tar[start..]);
+        // The last read was cut short, as the region isn't a multiple of the buffer size.
+        assert_ne!((tar.len() - start) % buf.len(), 0);
+        assert_eq!(
+            extract_into(Cursor::new(&compressed), &zinfo, start, &mut []).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_offset_lookup() {
+        let mut zinfo = build_zinfo(&testutil::gzip(b"hello")[..], 1 << 16).unwrap();
+        zinfo.checkpoints = [100, 200, 300]
+            .into_iter()
+            .map(|out| GZipCheckpoint {
+                r#in: out / 10,
+                out,
+                bits: 0,
+                window: [0; WINSIZE],
+            })
+            .collect();
+        let out_for = |offset| zinfo.checkpoint_for_offset(offset).map(|cp| cp.out);
+
+        assert_eq!(out_for(0), None);
+        assert_eq!(zinfo.span_index_for_offset(99), None);
+        assert_eq!(out_for(100), Some(100));
+        assert_eq!(zinfo.span_index_for_offset(100), Some(0));
+        assert_eq!(out_for(199), Some(100));
+        assert_eq!(out_for(200), Some(200));
+        assert_eq!(zinfo.span_index_for_offset(250), Some(1));
+        assert_eq!(zinfo.span_index_for_offset(300), Some(2));
+        assert_eq!(zinfo.span_index_for_offset(usize::MAX), Some(2));
+    }
+
+    #[test]
+    fn test_buf_read_input() {
+        let compressed = include_bytes!("testdata/layer.tar.gz");
+        fn zinfo_of<R, B: InputBuffer<R>>(
+            mut decoder: GzipZInfoDecompressor<R, B>,
+        ) -> (Vec<u8>, ZInfo) {
+            let mut data = Vec::new();
+            decoder.read_to_end(&mut data).unwrap();
+            (data, decoder.into_zinfo())
+        }
+        let (data, zinfo) = zinfo_of(GzipZInfoDecompressor::new(&compressed[..], 1 << 16).unwrap());
+        assert!(zinfo.checkpoints.len() > 2);
+
+        for capacity in [1, 1000, 1 << 20] {
+            let reader = io::BufReader::with_capacity(capacity, &compressed[..]);
+            let decoder = GzipZInfoDecompressor::from_buf_read(reader, 1 << 16).unwrap();
+            let (buffered_data, buffered) = zinfo_of(decoder);
+            assert_eq!(buffered_data, data);
+            assert_eq!(buffered.checkpoints, zinfo.checkpoints);
+            assert_eq!(buffered.span_digests, zinfo.span_digests);
+            assert_eq!(buffered.total_in, zinfo.total_in);
+            assert_eq!(buffered.total_out, zinfo.total_out);
+        }
     }
 
     #[test]
@@ -502,4 +2216,16 @@ mod test {
         buffer.write(&[4u8; 75]);
         assert_eq!(buffer.read(), ([3u8; 25].as_slice(), [4u8; 75].as_slice()));
     }
+
+    #[test]
+    fn test_ring_buffer_contiguous() {
+        let mut buffer = RingBuffer::<u8, 100>::new();
+        assert_eq!(buffer.read_contiguous(), [0u8; 100]);
+        for (i, len) in [30, 50, 45, 100, 7, 160].into_iter().enumerate() {
+            let data: Vec<u8> = (0..len).map(|b| (b + i * 31) as u8).collect();
+            buffer.write(&data);
+            let (left, right) = buffer.read();
+            assert_eq!(buffer.read_contiguous()[..], [left, right].concat());
+        }
+    }
 }
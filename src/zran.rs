@@ -0,0 +1,109 @@
+//! Export of checkpoints in the index layout used by zlib's `examples/zran.c`.
+//!
+//! zran keeps its index in memory as a `struct deflate_index` holding an array of
+//! `struct point`. Tools built on it that persist the index write those structs as laid out on
+//! 64-bit little-endian platforms, which is the layout produced here:
+//!
+//! | zran field                | bytes | source                                   |
+//! |---------------------------|-------|------------------------------------------|
+//! | `deflate_index.have`      | 4     | number of checkpoints (`int`)            |
+//! | `deflate_index.mode`      | 4     | `31`, gzip decoding (`int`)              |
+//! | `deflate_index.length`    | 8     | total uncompressed size (`off_t`)        |
+//! | `point.out`               | 8     | `GZipCheckpoint::out` (`off_t`)          |
+//! | `point.in`                | 8     | `GZipCheckpoint::in` (`off_t`)           |
+//! | `point.bits`              | 4     | `GZipCheckpoint::bits` (`int`)           |
+//! | `point.window`            | 32768 | `GZipCheckpoint::window`                 |
+//! | padding                   | 4     | zero, aligning the next point to 8 bytes |
+//!
+//! The point fields repeat `have` times. As in zran, `bits` is the number of bits of the byte at
+//! `in - 1` that belong to the point, and `window` holds the 32 KiB of output preceding `out` in
+//! order.
+
+use std::io::{self, Write};
+
+use crate::zinfo::{GZipCheckpoint, WINSIZE};
+
+/// zran's `mode` for decoding a gzip stream.
+const ZRAN_MODE_GZIP: i32 = 31;
+
+/// Size of a `deflate_index` header.
+pub const ZRAN_HEADER_SIZE: usize = 16;
+/// Size of a single `point`, including trailing padding.
+pub const ZRAN_POINT_SIZE: usize = 8 + 8 + 4 + WINSIZE + 4;
+
+/// Writes `checkpoints` in the zran index layout. `length` is the total uncompressed size.
+pub fn write_zran_index<W: Write>(
+    mut writer: W,
+    checkpoints: &[GZipCheckpoint],
+    length: u64,
+) -> io::Result<()> {
+    let have = i32::try_from(checkpoints.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many checkpoints"))?;
+    writer.write_all(&have.to_le_bytes())?;
+    writer.write_all(&ZRAN_MODE_GZIP.to_le_bytes())?;
+    writer.write_all(&(length as i64).to_le_bytes())?;
+    for checkpoint in checkpoints {
+        writer.write_all(&(checkpoint.out as i64).to_le_bytes())?;
+        writer.write_all(&(checkpoint.r#in as i64).to_le_bytes())?;
+        writer.write_all(&i32::from(checkpoint.bits).to_le_bytes())?;
+        writer.write_all(&checkpoint.window)?;
+        writer.write_all(&[0; 4])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read};
+
+    use crate::zinfo::{inflate_from_checkpoint, GzipZInfoDecompressor};
+
+    use super::*;
+
+    /// Reads an index the way a zran tool would, mirroring the C struct layout.
+    fn read_zran_index(data: &[u8]) -> (i32, i64, Vec<GZipCheckpoint>) {
+        let i32_at = |at: usize| i32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let i64_at = |at: usize| i64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+
+        let have = i32_at(0) as usize;
+        assert_eq!(data.len(), ZRAN_HEADER_SIZE + have * ZRAN_POINT_SIZE);
+        let points = (0..have)
+            .map(|i| {
+                let at = ZRAN_HEADER_SIZE + i * ZRAN_POINT_SIZE;
+                GZipCheckpoint {
+                    out: i64_at(at) as usize,
+                    r#in: i64_at(at + 8) as usize,
+                    bits: i32_at(at + 16) as u8,
+                    window: data[at + 20..at + 20 + WINSIZE].try_into().unwrap(),
+                }
+            })
+            .collect();
+        (i32_at(4), i64_at(8), points)
+    }
+
+    #[test]
+    fn test_zran_index() {
+        let compressed = include_bytes!("testdata/layer.tar.gz");
+        let mut decompressor = GzipZInfoDecompressor::new(&compressed[..], 1 << 20).unwrap();
+        let mut uncompressed = Vec::new();
+        decompressor.read_to_end(&mut uncompressed).unwrap();
+        let zinfo = decompressor.into_zinfo();
+
+        let mut index = Vec::new();
+        write_zran_index(&mut index, &zinfo.checkpoints, zinfo.total_out as u64).unwrap();
+
+        let (mode, length, points) = read_zran_index(&index);
+        assert_eq!(mode, 31);
+        assert_eq!(length, uncompressed.len() as i64);
+        assert_eq!(points, zinfo.checkpoints);
+
+        // Resuming from a parsed point yields the original data.
+        let point = points.last().unwrap();
+        let start = point.r#in - usize::from(point.bits > 0);
+        let data =
+            inflate_from_checkpoint(Cursor::new(&compressed[start..]), point, point.out, 1 << 10)
+                .unwrap();
+        assert_eq!(data, uncompressed[point.out..point.out + data.len()]);
+        assert!(!data.is_empty());
+    }
+}
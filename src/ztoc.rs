@@ -1,14 +1,36 @@
 use std::{
-    collections::HashMap,
-    io::{self, Read, Result},
-    path::PathBuf,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, Read, Result, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
     str::Utf8Error,
+    time::Duration,
 };
 
 use chrono::{DateTime, NaiveDateTime};
+use sha2::{Digest, Sha256};
 use tar::Archive;
 
-use crate::zinfo::{GzipZInfoDecompressor, ZInfo};
+#[cfg(unix)]
+use crate::mmap::Mmap;
+use crate::{
+    error::ZtocError,
+    zinfo::{
+        check_checkpoint_offsets, checkpoint_for_offset, compress_window, decompress_window,
+        inflate_from_checkpoint, DigestAlgorithm, GZipCheckpoint, GzipZInfoDecompressor,
+        SpanDigestKind, ZInfo, CHECKPOINTS_HEADER_SIZE, CHECKPOINT_SIZE, WINSIZE,
+    },
+};
+
+/// The default minimum span size between checkpoints.
+pub const DEFAULT_SPAN_SIZE: usize = 1 << 22; // 4MiB
+
+const ZTOC_VERSION: &str = "0.9";
+// PAX records holding extended attributes, as written by GNU tar and Go's archive/tar.
+const PAX_XATTR_PREFIX: &str = "SCHILY.xattr.";
+const BUILD_TOOL_IDENTIFIER: &str = concat!("Replit SOCI v", env!("CARGO_PKG_VERSION"));
 
 #[derive(Debug)]
 pub struct CompressionOffset(pub u64);
@@ -21,60 +43,1056 @@ pub struct ZToc {
     pub uncompressed_archive_size: CompressionOffset,
     pub toc: Toc,
     pub compression_info: CompressionInfo,
+    /// Free-form annotations describing the whole ztoc. Empty by default.
+    pub annotations: HashMap<String, String>,
+    /// SHA-256 of the whole compressed layer, as `sha256:<hex>`, if requested with
+    /// [`ZtocBuilder::compressed_digest`].
+    pub compressed_digest: Option<String>,
+    /// Measurements taken while building, if requested.
+    pub metrics: BuildMetrics,
+}
+
+/// The TOC half of a [`ZToc`], see [`ZToc::split`]. Encoded with
+/// [`crate::encode::encode_toc_artifact`].
+#[derive(Debug)]
+pub struct TocArtifact {
+    pub version: String,
+    pub build_tool_identifier: String,
+    pub uncompressed_archive_size: CompressionOffset,
+    pub toc: Toc,
+    pub annotations: HashMap<String, String>,
+}
+
+/// The compression index half of a [`ZToc`], see [`ZToc::split`]. Encoded with
+/// [`crate::encode::encode_compression_artifact`].
+#[derive(Debug)]
+pub struct CompressionArtifact {
+    pub version: String,
+    pub compressed_archive_size: CompressionOffset,
+    pub uncompressed_archive_size: CompressionOffset,
+    pub compression_info: CompressionInfo,
+    pub compressed_digest: Option<String>,
+}
+
+/// Measurements taken while building a ztoc.
+#[derive(Debug, Default)]
+pub struct BuildMetrics {
+    /// Wall-clock time spent inflating each span, parallel to the span digests. Empty unless
+    /// enabled with [`ZtocBuilder::span_timing`].
+    pub span_durations: Vec<Duration>,
+    /// Entries whose uname or gname wasn't valid UTF-8 and was decoded lossily. Empty unless
+    /// enabled with [`ZtocBuilder::lossy_names`].
+    pub lossy_names: Vec<PathBuf>,
+    /// Entries left out of the TOC because their metadata couldn't be read. Empty unless enabled
+    /// with [`ZtocBuilder::skip_unreadable_entries`].
+    pub skipped_entries: Vec<SkippedEntry>,
+}
+
+/// An archive entry left out of the TOC, see [`ZtocBuilder::skip_unreadable_entries`].
+#[derive(Debug)]
+pub struct SkippedEntry {
+    /// The position of the entry in the archive, counting from 0 and including skipped entries.
+    pub index: usize,
+    pub error: io::Error,
 }
 
 impl ZToc {
-    pub fn new<R>(reader: R) -> Result<ZToc>
+    pub fn new<R>(reader: R) -> std::result::Result<ZToc, ZtocError>
+    where
+        R: Read,
+    {
+        ZToc::with_span_size(reader, DEFAULT_SPAN_SIZE)
+    }
+
+    /// Builds a ztoc with a checkpoint at the first block boundary after every `span_size`
+    /// uncompressed bytes. Fails if `span_size` is 0, which would checkpoint every block.
+    pub fn with_span_size<R>(reader: R, span_size: usize) -> std::result::Result<ZToc, ZtocError>
     where
         R: Read,
     {
-        // TODO: Make this configurable.
-        let span_size = 1 << 22; // 4MiB
-        let mut decompressor = GzipZInfoDecompressor::new(reader, span_size)?;
-        let toc = generate_tar_metadata(&mut decompressor)?;
-        // Ensure we read the rest.
-        let mut buf = [0u8; 1 << 10];
-        while decompressor.read(&mut buf)? > 0 {}
+        ZToc::builder().span_size(span_size).build(reader)
+    }
+
+    /// Like [`ZToc::with_span_size`], but reads the layer at `path` through a memory map instead
+    /// of buffered reads, which is faster for large local layers.
+    #[cfg(unix)]
+    pub fn from_path<P>(path: P, span_size: usize) -> std::result::Result<ZToc, ZtocError>
+    where
+        P: AsRef<Path>,
+    {
+        let map = Mmap::map(&File::open(path)?)?;
+        ZToc::with_span_size(&map[..], span_size)
+    }
+
+    /// Builds a ztoc from an already decompressed tar stream and a zinfo computed separately for
+    /// the compressed layer, e.g. by another tool. Fails if the tar stream and the zinfo disagree
+    /// on the uncompressed size.
+    pub fn from_tar_and_zinfo<R>(tar: R, zinfo: ZInfo) -> std::result::Result<ZToc, ZtocError>
+    where
+        R: Read,
+    {
+        zinfo.validate()?;
+        let mut tar = CountingReader {
+            inner: tar,
+            count: 0,
+        };
+        let toc = generate_tar_metadata(&mut tar)?;
+        if tar.count != zinfo.total_out as u64 {
+            return Err(ZtocError::InvalidZtoc(format!(
+                "tar stream is {} bytes but the zinfo covers {} uncompressed bytes",
+                tar.count, zinfo.total_out
+            )));
+        }
+
+        Ok(ZToc {
+            version: String::from(ZTOC_VERSION),
+            build_tool_identifier: String::from(BUILD_TOOL_IDENTIFIER),
+            compressed_achrive_size: CompressionOffset(zinfo.total_in as u64),
+            uncompressed_archive_size: CompressionOffset(zinfo.total_out as u64),
+            toc,
+            compression_info: zinfo.into(),
+            annotations: HashMap::new(),
+            compressed_digest: None,
+            metrics: BuildMetrics::default(),
+        })
+    }
+
+    /// Splits the ztoc into its TOC and its compression index, so they can be stored and
+    /// updated separately. Both halves record the uncompressed archive size they describe, which
+    /// [`ZToc::join`] checks. Build metrics are dropped.
+    pub fn split(self) -> (TocArtifact, CompressionArtifact) {
+        (
+            TocArtifact {
+                version: self.version.clone(),
+                build_tool_identifier: self.build_tool_identifier,
+                uncompressed_archive_size: CompressionOffset(self.uncompressed_archive_size.0),
+                toc: self.toc,
+                annotations: self.annotations,
+            },
+            CompressionArtifact {
+                version: self.version,
+                compressed_archive_size: self.compressed_achrive_size,
+                uncompressed_archive_size: self.uncompressed_archive_size,
+                compression_info: self.compression_info,
+                compressed_digest: self.compressed_digest,
+            },
+        )
+    }
+
+    /// Recombines the halves of a ztoc split with [`ZToc::split`]. Fails if they disagree on the
+    /// ztoc version or the uncompressed archive size.
+    pub fn join(
+        toc: TocArtifact,
+        compression: CompressionArtifact,
+    ) -> std::result::Result<ZToc, ZtocError> {
+        if toc.version != compression.version {
+            return Err(ZtocError::InvalidZtoc(format!(
+                "TOC is version {} but the compression index is version {}",
+                toc.version, compression.version
+            )));
+        }
+        if toc.uncompressed_archive_size.0 != compression.uncompressed_archive_size.0 {
+            return Err(ZtocError::InvalidZtoc(format!(
+                "TOC covers {} uncompressed bytes but the compression index covers {}",
+                toc.uncompressed_archive_size.0, compression.uncompressed_archive_size.0
+            )));
+        }
+        Ok(ZToc {
+            version: toc.version,
+            build_tool_identifier: toc.build_tool_identifier,
+            compressed_achrive_size: compression.compressed_archive_size,
+            uncompressed_archive_size: toc.uncompressed_archive_size,
+            toc: toc.toc,
+            compression_info: compression.compression_info,
+            annotations: toc.annotations,
+            compressed_digest: compression.compressed_digest,
+            metrics: BuildMetrics::default(),
+        })
+    }
+
+    /// Returns a builder for configuring how a ztoc is generated.
+    pub fn builder() -> ZtocBuilder {
+        ZtocBuilder::default()
+    }
+
+    /// Reads the contents of the entry named `name` from `archive`, the compressed layer this
+    /// ztoc was built from. Decompression resumes from the last checkpoint before the entry's
+    /// data, so only the spans covering it are read. Hardlinks resolve to the data of their
+    /// target and GNU sparse files are expanded, with zeros in their holes.
+    pub fn read_file<R>(
+        &self,
+        name: &str,
+        mut archive: R,
+    ) -> std::result::Result<Vec<u8>, ZtocError>
+    where
+        R: Read + Seek,
+    {
+        let entry = self.resolve_entry(name)?;
+        let expanded = entry.uncompressed_size.0 as usize;
+        let stored = entry.stored_size() as usize;
+        if stored == 0 {
+            return Ok(vec![0; expanded]);
+        }
+
+        let offset = entry.uncompressed_offset.0 as usize;
+        let checkpoints = parse_checkpoints(&self.compression_info.checkpoints)?;
+        let checkpoint = checkpoint_for_offset(&checkpoints, offset)
+            .map(|index| &checkpoints[index])
+            .ok_or_else(|| ZtocError::InvalidZtoc(format!("no checkpoint covers {}", name)))?;
+        let start = checkpoint.r#in - usize::from(checkpoint.bits > 0);
+        archive.seek(SeekFrom::Start(start as u64))?;
+        let data = inflate_from_checkpoint(archive, checkpoint, offset, stored)?;
+        if data.len() != stored {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("archive ended before the end of {}", name),
+            )
+            .into());
+        }
+        if entry.sparse.is_empty() {
+            return Ok(data);
+        }
+
+        let mut file = vec![0; expanded];
+        let mut segments = &data[..];
+        for &(segment_offset, length) in &entry.sparse {
+            let (segment, rest) = segments.split_at(length as usize);
+            let at = segment_offset as usize;
+            file.get_mut(at..at + segment.len())
+                .ok_or_else(|| {
+                    ZtocError::InvalidZtoc(format!("sparse segment past the end of {}", name))
+                })?
+                .copy_from_slice(segment);
+            segments = rest;
+        }
+        Ok(file)
+    }
+
+    /// Looks up the entry named `name`, following hardlinks to their target.
+    fn resolve_entry(&self, name: &str) -> std::result::Result<&FileMetadata, ZtocError> {
+        let find = |name: &Path| {
+            self.toc
+                .metadata
+                .iter()
+                .find(|entry| entry.name == name)
+                .ok_or_else(|| ZtocError::NotFound(name.to_path_buf()))
+        };
+        let mut entry = find(Path::new(name))?;
+        // Every link in a chain is a distinct entry, so a longer one must be a cycle.
+        for _ in 0..self.toc.metadata.len() {
+            if entry.r#type != tar::EntryType::Link {
+                return Ok(entry);
+            }
+            entry = find(entry.link_name.as_deref().unwrap_or(Path::new("")))?;
+        }
+        Err(ZtocError::InvalidZtoc(format!(
+            "hardlink cycle at {}",
+            name
+        )))
+    }
+
+    /// Returns a one-line summary of the layer, for example
+    /// `2500 files, 577 dirs, 184 symlinks, 18 spans, 4MiB span, 29MB -> 75MB, gzip`.
+    ///
+    /// Files are regular and contiguous entries. The span size is in binary units, the
+    /// compressed and uncompressed archive sizes are rounded to decimal units. The format is
+    /// stable so it can be parsed by dashboards.
+    pub fn describe(&self) -> String {
+        let count = |types: &[tar::EntryType]| {
+            self.toc
+                .metadata
+                .iter()
+                .filter(|entry| types.contains(&entry.r#type))
+                .count()
+        };
+        let span_size = self
+            .compression_info
+            .checkpoints
+            .get(4..CHECKPOINTS_HEADER_SIZE)
+            .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()));
+        format!(
+            "{} files, {} dirs, {} symlinks, {} spans, {} span, {} -> {}, gzip",
+            count(&[tar::EntryType::Regular, tar::EntryType::Continuous]),
+            count(&[tar::EntryType::Directory]),
+            count(&[tar::EntryType::Symlink]),
+            self.compression_info.max_span_id + 1,
+            format_binary_size(span_size),
+            format_decimal_size(self.compressed_achrive_size.0),
+            format_decimal_size(self.uncompressed_archive_size.0),
+        )
+    }
+}
+
+/// Copies the data offset and size of each hardlink's target to the hardlink. A target must
+/// precede its hardlinks in the archive, and a link to a hardlink resolves to the same data.
+fn resolve_hardlinks(toc: &mut Toc) -> std::result::Result<(), ZtocError> {
+    let mut data = HashMap::new();
+    for entry in &mut toc.metadata {
+        if entry.r#type == tar::EntryType::Link {
+            let target = entry.link_name.as_deref().unwrap_or(Path::new(""));
+            let &(offset, size) = data.get(target).ok_or_else(|| ZtocError::InvalidPath {
+                path: entry.name.clone(),
+                reason: format!("hardlink target {:?} is not in the archive", target),
+            })?;
+            entry.uncompressed_offset = CompressionOffset(offset);
+            entry.uncompressed_size = CompressionOffset(size);
+        }
+        data.insert(
+            entry.name.clone(),
+            (entry.uncompressed_offset.0, entry.uncompressed_size.0),
+        );
+    }
+    Ok(())
+}
+
+/// Formats `size` in the largest binary unit that divides it exactly, e.g. `4MiB`.
+fn format_binary_size(size: u64) -> String {
+    for (shift, unit) in [(30, "GiB"), (20, "MiB"), (10, "KiB")] {
+        if size != 0 && size.is_multiple_of(1 << shift) {
+            return format!("{}{}", size >> shift, unit);
+        }
+    }
+    format!("{}B", size)
+}
+
+/// Formats `size` rounded to the nearest whole decimal unit, e.g. `120MB`.
+fn format_decimal_size(size: u64) -> String {
+    for (scale, unit) in [(1_000_000_000, "GB"), (1_000_000, "MB"), (1_000, "kB")] {
+        if size >= scale {
+            return format!("{}{}", (size + scale / 2) / scale, unit);
+        }
+    }
+    format!("{}B", size)
+}
+
+/// Configures and builds a [`ZToc`] from a compressed layer.
+#[derive(Debug, Clone)]
+pub struct ZtocBuilder {
+    span_size: usize,
+    version: String,
+    build_tool_identifier: String,
+    extra_digest_algorithms: Vec<DigestAlgorithm>,
+    span_digest_kind: SpanDigestKind,
+    annotations: HashMap<String, String>,
+    compressed_digest: bool,
+    span_digests: bool,
+    digest_threads: usize,
+    span_timing: bool,
+    allowed_entry_types: Option<Vec<tar::EntryType>>,
+    included_entry_types: Option<Vec<tar::EntryType>>,
+    path_prefixes: Option<Vec<PathBuf>>,
+    prefix_ancestors: bool,
+    lossy_names: bool,
+    check_isize: bool,
+    compressed_span_offsets: bool,
+    skip_unreadable_entries: bool,
+    max_entries: Option<usize>,
+    resolve_hardlinks: bool,
+}
+
+impl Default for ZtocBuilder {
+    /// The defaults, which [`ZToc::new`] builds with:
+    ///
+    /// - span size: [`DEFAULT_SPAN_SIZE`], 4 MiB
+    /// - version: `"0.9"`, the ztoc format version soci reads
+    /// - build tool identifier: `"Replit SOCI v<crate version>"`
+    /// - compression algorithm: gzip, the only one supported
+    /// - span digests: SHA-256 only, over compressed span data, hashed while reading
+    /// - no annotations, compressed layer digest or span timing
+    /// - every entry type allowed and included in the TOC
+    /// - entries at every path included in the TOC
+    /// - uname and gname must be valid UTF-8
+    /// - no check of the uncompressed size against the gzip trailer
+    /// - no per-entry compressed span offsets
+    /// - an entry with unreadable metadata fails the build
+    /// - no limit on the number of entries
+    /// - hardlinks recorded with the zero size and offset tar gives them
+    fn default() -> Self {
+        ZtocBuilder {
+            span_size: DEFAULT_SPAN_SIZE,
+            version: String::from(ZTOC_VERSION),
+            build_tool_identifier: String::from(BUILD_TOOL_IDENTIFIER),
+            extra_digest_algorithms: Vec::new(),
+            span_digest_kind: SpanDigestKind::Compressed,
+            annotations: HashMap::new(),
+            compressed_digest: false,
+            span_digests: true,
+            digest_threads: 0,
+            span_timing: false,
+            allowed_entry_types: None,
+            included_entry_types: None,
+            path_prefixes: None,
+            prefix_ancestors: false,
+            lossy_names: false,
+            check_isize: false,
+            compressed_span_offsets: false,
+            skip_unreadable_entries: false,
+            max_entries: None,
+            resolve_hardlinks: false,
+        }
+    }
+}
+
+impl ZtocBuilder {
+    /// Sets the ztoc format version written to the ztoc, e.g. to target a newer reader. Only the
+    /// recorded string changes; the encoding is the same.
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Sets the build tool identifier written to the ztoc, e.g. to embed the caller's own tool
+    /// and version.
+    pub fn build_tool_identifier(mut self, identifier: impl Into<String>) -> Self {
+        self.build_tool_identifier = identifier.into();
+        self
+    }
+
+    /// Computes span digests with these algorithms in addition to SHA-256.
+    pub fn extra_digest_algorithms(mut self, algorithms: &[DigestAlgorithm]) -> Self {
+        self.extra_digest_algorithms = algorithms.to_vec();
+        self
+    }
+
+    /// Selects whether span digests cover compressed (the default, as in soci) or uncompressed
+    /// span data.
+    pub fn span_digest_kind(mut self, kind: SpanDigestKind) -> Self {
+        self.span_digest_kind = kind;
+        self
+    }
+
+    /// Adds an annotation to the ztoc, replacing any previous value for `key`.
+    pub fn annotation(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.annotations.insert(key.into(), value.into());
+        self
+    }
+
+    /// Computes the SHA-256 of the compressed layer while reading it, saving a separate pass
+    /// over the layer to compute e.g. a cache key.
+    pub fn compressed_digest(mut self, enabled: bool) -> Self {
+        self.compressed_digest = enabled;
+        self
+    }
+
+    /// Sets the minimum number of uncompressed bytes between checkpoints. Building fails if it
+    /// is 0, which would checkpoint every block.
+    pub fn span_size(mut self, span_size: usize) -> Self {
+        self.span_size = span_size;
+        self
+    }
+
+    /// Enables (the default) or disables computing span digests. Skipping them is faster, but
+    /// leaves `span_digests` empty so spans can't be verified by digest. Only meant for trusted
+    /// pipelines; soci expects span digests to be present.
+    pub fn span_digests(mut self, enabled: bool) -> Self {
+        self.span_digests = enabled;
+        self
+    }
+
+    /// Hashes span digests on `threads` worker threads instead of while reading the layer, see
+    /// [`GzipZInfoDecompressor::with_parallel_digests`]. The digests are the same either way.
+    pub fn parallel_digests(mut self, threads: usize) -> Self {
+        self.digest_threads = threads;
+        self
+    }
+
+    /// Records how long each span took to inflate in [`BuildMetrics::span_durations`], to find
+    /// regions that are slow to decompress.
+    pub fn span_timing(mut self, enabled: bool) -> Self {
+        self.span_timing = enabled;
+        self
+    }
+
+    /// Fails the build with [`ZtocError::DisallowedEntryType`] if the layer contains an entry
+    /// whose type is not in `types`, e.g. to reject device nodes. PAX and GNU long name headers
+    /// are part of the entry they precede and don't need to be listed.
+    pub fn allowed_entry_types(mut self, types: &[tar::EntryType]) -> Self {
+        self.allowed_entry_types = Some(types.to_vec());
+        self
+    }
+
+    /// Leaves entries whose type is not in `types` out of the TOC, e.g. `&[EntryType::Regular]`
+    /// to index only regular files. Hardlinks and symlinks are separate types and can be
+    /// included independently. Unlike [`Self::allowed_entry_types`], other entries don't fail the
+    /// build, and their data is still covered by the checkpoints.
+    pub fn included_entry_types(mut self, types: &[tar::EntryType]) -> Self {
+        self.included_entry_types = Some(types.to_vec());
+        self
+    }
+
+    /// Leaves entries out of the TOC unless their path starts with one of `prefixes`, e.g.
+    /// `usr/lib` to index a single directory. Paths are compared by component, so `usr/lib`
+    /// matches `usr/lib/libc.so` but not `usr/library`. Offsets stay those of the whole archive.
+    pub fn path_prefixes<P: AsRef<Path>>(mut self, prefixes: &[P]) -> Self {
+        self.path_prefixes = Some(prefixes.iter().map(|p| p.as_ref().to_path_buf()).collect());
+        self
+    }
+
+    /// With [`Self::path_prefixes`], also keeps the directory entries of each prefix's
+    /// ancestors, such as `usr` for `usr/lib`, so the TOC still describes a complete tree.
+    pub fn prefix_ancestors(mut self, enabled: bool) -> Self {
+        self.prefix_ancestors = enabled;
+        self
+    }
+
+    /// Decodes a uname or gname that isn't valid UTF-8 lossily, replacing invalid sequences with
+    /// U+FFFD, instead of failing the build. Affected entries are listed in
+    /// [`BuildMetrics::lossy_names`].
+    pub fn lossy_names(mut self, enabled: bool) -> Self {
+        self.lossy_names = enabled;
+        self
+    }
+
+    /// After reading the layer, checks that the uncompressed size matches the ISIZE field of the
+    /// gzip trailer (modulo 2^32), failing with [`ZtocError::SizeMismatch`] otherwise. This is a
+    /// sanity check of the decompressor's accounting, which the offsets in the ztoc rely on.
+    /// zlib-wrapped layers have no ISIZE field and aren't checked.
+    pub fn check_isize(mut self, enabled: bool) -> Self {
+        self.check_isize = enabled;
+        self
+    }
+
+    /// Records in each entry's [`FileMetadata::compressed_span_offset`] the compressed offset of
+    /// the checkpoint covering its data, saving readers a checkpoint lookup per entry. This makes
+    /// every TOC entry 8 bytes larger.
+    pub fn compressed_span_offsets(mut self, enabled: bool) -> Self {
+        self.compressed_span_offsets = enabled;
+        self
+    }
+
+    /// Leaves entries whose metadata can't be read, e.g. because of an out of range mtime or a
+    /// non-UTF-8 uname, out of the TOC and lists them in [`BuildMetrics::skipped_entries`]
+    /// instead of failing the build. Their data is still covered by the checkpoints. Malformed
+    /// tar headers still fail the build, since the rest of the archive can't be located.
+    pub fn skip_unreadable_entries(mut self, enabled: bool) -> Self {
+        self.skip_unreadable_entries = enabled;
+        self
+    }
+
+    /// Fails the build with [`ZtocError::TooManyEntries`] once the archive has more than `limit`
+    /// entries, counting skipped ones. This bounds the memory a crafted archive of many tiny
+    /// entries can make the build use.
+    pub fn max_entries(mut self, limit: usize) -> Self {
+        self.max_entries = Some(limit);
+        self
+    }
+
+    /// Records each hardlink with the `uncompressed_offset` and `uncompressed_size` of the entry
+    /// it links to, so readers can find its data without resolving the link. The build fails
+    /// with [`ZtocError::InvalidPath`] if a hardlink's target isn't an earlier entry.
+    pub fn resolve_hardlinks(mut self, enabled: bool) -> Self {
+        self.resolve_hardlinks = enabled;
+        self
+    }
+
+    pub fn build<R>(&self, reader: R) -> std::result::Result<ZToc, ZtocError>
+    where
+        R: Read,
+    {
+        let span_size = self.span_size;
+        if span_size == 0 {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "span size must be non-zero").into(),
+            );
+        }
+        let mut input = DigestingReader {
+            inner: reader,
+            hasher: self.compressed_digest.then(Sha256::new),
+        };
+        let mut decompressor = GzipZInfoDecompressor::new(&mut input, span_size)?
+            .with_extra_digest_algorithms(&self.extra_digest_algorithms)
+            .with_span_digest_kind(self.span_digest_kind)
+            .with_span_digests(self.span_digests)
+            .with_parallel_digests(self.digest_threads)
+            .with_span_timing(self.span_timing);
+        let mut lossy_names = Vec::new();
+        let mut skipped_entries = Vec::new();
+        let mut toc = read_tar_metadata(
+            &mut decompressor,
+            self.lossy_names.then_some(&mut lossy_names),
+            self.skip_unreadable_entries.then_some(&mut skipped_entries),
+            self.max_entries,
+        )?;
+        // Before filtering, which may leave out the targets.
+        if self.resolve_hardlinks {
+            resolve_hardlinks(&mut toc)?;
+        }
+        if let Some(allowed) = &self.allowed_entry_types {
+            if let Some(entry) = toc
+                .metadata
+                .iter()
+                .find(|entry| !allowed.contains(&entry.r#type))
+            {
+                return Err(ZtocError::DisallowedEntryType {
+                    path: entry.name.clone(),
+                    entry_type: entry.r#type,
+                });
+            }
+        }
+        if let Some(included) = &self.included_entry_types {
+            toc.metadata
+                .retain(|entry| included.contains(&entry.r#type));
+        }
+        if let Some(prefixes) = &self.path_prefixes {
+            toc.metadata.retain(|entry| {
+                prefixes.iter().any(|prefix| {
+                    entry.name.starts_with(prefix)
+                        || (self.prefix_ancestors
+                            && entry.r#type == tar::EntryType::Directory
+                            && prefix.starts_with(&entry.name))
+                })
+            });
+        }
+        let metrics = BuildMetrics {
+            span_durations: decompressor
+                .span_durations()
+                .map(<[_]>::to_vec)
+                .unwrap_or_default(),
+            lossy_names,
+            skipped_entries,
+        };
+        let isize = decompressor.gzip_isize();
+        let gzip = decompressor.is_gzip();
         let zinfo = decompressor.into_zinfo();
+        zinfo.validate()?;
+        if self.check_isize && gzip {
+            check_isize(zinfo.total_out, isize)?;
+        }
+        if self.compressed_span_offsets {
+            for entry in &mut toc.metadata {
+                entry.compressed_span_offset =
+                    checkpoint_for_offset(&zinfo.checkpoints, entry.uncompressed_offset.0 as usize)
+                        .map(|index| zinfo.checkpoints[index].r#in as u64);
+            }
+        }
+        let compressed_digest = if input.hasher.is_some() {
+            // Include anything after the end of the gzip stream, so the digest covers the layer
+            // exactly as it was read.
+            io::copy(&mut input, &mut io::sink())?;
+            input
+                .hasher
+                .map(|hasher| format!("sha256:{:x}", hasher.finalize()))
+        } else {
+            None
+        };
 
         Ok(ZToc {
-            version: String::from("0.9"),
-            build_tool_identifier: String::from("Replit SOCI v0.1"),
+            version: self.version.clone(),
+            build_tool_identifier: self.build_tool_identifier.clone(),
             compressed_achrive_size: CompressionOffset(zinfo.total_in as u64),
             uncompressed_archive_size: CompressionOffset(zinfo.total_out as u64),
             toc,
             compression_info: zinfo.into(),
+            annotations: self.annotations.clone(),
+            compressed_digest,
+            metrics,
         })
     }
 }
 
 #[derive(Debug)]
 pub struct CompressionInfo {
+    /// The number of spans - 1, or 0 for a zinfo without checkpoints.
     pub max_span_id: usize,
     pub span_digests: Vec<String>,
+    pub span_digest_kind: SpanDigestKind,
+    pub extra_span_digests: BTreeMap<DigestAlgorithm, Vec<String>>,
     pub checkpoints: Vec<u8>,
 }
 
 impl From<ZInfo> for CompressionInfo {
     fn from(zinfo: ZInfo) -> Self {
-        let mut checkpoints = Vec::new();
+        let checkpoints = zinfo.to_bytes();
+        CompressionInfo {
+            max_span_id: zinfo.checkpoints.len().saturating_sub(1),
+            span_digests: zinfo.span_digests,
+            span_digest_kind: zinfo.span_digest_kind,
+            extra_span_digests: zinfo.extra_span_digests,
+            checkpoints,
+        }
+    }
+}
+
+impl CompressionInfo {
+    /// Checks that the checkpoints blob holds `max_span_id + 1` checkpoints, or none with a
+    /// `max_span_id` of 0, and that there is one span digest per span, for every digest
+    /// algorithm. No span digests at all is consistent, as built with span digests disabled.
+    pub fn validate_consistency(&self) -> std::result::Result<(), ZtocError> {
+        if self.checkpoints.len() < CHECKPOINTS_HEADER_SIZE {
+            return Err(ZtocError::InvalidZtoc(
+                "checkpoints blob is missing its header".into(),
+            ));
+        }
+        let count = u32::from_le_bytes(self.checkpoints[..4].try_into().unwrap()) as usize;
+        if self.checkpoints.len() != CHECKPOINTS_HEADER_SIZE + count * CHECKPOINT_SIZE {
+            return Err(ZtocError::InvalidZtoc(format!(
+                "checkpoints blob is {} bytes but its header declares {} checkpoints",
+                self.checkpoints.len(),
+                count
+            )));
+        }
+        let spans = count;
+        if count.saturating_sub(1) != self.max_span_id {
+            return Err(ZtocError::InvalidZtoc(format!(
+                "max_span_id is {} but the checkpoints blob holds {} checkpoints",
+                self.max_span_id, count
+            )));
+        }
+        if self.span_digests.is_empty() && self.extra_span_digests.is_empty() {
+            return Ok(());
+        }
+        let digest_lists = std::iter::once(("sha256", &self.span_digests)).chain(
+            self.extra_span_digests
+                .iter()
+                .map(|(algorithm, digests)| (algorithm.name(), digests)),
+        );
+        for (algorithm, digests) in digest_lists {
+            if digests.len() != spans {
+                return Err(ZtocError::InvalidZtoc(format!(
+                    "{} {} span digests for {} spans",
+                    digests.len(),
+                    algorithm,
+                    spans
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the compressed and uncompressed byte ranges of each span, given the total sizes of
+    /// the archive. The last span extends to both totals.
+    ///
+    /// The compressed ranges are those covered by the span digests: a span whose checkpoint
+    /// starts partway through a byte includes that byte, so it overlaps the end of the previous
+    /// span by one byte. Fetching a span's compressed range is enough to decompress it from its
+    /// checkpoint.
+    pub fn span_ranges(
+        &self,
+        total_compressed: u64,
+        total_uncompressed: u64,
+    ) -> std::result::Result<Vec<SpanRanges>, ZtocError> {
+        let checkpoints = parse_checkpoints(&self.checkpoints)?;
+        Ok(checkpoints
+            .iter()
+            .enumerate()
+            .map(|(i, checkpoint)| {
+                let (compressed_end, uncompressed_end) = checkpoints
+                    .get(i + 1)
+                    .map_or((total_compressed, total_uncompressed), |next| {
+                        (next.r#in as u64, next.out as u64)
+                    });
+                let compressed_start = (checkpoint.r#in - usize::from(checkpoint.bits > 0)) as u64;
+                (
+                    compressed_start..compressed_end,
+                    checkpoint.out as u64..uncompressed_end,
+                )
+            })
+            .collect())
+    }
+
+    /// Classifies each checkpoint by where its uncompressed offset falls in `toc`: strictly
+    /// inside an entry's data, or at or between entry boundaries (including headers).
+    pub fn checkpoint_context(
+        &self,
+        toc: &Toc,
+    ) -> std::result::Result<Vec<CheckpointContext>, ZtocError> {
+        let mut files = toc
+            .metadata
+            .iter()
+            .filter(|entry| entry.uncompressed_size.0 > 0)
+            .collect::<Vec<_>>();
+        files.sort_by_key(|entry| entry.uncompressed_offset.0);
+
+        Ok(parse_checkpoints(&self.checkpoints)?
+            .iter()
+            .map(|checkpoint| {
+                let out = checkpoint.out as u64;
+                let index = files.partition_point(|entry| entry.uncompressed_offset.0 < out);
+                match index.checked_sub(1).map(|i| files[i]) {
+                    Some(entry)
+                        if entry.uncompressed_offset.0 + entry.uncompressed_size.0 > out =>
+                    {
+                        CheckpointContext::WithinFile {
+                            path: entry.name.clone(),
+                            offset: out - entry.uncompressed_offset.0,
+                        }
+                    }
+                    _ => CheckpointContext::Boundary,
+                }
+            })
+            .collect())
+    }
+}
+
+/// The compressed and uncompressed byte ranges of a span, in that order.
+pub type SpanRanges = (Range<u64>, Range<u64>);
+
+/// Where a checkpoint falls relative to the entries of the TOC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckpointContext {
+    /// The checkpoint is `offset` bytes into the data of the entry at `path`.
+    WithinFile { path: PathBuf, offset: u64 },
+    /// The checkpoint is at the start or end of an entry's data, or in between entries.
+    Boundary,
+}
+
+// Size of the optional end-of-stream sentinel: in, out and a marker byte in place of bits.
+pub(crate) const STREAM_END_SIZE: usize = 8 + 8 + 1;
+// Never a valid bit count, so a sentinel can't be mistaken for a checkpoint.
+const STREAM_END_MARKER: u8 = 0xff;
+// Set in the checkpoint count of a blob whose windows are zlib-compressed. Each checkpoint's
+// window is then replaced by the compressed window's length as a u32 and the compressed window.
+pub(crate) const COMPRESSED_WINDOWS: u32 = 1 << 31;
+// Set in the checkpoint count of a blob whose checkpoint offsets are LEB128 varints.
+const VARINT_OFFSETS: u32 = 1 << 30;
+
+/// The end of the compressed stream, as recorded by the optional sentinel in a checkpoints blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamEnd {
+    pub r#in: u64,
+    pub out: u64,
+}
+
+impl StreamEnd {
+    /// Serializes the sentinel, to be appended to a checkpoints blob.
+    pub(crate) fn to_bytes(self) -> [u8; STREAM_END_SIZE] {
+        let mut bytes = [0u8; STREAM_END_SIZE];
+        bytes[..8].copy_from_slice(&self.r#in.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.out.to_le_bytes());
+        bytes[16] = STREAM_END_MARKER;
+        bytes
+    }
+}
+
+/// Parses the checkpoints blob written by `CompressionInfo::from` back into checkpoints, also
+/// accepting blobs rewritten by [`compress_checkpoint_windows`] or [`varint_checkpoint_offsets`].
+/// An end-of-stream sentinel, if present, is ignored.
+///
+/// Fails with [`ZtocError::InvalidZtoc`] unless the checkpoints can be resumed from: offsets
+/// must strictly increase, and `bits` must be below 8 and only set past the first byte.
+pub fn parse_checkpoints(blob: &[u8]) -> Result<Vec<GZipCheckpoint>> {
+    let records = checkpoint_records(blob)?;
+    let checkpoints = records
+        .checkpoints
+        .iter()
+        .map(|record| {
+            Ok(GZipCheckpoint {
+                r#in: record.r#in as usize,
+                out: record.out as usize,
+                bits: record.bits,
+                window: if records.compressed_windows {
+                    decompress_window(record.window)?
+                } else {
+                    record.window.try_into().unwrap()
+                },
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    check_checkpoint_offsets(&checkpoints)
+        .map_err(|(index, msg)| ZtocError::InvalidZtoc(format!("checkpoint {}: {}", index, msg)))?;
+    Ok(checkpoints)
+}
+
+/// Rewrites a checkpoints blob, which must not have a sentinel, with every window
+/// zlib-compressed. Windows are often highly compressible and make up almost all of a ztoc, but
+/// only readers that know about compressed windows can parse the result.
+///
+/// Compression is flagged by the top bit of the checkpoint count in the header, so readers that
+/// don't know about it reject the blob instead of misreading it. Each checkpoint's window is
+/// replaced by the compressed size as a u32 followed by the compressed window.
+pub fn compress_checkpoint_windows(blob: &[u8]) -> Result<Vec<u8>> {
+    let records = checkpoint_records(blob)?;
+    let varint_offsets = records.varint_offsets;
+    rewrite_checkpoints(records, blob, true, varint_offsets)
+}
+
+/// Rewrites a checkpoints blob, which must not have a sentinel, with each checkpoint's `in` and
+/// `out` as LEB128 varints instead of fixed 8-byte integers.
+///
+/// This saves up to 14 bytes per checkpoint, and most for the early checkpoints of a layer. Next
+/// to 32 KiB windows that is negligible, so it mainly pays off together with
+/// [`compress_checkpoint_windows`]. It is flagged by the second highest bit of the checkpoint
+/// count in the header, so readers that don't know about it reject the blob instead of
+/// misreading it. Windows are not affected.
+pub fn varint_checkpoint_offsets(blob: &[u8]) -> Result<Vec<u8>> {
+    let records = checkpoint_records(blob)?;
+    let compressed_windows = records.compressed_windows;
+    rewrite_checkpoints(records, blob, compressed_windows, true)
+}
 
-        checkpoints.extend_from_slice(&(zinfo.checkpoints.len() as u32).to_le_bytes());
-        checkpoints.extend_from_slice(&(zinfo.span_size as u64).to_le_bytes());
+/// Rewrites a checkpoints blob in any of the layouts [`parse_checkpoints`] accepts into the
+/// layout written by `CompressionInfo::from`, with plain windows, fixed-size offsets and no
+/// sentinel.
+pub fn plain_checkpoints(blob: &[u8]) -> Result<Vec<u8>> {
+    let records = CheckpointRecords {
+        sentinel: false,
+        ..checkpoint_records(blob)?
+    };
+    rewrite_checkpoints(records, blob, false, false)
+}
 
-        for span in &zinfo.checkpoints {
-            checkpoints.extend_from_slice(&span.r#in.to_le_bytes());
-            checkpoints.extend_from_slice(&span.out.to_le_bytes());
-            checkpoints.push(span.bits);
-            checkpoints.extend_from_slice(&span.window);
+/// Writes the checkpoints of `blob` in the given layout.
+fn rewrite_checkpoints(
+    records: CheckpointRecords<'_>,
+    blob: &[u8],
+    compressed_windows: bool,
+    varint_offsets: bool,
+) -> Result<Vec<u8>> {
+    if records.sentinel {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "checkpoints blob has a sentinel",
+        ));
+    }
+    let mut count = records.checkpoints.len() as u32;
+    if compressed_windows {
+        count |= COMPRESSED_WINDOWS;
+    }
+    if varint_offsets {
+        count |= VARINT_OFFSETS;
+    }
+    let mut rewritten = Vec::with_capacity(blob.len());
+    rewritten.extend_from_slice(&count.to_le_bytes());
+    rewritten.extend_from_slice(&blob[4..CHECKPOINTS_HEADER_SIZE]);
+    for record in records.checkpoints {
+        if varint_offsets {
+            write_varint(&mut rewritten, record.r#in);
+            write_varint(&mut rewritten, record.out);
+        } else {
+            rewritten.extend_from_slice(&record.r#in.to_le_bytes());
+            rewritten.extend_from_slice(&record.out.to_le_bytes());
+        }
+        rewritten.push(record.bits);
+        match (records.compressed_windows, compressed_windows) {
+            (false, false) => rewritten.extend_from_slice(record.window),
+            (true, false) => rewritten.extend_from_slice(&decompress_window(record.window)?),
+            (from_compressed, true) => {
+                let window = if from_compressed {
+                    Cow::Borrowed(record.window)
+                } else {
+                    Cow::Owned(compress_window(record.window.try_into().unwrap())?)
+                };
+                rewritten.extend_from_slice(&(window.len() as u32).to_le_bytes());
+                rewritten.extend_from_slice(&window);
+            }
         }
+    }
+    Ok(rewritten)
+}
 
-        CompressionInfo {
-            max_span_id: zinfo.checkpoints.len() - 1,
-            span_digests: zinfo.span_digests,
-            checkpoints,
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
         }
     }
+    None
+}
+
+/// Returns the end-of-stream sentinel of a checkpoints blob, if it has one.
+///
+/// The sentinel is a trailing record after the `count` checkpoints declared in the header. It
+/// holds the total compressed and uncompressed sizes followed by a `0xff` marker byte in place of
+/// `bits`, and has no window. It isn't counted in the header, so readers that only look at the
+/// declared checkpoints are unaffected by it.
+pub fn parse_stream_end(blob: &[u8]) -> Result<Option<StreamEnd>> {
+    if !checkpoint_records(blob)?.sentinel {
+        return Ok(None);
+    }
+    let sentinel = &blob[blob.len() - STREAM_END_SIZE..];
+    Ok(Some(StreamEnd {
+        r#in: u64::from_le_bytes(sentinel[..8].try_into().unwrap()),
+        out: u64::from_le_bytes(sentinel[8..16].try_into().unwrap()),
+    }))
+}
+
+/// The serialized checkpoints of a checkpoints blob.
+struct CheckpointRecords<'a> {
+    compressed_windows: bool,
+    varint_offsets: bool,
+    checkpoints: Vec<CheckpointRecord<'a>>,
+    /// Whether an end-of-stream sentinel follows the checkpoints.
+    sentinel: bool,
+}
+
+/// A serialized checkpoint, with its window as stored, i.e. compressed if the blob's windows are.
+struct CheckpointRecord<'a> {
+    r#in: u64,
+    out: u64,
+    bits: u8,
+    window: &'a [u8],
+}
+
+/// Validates the header of a checkpoints blob and splits the rest into serialized checkpoints
+/// and the sentinel, if there is one.
+fn checkpoint_records(blob: &[u8]) -> Result<CheckpointRecords<'_>> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+    if blob.len() < CHECKPOINTS_HEADER_SIZE {
+        return Err(invalid("checkpoints blob is missing its header"));
+    }
+    let count = u32::from_le_bytes(blob[..4].try_into().unwrap());
+    let compressed_windows = count & COMPRESSED_WINDOWS != 0;
+    let varint_offsets = count & VARINT_OFFSETS != 0;
+    let count = (count & !(COMPRESSED_WINDOWS | VARINT_OFFSETS)) as usize;
+
+    fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        let (taken, rest) = bytes.split_at_checked(len)?;
+        *bytes = rest;
+        Some(taken)
+    }
+    let mut records = &blob[CHECKPOINTS_HEADER_SIZE..];
+    let mut read_record = || -> Option<CheckpointRecord> {
+        let mut offset = || match varint_offsets {
+            true => read_varint(&mut records),
+            false => Some(u64::from_le_bytes(
+                take(&mut records, 8)?.try_into().unwrap(),
+            )),
+        };
+        let (r#in, out) = (offset()?, offset()?);
+        let bits = take(&mut records, 1)?[0];
+        let window_size = match compressed_windows {
+            true => u32::from_le_bytes(take(&mut records, 4)?.try_into().unwrap()) as usize,
+            false => WINSIZE,
+        };
+        Some(CheckpointRecord {
+            r#in,
+            out,
+            bits,
+            window: take(&mut records, window_size)?,
+        })
+    };
+    let checkpoints = (0..count)
+        .map(|_| read_record())
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| invalid("checkpoints blob length does not match its checkpoint count"))?;
+
+    let sentinel = match records.len() {
+        0 => false,
+        STREAM_END_SIZE if records[STREAM_END_SIZE - 1] == STREAM_END_MARKER => true,
+        _ => {
+            return Err(invalid(
+                "checkpoints blob length does not match its checkpoint count",
+            ))
+        }
+    };
+    Ok(CheckpointRecords {
+        compressed_windows,
+        varint_offsets,
+        checkpoints,
+        sentinel,
+    })
 }
 
 #[derive(Debug)]
@@ -88,6 +1106,8 @@ pub struct FileMetadata {
     pub r#type: tar::EntryType,
     pub uncompressed_offset: CompressionOffset,
     pub uncompressed_size: CompressionOffset,
+    /// The link target. Always set for hardlinks and symlinks, even if the target is empty, and
+    /// `None` for every other entry type.
     pub link_name: Option<PathBuf>,
     pub mode: u32,
     pub uid: u64,
@@ -98,53 +1118,161 @@ pub struct FileMetadata {
     pub dev_major: Option<u32>,
     pub dev_minor: Option<u32>,
     pub x_attrs: HashMap<String, String>,
+    /// The compressed offset of the checkpoint covering the start of the entry's data, if
+    /// requested with [`ZtocBuilder::compressed_span_offsets`].
+    pub compressed_span_offset: Option<u64>,
+    /// The `(offset, length)` data segments of a GNU sparse file, in the expanded file's
+    /// coordinates, or empty for every other entry. `uncompressed_size` is the expanded size.
+    /// Only the segments in the entry's own header are available: the tar reader consumes any
+    /// GNU extension headers continuing the map without exposing them.
+    pub sparse: Vec<(u64, u64)>,
 }
 
 impl<R: Read> TryFrom<tar::Entry<'_, R>> for FileMetadata {
     type Error = io::Error;
 
     fn try_from(mut entry: tar::Entry<R>) -> std::result::Result<Self, Self::Error> {
+        FileMetadata::try_from(&mut entry)
+    }
+}
+
+impl<R: Read> TryFrom<&mut tar::Entry<'_, R>> for FileMetadata {
+    type Error = io::Error;
+
+    fn try_from(entry: &mut tar::Entry<R>) -> std::result::Result<Self, Self::Error> {
+        FileMetadata::from_entry(entry, false)
+    }
+}
+
+impl FileMetadata {
+    /// Returns how many bytes of the entry's data the archive stores: `uncompressed_size`,
+    /// except for a sparse file, which only stores its data segments.
+    pub fn stored_size(&self) -> u64 {
+        match self.sparse.is_empty() {
+            true => self.uncompressed_size.0,
+            false => self.sparse.iter().map(|&(_, length)| length).sum(),
+        }
+    }
+
+    /// Reads an entry's metadata, decoding a uname or gname that isn't valid UTF-8 lossily if
+    /// `lossy_names` is set and failing otherwise.
+    fn from_entry<R: Read>(entry: &mut tar::Entry<R>, lossy_names: bool) -> Result<Self> {
+        let path: PathBuf = entry.path()?.into();
+        let decode_name = |field, name: Option<&[u8]>| -> Result<Option<String>> {
+            name.map(|name| match std::str::from_utf8(name) {
+                Ok(name) => Ok(name.to_string()),
+                Err(_) if lossy_names => Ok(String::from_utf8_lossy(name).into_owned()),
+                Err(_) => Err(ZtocError::NonUtf8 {
+                    path: path.clone(),
+                    field,
+                }
+                .into()),
+            })
+            .transpose()
+        };
+        let pax: HashMap<String, String> = match entry.pax_extensions()? {
+            // No PAX header precedes this entry.
+            None => HashMap::new(),
+            Some(extensions) => extensions
+                .map(|ext| {
+                    ext.and_then(|ext| {
+                        Ok((
+                            ext.key().map_err(map_utf8_error)?.to_string(),
+                            ext.value().map_err(map_utf8_error)?.to_string(),
+                        ))
+                    })
+                })
+                .collect::<Result<_>>()
+                .map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "malformed PAX extensions for {}: {}",
+                            String::from_utf8_lossy(&entry.path_bytes()),
+                            err
+                        ),
+                    )
+                })?,
+        };
+        let mtime = entry.header().mtime()?;
         let mut meta = FileMetadata {
-            name: entry.path()?.into(),
+            uname: decode_name("uname", entry.header().username_bytes())?,
+            gname: decode_name("gname", entry.header().groupname_bytes())?,
+            mod_time: i64::try_from(mtime)
+                .ok()
+                .and_then(|mtime| DateTime::from_timestamp(mtime, 0))
+                .ok_or_else(|| ZtocError::InvalidMtime {
+                    path: path.clone(),
+                    mtime,
+                })?
+                .naive_utc(),
+            name: path,
             r#type: entry.header().entry_type(),
             uncompressed_offset: CompressionOffset(entry.raw_file_position()),
             uncompressed_size: CompressionOffset(entry.size()),
-            link_name: entry.link_name()?.map(Into::into),
+            link_name: match entry.header().entry_type() {
+                // tar reports an empty link target as no target at all.
+                tar::EntryType::Link | tar::EntryType::Symlink => {
+                    Some(entry.link_name()?.map(Into::into).unwrap_or_default())
+                }
+                _ => entry.link_name()?.map(Into::into),
+            },
             mode: entry.header().mode()?,
             uid: entry.header().uid()?,
             gid: entry.header().gid()?,
-            uname: entry
-                .header()
-                .username()
-                .map_err(map_utf8_error)?
-                .map(Into::into),
-            gname: entry
-                .header()
-                .groupname()
-                .map_err(map_utf8_error)?
-                .map(Into::into),
-            mod_time: DateTime::from_timestamp(entry.header().mtime()? as i64, 0)
-                .ok_or(io::Error::new(io::ErrorKind::InvalidData, "invalid mtime"))?
-                .naive_utc(),
             dev_major: None,
             dev_minor: None,
-            // lol maybe I went too far...
-            x_attrs: entry
-                .pax_extensions()?
-                .map(|exts| {
-                    exts.map(|ext| {
-                        ext.and_then(|ext| {
-                            Ok((
-                                ext.key().map_err(map_utf8_error)?.to_string(),
-                                ext.value().map_err(map_utf8_error)?.to_string(),
-                            ))
-                        })
-                    })
-                    .collect::<Result<_>>()
+            compressed_span_offset: None,
+            sparse: match entry.header().as_gnu() {
+                Some(gnu) if entry.header().entry_type().is_gnu_sparse() => gnu
+                    .sparse
+                    .iter()
+                    .filter(|segment| !segment.is_empty())
+                    .map(|segment| Ok((segment.offset()?, segment.length()?)))
+                    .collect::<Result<_>>()?,
+                _ => Vec::new(),
+            },
+            // Other PAX records are header fields, handled below or by the tar reader itself.
+            x_attrs: pax
+                .iter()
+                .filter_map(|(key, value)| {
+                    let key = key.strip_prefix(PAX_XATTR_PREFIX)?;
+                    Some((key.to_string(), value.clone()))
                 })
-                .transpose()?
-                .unwrap_or_default(),
+                .collect(),
         };
+        // Ownership that does not fit the header fields is only recorded in PAX records, with the
+        // header fields left zero or truncated, so the records take precedence.
+        for (key, value) in [("uid", &mut meta.uid), ("gid", &mut meta.gid)] {
+            if let Some(record) = pax.get(key) {
+                *value = record.parse().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "invalid PAX {} {:?} for {}",
+                            key,
+                            record,
+                            meta.name.display()
+                        ),
+                    )
+                })?;
+            }
+        }
+        // The header only holds whole seconds, PAX records may add a fraction.
+        if let Some(record) = pax.get("mtime") {
+            meta.mod_time = parse_pax_time(record).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid PAX mtime {:?} for {}", record, meta.name.display()),
+                )
+            })?;
+        }
+        if let Some(uname) = pax.get("uname") {
+            meta.uname = Some(uname.clone());
+        }
+        if let Some(gname) = pax.get("gname") {
+            meta.gname = Some(gname.clone());
+        }
         if matches!(
             entry.header().entry_type(),
             tar::EntryType::Block | tar::EntryType::Char
@@ -156,17 +1284,152 @@ impl<R: Read> TryFrom<tar::Entry<'_, R>> for FileMetadata {
     }
 }
 
-fn generate_tar_metadata<R: Read>(reader: &mut R) -> Result<Toc> {
-    let mut archive = Archive::new(reader);
-    archive.set_unpack_xattrs(true);
-    archive.set_preserve_permissions(true);
-    let metadata = archive
-        .entries()?
-        .map(|entry| entry.and_then(TryInto::try_into))
-        .collect::<Result<_>>()?;
+/// Parses a PAX time record, decimal seconds since the epoch with an optional fraction such as
+/// `1700000000.123456789`. Digits beyond nanoseconds are truncated.
+fn parse_pax_time(record: &str) -> Option<NaiveDateTime> {
+    let (negative, unsigned) = match record.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, record),
+    };
+    let (secs, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if secs.is_empty() || !all_digits(secs) || !all_digits(fraction) {
+        return None;
+    }
+    let mut secs: i64 = secs.parse().ok()?;
+    let mut nanos = fraction
+        .bytes()
+        .chain(std::iter::repeat(b'0'))
+        .take(9)
+        .fold(0, |nanos, digit| nanos * 10 + u32::from(digit - b'0'));
+    if negative {
+        secs = -secs;
+        // -1.25 is 1.25 seconds before the epoch, i.e. 0.75 seconds after -2.
+        if nanos > 0 {
+            secs -= 1;
+            nanos = 1_000_000_000 - nanos;
+        }
+    }
+    DateTime::from_timestamp(secs, nanos).map(|time| time.naive_utc())
+}
+
+/// Checks the uncompressed size `total_out` against the gzip trailer's `isize`, which is `None` if
+/// the stream has no trailer.
+fn check_isize(total_out: usize, isize: Option<u32>) -> std::result::Result<(), ZtocError> {
+    match isize {
+        Some(isize) if isize == total_out as u32 => Ok(()),
+        isize => Err(ZtocError::SizeMismatch {
+            total_out: total_out as u64,
+            isize,
+        }),
+    }
+}
+
+fn generate_tar_metadata<R: Read>(reader: &mut R) -> std::result::Result<Toc, ZtocError> {
+    read_tar_metadata(reader, None, None, None)
+}
+
+/// Reads the TOC of a tar archive, and then the rest of `reader` up to EOF. If `lossy_names` is
+/// set, unames and gnames that aren't valid UTF-8 are decoded lossily and the affected entries
+/// are added to it. If `skipped` is set, entries whose metadata can't be read are left out of
+/// the TOC and added to it instead.
+fn read_tar_metadata<R: Read>(
+    reader: &mut R,
+    mut lossy_names: Option<&mut Vec<PathBuf>>,
+    mut skipped: Option<&mut Vec<SkippedEntry>>,
+    max_entries: Option<usize>,
+) -> std::result::Result<Toc, ZtocError> {
+    let mut reader = CountingReader {
+        inner: reader,
+        count: 0,
+    };
+    let mut metadata: Vec<FileMetadata> = Vec::new();
+    let result = (|| -> std::result::Result<(), ZtocError> {
+        let mut archive = Archive::new(&mut reader);
+        archive.set_unpack_xattrs(true);
+        archive.set_preserve_permissions(true);
+        for (index, entry) in archive.entries()?.enumerate() {
+            if let Some(limit) = max_entries.filter(|&limit| index >= limit) {
+                return Err(ZtocError::TooManyEntries { limit });
+            }
+            let mut entry = entry?;
+            let meta = match FileMetadata::from_entry(&mut entry, lossy_names.is_some()) {
+                Ok(meta) => meta,
+                Err(error) => match &mut skipped {
+                    Some(skipped) => {
+                        skipped.push(SkippedEntry { index, error });
+                        continue;
+                    }
+                    None => return Err(error.into()),
+                },
+            };
+            if let Some(lossy_names) = &mut lossy_names {
+                let header = entry.header();
+                if [header.username_bytes(), header.groupname_bytes()]
+                    .into_iter()
+                    .flatten()
+                    .any(|name| std::str::from_utf8(name).is_err())
+                {
+                    lossy_names.push(meta.name.clone());
+                }
+            }
+            metadata.push(meta);
+        }
+        // The archive stops at the first zero block of its end marker, leaving the second one
+        // and any padding to the record size unread.
+        io::copy(&mut reader, &mut io::sink())?;
+        Ok(())
+    })();
+
+    if let Err(err) = result {
+        // If the archive failed while the last entry's data was still outstanding, the archive
+        // was cut short rather than being malformed.
+        return Err(match (err, metadata.last()) {
+            (ZtocError::Io(_), Some(last))
+                if last.uncompressed_offset.0 + last.stored_size() > reader.count =>
+            {
+                ZtocError::TruncatedEntry {
+                    path: last.name.clone(),
+                    expected: last.stored_size(),
+                    available: reader.count.saturating_sub(last.uncompressed_offset.0),
+                }
+            }
+            (err, _) => err,
+        });
+    }
     Ok(Toc { metadata })
 }
 
+/// Tracks how many bytes have been read from the underlying reader.
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+/// Hashes the bytes read from the underlying reader, if a hasher is set.
+struct DigestingReader<R> {
+    inner: R,
+    hasher: Option<Sha256>,
+}
+
+impl<R: Read> Read for DigestingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
 fn map_utf8_error(_: Utf8Error) -> io::Error {
     io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-8")
 }
@@ -175,7 +1438,10 @@ fn map_utf8_error(_: Utf8Error) -> io::Error {
 mod test {
     use std::io::Cursor;
 
-    use crate::zinfo::GzipZInfoDecompressor;
+    use crate::{
+        testutil,
+        zinfo::{build_zinfo, GzipZInfoDecompressor},
+    };
 
     use super::*;
 
@@ -206,4 +1472,1101 @@ mod test {
                 .collect::<Vec<&str>>(),
         );
     }
+
+    #[test]
+    fn test_describe() {
+        let ztoc = ZToc::new(&include_bytes!("testdata/layer.tar.gz")[..]).unwrap();
+        assert_eq!(
+            ztoc.describe(),
+            "2500 files, 577 dirs, 184 symlinks, 18 spans, 4MiB span, 29MB -> 75MB, gzip"
+        );
+
+        assert_eq!(format_binary_size(1 << 16), "64KiB");
+        assert_eq!(format_binary_size(1000), "1000B");
+        assert_eq!(format_decimal_size(999), "999B");
+        assert_eq!(format_decimal_size(1_500), "2kB");
+    }
+
+    #[test]
+    fn test_pax_extensions() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "plain", &b"hello"[..])
+            .unwrap();
+        let records = b"37 SCHILY.xattr.user.comment=careful\n29 SCHILY.xattr.user.foo=bar\n28 path=annotated-long-name\n";
+        let mut header = testutil::header(tar::EntryType::XHeader, records.len() as u64);
+        builder
+            .append_data(&mut header, "PaxHeaders/annotated", &records[..])
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "annotated", &b"hello"[..])
+            .unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        let toc = generate_tar_metadata(&mut Cursor::new(&tar)).unwrap();
+        assert!(toc.metadata[0].x_attrs.is_empty());
+        let annotated = &toc.metadata[1];
+        assert_eq!(annotated.name, Path::new("annotated-long-name"));
+        assert_eq!(
+            annotated.x_attrs,
+            HashMap::from([
+                ("user.comment".to_string(), "careful".to_string()),
+                ("user.foo".to_string(), "bar".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tar_metadata_reads_to_eof() {
+        let compressed = include_bytes!("testdata/test.tar.gz");
+        let tar = include_bytes!("testdata/test.tar");
+        let mut decompressor = GzipZInfoDecompressor::new(&compressed[..], 1 << 12).unwrap();
+        let toc = generate_tar_metadata(&mut decompressor).unwrap();
+        assert!(!toc.metadata.is_empty());
+        let zinfo = decompressor.into_zinfo();
+        assert_eq!(zinfo.total_in, compressed.len());
+        assert_eq!(zinfo.total_out, tar.len());
+        // The end marker and the padding after it aren't part of any entry.
+        let last = toc.metadata.last().unwrap();
+        assert!(last.uncompressed_offset.0 + last.uncompressed_size.0 + 1024 <= tar.len() as u64);
+    }
+
+    #[test]
+    fn test_gnu_sparse_entry() {
+        // 1 KiB of data stored as two segments of an 8 KiB file, ending in a zero-length segment
+        // that marks the trailing hole, as GNU tar writes it.
+        let segments = [(0, 512), (4096, 512), (8192, 0)];
+        let mut header = testutil::header(tar::EntryType::GNUSparse, 1024);
+        let gnu = header.as_gnu_mut().unwrap();
+        let octal = |field: &mut [u8; 12], value: u64| {
+            field.copy_from_slice(format!("{:011o}\0", value).as_bytes())
+        };
+        for (sparse, &(offset, length)) in gnu.sparse.iter_mut().zip(&segments) {
+            octal(&mut sparse.offset, offset);
+            octal(&mut sparse.numbytes, length);
+        }
+        octal(&mut gnu.realsize, 8192);
+        let mut builder = tar::Builder::new(Vec::new());
+        builder
+            .append_data(&mut header, "sparse", &[7u8; 1024][..])
+            .unwrap();
+        builder
+            .append_data(
+                &mut testutil::header(tar::EntryType::Regular, 3),
+                "dense",
+                &b"abc"[..],
+            )
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let ztoc = ZToc::new(&layer[..]).unwrap();
+        let [sparse, dense] = &ztoc.toc.metadata[..] else {
+            panic!("expected two entries, got {:?}", ztoc.toc.metadata);
+        };
+        assert_eq!(sparse.sparse, segments);
+        assert_eq!(sparse.uncompressed_size.0, 8192);
+        assert!(dense.sparse.is_empty());
+
+        let expanded = ztoc.read_file("sparse", Cursor::new(&layer)).unwrap();
+        assert_eq!(expanded.len(), 8192);
+        assert!(expanded[..512].iter().all(|&byte| byte == 7));
+        assert!(expanded[512..4096].iter().all(|&byte| byte == 0));
+        assert!(expanded[4096..4608].iter().all(|&byte| byte == 7));
+        assert!(expanded[4608..].iter().all(|&byte| byte == 0));
+
+        let decoded =
+            crate::decode::decode_ztoc(&crate::encode::encode_ztoc(&ztoc).unwrap()).unwrap();
+        assert_eq!(decoded.toc.metadata[0].sparse, segments);
+        assert_eq!(decoded.toc.metadata[0].r#type, tar::EntryType::Regular);
+        assert!(decoded.toc.metadata[1].sparse.is_empty());
+    }
+
+    #[test]
+    fn test_gzip_header_crc_mismatch() {
+        let tar = testutil::tar_of(&[("file", b"contents")]);
+        let plain = testutil::gzip_with_empty_blocks(&tar, 0);
+        let mut layer = testutil::gzip_with_header_fields(&tar);
+        // The FHCRC field is the last two bytes of the header, right before the deflate stream
+        // that follows the 10 byte fixed header of `plain`.
+        let crc = layer.len() - (plain.len() - 10) - 2;
+        layer[crc] ^= 1;
+        let err = build_zinfo(&layer[..], 1 << 14).unwrap_err();
+        assert!(err.to_string().contains("header crc mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn test_gzip_header_fields() {
+        let tar = testutil::tar_of(&[("random", &testutil::random_bytes(1 << 18, 3))]);
+        let plain = testutil::gzip_with_empty_blocks(&tar, 0);
+        let layer = testutil::gzip_with_header_fields(&tar);
+        let header_fields = layer.len() - plain.len();
+        assert!(header_fields > 0);
+
+        let zinfo_of = |layer: &[u8]| build_zinfo(layer, 1 << 14).unwrap();
+        let (plain_zinfo, zinfo) = (zinfo_of(&plain), zinfo_of(&layer));
+        assert!(zinfo.checkpoints.len() > 2);
+        assert_eq!(zinfo.total_in, layer.len());
+        assert_eq!(zinfo.total_out, tar.len());
+        // Only the compressed offsets move, by exactly the length of the extra header fields.
+        assert_eq!(zinfo.checkpoints.len(), plain_zinfo.checkpoints.len());
+        for (checkpoint, plain) in zinfo.checkpoints.iter().zip(&plain_zinfo.checkpoints) {
+            assert_eq!(checkpoint.r#in, plain.r#in + header_fields);
+            assert_eq!((checkpoint.out, checkpoint.bits), (plain.out, plain.bits));
+            assert_eq!(checkpoint.window, plain.window);
+        }
+
+        let ztoc = ZToc::from_tar_and_zinfo(&tar[..], zinfo).unwrap();
+        assert_eq!(ztoc.compressed_achrive_size.0, layer.len() as u64);
+        let checkpoints = parse_checkpoints(&ztoc.compression_info.checkpoints).unwrap();
+        let checkpoint = &checkpoints[1];
+        let start = checkpoint.r#in - usize::from(checkpoint.bits > 0);
+        let data = crate::zinfo::inflate_from_checkpoint(
+            &layer[start..],
+            checkpoint,
+            checkpoint.out,
+            1 << 10,
+        )
+        .unwrap();
+        assert_eq!(data, tar[checkpoint.out..checkpoint.out + (1 << 10)]);
+    }
+
+    #[test]
+    fn test_pax_ownership() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let records = b"15 uid=4000000\n15 gid=4000001\n15 uname=alice\n";
+        let mut header = testutil::header(tar::EntryType::XHeader, records.len() as u64);
+        builder
+            .append_data(&mut header, "PaxHeaders/owned", &records[..])
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        header.set_uid(0);
+        header.set_gid(0);
+        builder
+            .append_data(&mut header, "owned", &b"hello"[..])
+            .unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        let toc = generate_tar_metadata(&mut Cursor::new(&tar)).unwrap();
+        let owned = &toc.metadata[0];
+        assert_eq!((owned.uid, owned.gid), (4_000_000, 4_000_001));
+        assert_eq!(owned.uname.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn test_pax_mtime() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let records = b"30 mtime=1700000000.123456789\n";
+        let mut header = testutil::header(tar::EntryType::XHeader, records.len() as u64);
+        builder
+            .append_data(&mut header, "PaxHeaders/precise", &records[..])
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "precise", &b"hello"[..])
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "coarse", &b"hello"[..])
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let ztoc = ZToc::new(&layer[..]).unwrap();
+        let encoded = crate::encode::encode_ztoc(&ztoc).unwrap();
+        let decoded = crate::ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let entries = decoded.toc().unwrap().metadata().unwrap();
+        assert_eq!(
+            entries.get(0).mod_time(),
+            Some("2023-11-14T22:13:20.123456789+00:00")
+        );
+        assert_eq!(entries.get(1).mod_time(), Some("2023-11-14T22:13:20+00:00"));
+
+        let parse = |record| parse_pax_time(record).map(|time| time.and_utc().to_rfc3339());
+        assert_eq!(
+            parse("1.5").as_deref(),
+            Some("1970-01-01T00:00:01.500+00:00")
+        );
+        assert_eq!(
+            parse("-1.25").as_deref(),
+            Some("1969-12-31T23:59:58.750+00:00")
+        );
+        assert_eq!(
+            parse("1.1234567899").as_deref(),
+            Some("1970-01-01T00:00:01.123456789+00:00")
+        );
+        for invalid in ["", ".5", "1.5s", "1e9", "--1"] {
+            assert_eq!(parse(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_gnu_incremental() {
+        // Produced by `tar --listed-incremental=... --owner=1234 --group=5678 -cf`, which writes
+        // directories as GNU dumpdir ('D') entries listing their contents.
+        let tar = include_bytes!("testdata/incremental.tar");
+        let toc = generate_tar_metadata(&mut Cursor::new(&tar[..])).unwrap();
+        assert_eq!(toc.metadata.len(), 2);
+        let dir = &toc.metadata[0];
+        assert_eq!(dir.name, PathBuf::from("d/"));
+        assert_eq!(dir.r#type, tar::EntryType::new(b'D'));
+        assert_eq!((dir.uid, dir.gid, dir.mode), (1234, 5678, 0o755));
+        let file = &toc.metadata[1];
+        assert_eq!((file.uid, file.gid, file.mode), (1234, 5678, 0o644));
+    }
+
+    #[test]
+    fn test_v7_tar() {
+        // Produced by `tar --format=v7 --owner=1234 --group=5678 -cf`. v7 headers have no magic,
+        // uname, gname or device fields, and mark regular files with a NUL type.
+        let tar = include_bytes!("testdata/v7.tar");
+        assert!(tar::Header::from_byte_slice(&tar[..512])
+            .as_ustar()
+            .is_none());
+        let ztoc = ZToc::new(&testutil::gzip(tar)[..]).unwrap();
+        let metadata = &ztoc.toc.metadata;
+        assert_eq!(metadata.len(), 3);
+        let types = metadata
+            .iter()
+            .map(|entry| entry.r#type)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                tar::EntryType::Directory,
+                tar::EntryType::Regular,
+                tar::EntryType::Symlink,
+            ]
+        );
+        for entry in metadata {
+            assert_eq!((entry.uid, entry.gid), (1234, 5678), "{:?}", entry.name);
+            assert_eq!((entry.uname.as_ref(), entry.gname.as_ref()), (None, None));
+            assert_eq!(entry.mod_time.and_utc().timestamp(), 1_700_000_000);
+        }
+        assert_eq!(metadata[0].mode, 0o750);
+        assert_eq!(metadata[1].mode, 0o640);
+        assert_eq!(metadata[1].uncompressed_size.0, 6);
+        assert_eq!(metadata[2].link_name, Some(PathBuf::from("f")));
+        crate::encode::encode_ztoc(&ztoc).unwrap();
+    }
+
+    #[test]
+    fn test_malformed_pax_extensions() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let records = b"not a pax record\n";
+        let mut header = testutil::header(tar::EntryType::XHeader, records.len() as u64);
+        builder
+            .append_data(&mut header, "PaxHeaders/file", &records[..])
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "file", &b"hello"[..])
+            .unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        let err = generate_tar_metadata(&mut Cursor::new(&tar)).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("malformed PAX extensions for file"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_compressed_digest() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        assert_eq!(ZToc::new(layer).unwrap().compressed_digest, None);
+
+        // From `sha256sum src/testdata/test.tar.gz`.
+        let ztoc = ZToc::builder()
+            .compressed_digest(true)
+            .build(layer)
+            .unwrap();
+        assert_eq!(
+            ztoc.compressed_digest.as_deref(),
+            Some("sha256:fc73410932225e89d3d9be4e4782111e9e0510d74c4126bf3b902d398a7e688f")
+        );
+
+        // Bytes after the gzip stream are part of the layer too.
+        let mut trailing = layer.to_vec();
+        trailing.extend_from_slice(&[0; 16]);
+        let ztoc = ZToc::builder()
+            .compressed_digest(true)
+            .build(&trailing[..])
+            .unwrap();
+        assert_eq!(
+            ztoc.compressed_digest.unwrap(),
+            format!("sha256:{:x}", Sha256::digest(&trailing))
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_context() {
+        let large = testutil::random_bytes(300_000, 3);
+        let tar = testutil::tar_of(&[("small", b"hello"), ("large", &large), ("after", b"bye")]);
+        let compressed = testutil::gzip(&tar);
+
+        let mut decompressor = GzipZInfoDecompressor::new(&compressed[..], 1 << 16).unwrap();
+        let toc = generate_tar_metadata(&mut decompressor).unwrap();
+        io::copy(&mut decompressor, &mut io::sink()).unwrap();
+        let zinfo = decompressor.into_zinfo();
+        let outs = zinfo
+            .checkpoints
+            .iter()
+            .map(|c| c.out as u64)
+            .collect::<Vec<_>>();
+        let info = CompressionInfo::from(zinfo);
+
+        let large = &toc.metadata[1];
+        let contexts = info.checkpoint_context(&toc).unwrap();
+        assert_eq!(contexts.len(), outs.len());
+        assert_eq!(contexts[0], CheckpointContext::Boundary);
+        let mut within = 0;
+        for (out, context) in outs.iter().zip(&contexts) {
+            let start = large.uncompressed_offset.0;
+            if *out > start && *out < start + large.uncompressed_size.0 {
+                within += 1;
+                assert_eq!(
+                    *context,
+                    CheckpointContext::WithinFile {
+                        path: "large".into(),
+                        offset: out - start,
+                    }
+                );
+            } else {
+                assert_eq!(*context, CheckpointContext::Boundary);
+            }
+        }
+        assert!(within >= 3, "{:?}", contexts);
+    }
+
+    #[test]
+    fn test_span_ranges() {
+        let compressed = testutil::gzip(&testutil::text_like(1 << 20));
+        let mut decompressor = GzipZInfoDecompressor::new(&compressed[..], 1 << 16)
+            .unwrap()
+            .with_span_digest_kind(SpanDigestKind::Compressed);
+        let mut uncompressed = Vec::new();
+        decompressor.read_to_end(&mut uncompressed).unwrap();
+        let zinfo = decompressor.into_zinfo();
+        let bits = zinfo.checkpoints.iter().map(|c| c.bits).collect::<Vec<_>>();
+        let info = CompressionInfo::from(zinfo);
+
+        let ranges = info
+            .span_ranges(compressed.len() as u64, uncompressed.len() as u64)
+            .unwrap();
+        assert_eq!(ranges.len(), info.max_span_id + 1);
+        assert!(ranges.len() > 2);
+        assert_eq!(ranges[0].1.start, 0);
+        assert_eq!(ranges.last().unwrap().0.end, compressed.len() as u64);
+        assert_eq!(ranges.last().unwrap().1.end, uncompressed.len() as u64);
+        for (i, pair) in ranges.windows(2).enumerate() {
+            let ((compressed, uncompressed), (next_compressed, next_uncompressed)) =
+                (&pair[0], &pair[1]);
+            assert_eq!(uncompressed.end, next_uncompressed.start);
+            let shared = u64::from(bits[i + 1] > 0);
+            assert_eq!(compressed.end, next_compressed.start + shared);
+        }
+
+        // The compressed ranges are exactly what the span digests cover.
+        for ((range, _), digest) in ranges.iter().zip(&info.span_digests) {
+            let span = &compressed[range.start as usize..range.end as usize];
+            assert_eq!(*digest, format!("sha256:{:x}", Sha256::digest(span)));
+        }
+    }
+
+    #[test]
+    fn test_allowed_entry_types() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "file", &b"hello"[..])
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Char, 0);
+        header.set_device_major(1).unwrap();
+        header.set_device_minor(3).unwrap();
+        builder
+            .append_data(&mut header, "dev/null", io::empty())
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let allowed = [
+            tar::EntryType::Regular,
+            tar::EntryType::Directory,
+            tar::EntryType::Symlink,
+            tar::EntryType::Link,
+        ];
+        let err = ZToc::builder()
+            .allowed_entry_types(&allowed)
+            .build(&layer[..])
+            .unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                ZtocError::DisallowedEntryType { path, entry_type: tar::EntryType::Char }
+                    if path == &PathBuf::from("dev/null")
+            ),
+            "{}",
+            err
+        );
+
+        let ztoc = ZToc::builder().build(&layer[..]).unwrap();
+        assert_eq!(ztoc.toc.metadata[1].r#type, tar::EntryType::Char);
+    }
+
+    #[test]
+    fn test_included_entry_types() {
+        use tar::EntryType;
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(EntryType::Directory, 0);
+        builder
+            .append_data(&mut header, "dir", io::empty())
+            .unwrap();
+        let mut header = testutil::header(EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "dir/file", &b"hello"[..])
+            .unwrap();
+        let mut header = testutil::header(EntryType::Symlink, 0);
+        builder
+            .append_link(&mut header, "symlink", "dir/file")
+            .unwrap();
+        let mut header = testutil::header(EntryType::Link, 0);
+        builder
+            .append_link(&mut header, "hardlink", "dir/file")
+            .unwrap();
+        let mut header = testutil::header(EntryType::Fifo, 0);
+        builder
+            .append_data(&mut header, "fifo", io::empty())
+            .unwrap();
+        let mut header = testutil::header(EntryType::Regular, 3);
+        builder
+            .append_data(&mut header, "dir/other", &b"bye"[..])
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let names = |types: Option<&[EntryType]>| {
+            let mut builder = ZToc::builder();
+            if let Some(types) = types {
+                builder = builder.included_entry_types(types);
+            }
+            let ztoc = builder.build(&layer[..]).unwrap();
+            ztoc.toc
+                .metadata
+                .iter()
+                .map(|entry| entry.name.to_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(None),
+            [
+                "dir",
+                "dir/file",
+                "symlink",
+                "hardlink",
+                "fifo",
+                "dir/other"
+            ]
+        );
+        assert_eq!(
+            names(Some(&[EntryType::Regular])),
+            ["dir/file", "dir/other"]
+        );
+        assert_eq!(
+            names(Some(&[EntryType::Regular, EntryType::Symlink])),
+            ["dir/file", "symlink", "dir/other"]
+        );
+        assert_eq!(names(Some(&[EntryType::Link])), ["hardlink"]);
+    }
+
+    #[test]
+    fn test_path_prefixes() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let all = ZToc::new(layer).unwrap();
+        let ztoc = ZToc::builder()
+            .path_prefixes(&["src/testdata/"])
+            .build(layer)
+            .unwrap();
+        let names: Vec<_> = ztoc.toc.metadata.iter().map(|entry| &entry.name).collect();
+        assert_eq!(names, [Path::new("src/testdata/")]);
+        let ztoc = ZToc::builder()
+            .path_prefixes(&["src/testdata/"])
+            .prefix_ancestors(true)
+            .build(layer)
+            .unwrap();
+        let names: Vec<_> = ztoc.toc.metadata.iter().map(|entry| &entry.name).collect();
+        assert_eq!(names, [Path::new("src/"), Path::new("src/testdata/")]);
+
+        // Offsets are those of the whole archive.
+        let ztoc = ZToc::builder()
+            .path_prefixes(&["src/main.rs"])
+            .build(layer)
+            .unwrap();
+        let main = all
+            .toc
+            .metadata
+            .iter()
+            .find(|entry| entry.name == Path::new("src/main.rs"))
+            .unwrap();
+        assert_eq!(ztoc.toc.metadata.len(), 1);
+        assert_eq!(
+            ztoc.toc.metadata[0].uncompressed_offset.0,
+            main.uncompressed_offset.0
+        );
+
+        let tar = testutil::tar_of(&[
+            ("usr/lib/libz.so", b"z"),
+            ("usr/library/notes", b"n"),
+            ("etc/hosts", b"h"),
+        ]);
+        let ztoc = ZToc::builder()
+            .path_prefixes(&["usr/lib", "etc"])
+            .build(&testutil::gzip(&tar)[..])
+            .unwrap();
+        let names: Vec<_> = ztoc.toc.metadata.iter().map(|entry| &entry.name).collect();
+        assert_eq!(
+            names,
+            [Path::new("usr/lib/libz.so"), Path::new("etc/hosts")]
+        );
+    }
+
+    #[test]
+    fn test_no_span_digests() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let with = ZToc::new(layer).unwrap();
+        let without = ZToc::builder()
+            .extra_digest_algorithms(&[DigestAlgorithm::Sha512])
+            .span_digests(false)
+            .build(layer)
+            .unwrap();
+        assert!(!with.compression_info.span_digests.is_empty());
+        assert!(without.compression_info.span_digests.is_empty());
+        assert!(without.compression_info.extra_span_digests.is_empty());
+        without.compression_info.validate_consistency().unwrap();
+
+        // Everything else is unchanged.
+        assert_eq!(
+            with.compression_info.checkpoints,
+            without.compression_info.checkpoints
+        );
+        assert_eq!(
+            with.compression_info.max_span_id,
+            without.compression_info.max_span_id
+        );
+        assert_eq!(format!("{:?}", with.toc), format!("{:?}", without.toc));
+    }
+
+    #[test]
+    fn test_from_tar_and_zinfo() {
+        let compressed = &include_bytes!("testdata/test.tar.gz")[..];
+        let tar = &include_bytes!("testdata/test.tar")[..];
+        let zinfo = || build_zinfo(compressed, DEFAULT_SPAN_SIZE).unwrap();
+
+        let ztoc = ZToc::from_tar_and_zinfo(tar, zinfo()).unwrap();
+        let built = ZToc::new(compressed).unwrap();
+        assert_eq!(format!("{:?}", ztoc.toc), format!("{:?}", built.toc));
+        assert_eq!(
+            ztoc.compression_info.checkpoints,
+            built.compression_info.checkpoints
+        );
+        assert_eq!(
+            ztoc.compression_info.span_digests,
+            built.compression_info.span_digests
+        );
+        assert_eq!(ztoc.uncompressed_archive_size.0, tar.len() as u64);
+
+        let err = ZToc::from_tar_and_zinfo(&tar[..tar.len() - 512], zinfo()).unwrap_err();
+        assert!(matches!(err, ZtocError::InvalidZtoc(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_empty_archive() {
+        let layer = testutil::gzip(&[]);
+        let ztoc = ZToc::new(&layer[..]).unwrap();
+        assert!(ztoc.toc.metadata.is_empty());
+        assert_eq!(ztoc.compression_info.max_span_id, 0);
+        ztoc.compression_info.validate_consistency().unwrap();
+        crate::encode::encode_ztoc(&ztoc).unwrap();
+
+        // A zinfo that never recorded a checkpoint has no spans at all.
+        let zinfo = ZInfo::from_bytes(&[0; CHECKPOINTS_HEADER_SIZE]).unwrap();
+        let ztoc = ZToc::from_tar_and_zinfo(&[][..], zinfo).unwrap();
+        assert_eq!(ztoc.compression_info.max_span_id, 0);
+        ztoc.compression_info.validate_consistency().unwrap();
+        let encoded = crate::encode::encode_ztoc(&ztoc).unwrap();
+        let decoded = crate::ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        assert_eq!(decoded.compression_info().unwrap().max_span_id(), 0);
+    }
+
+    #[test]
+    fn test_read_file() {
+        let compressed = &include_bytes!("testdata/test.tar.gz")[..];
+        let ztoc = ZToc::new(compressed).unwrap();
+        let mut archive = tar::Archive::new(&include_bytes!("testdata/test.tar")[..]);
+        let mut want = Vec::new();
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .find(|entry| entry.path().unwrap() == Path::new("src/main.rs"))
+            .unwrap()
+            .read_to_end(&mut want)
+            .unwrap();
+        let data = ztoc
+            .read_file("src/main.rs", Cursor::new(compressed))
+            .unwrap();
+        assert_eq!(data, want);
+        assert!(ztoc
+            .read_file("src/", Cursor::new(compressed))
+            .unwrap()
+            .is_empty());
+        let err = ztoc
+            .read_file("missing", Cursor::new(compressed))
+            .unwrap_err();
+        assert!(matches!(err, ZtocError::NotFound(_)), "{}", err);
+
+        // Files in later spans and hardlinks to them.
+        let big = testutil::random_bytes(3 << 16, 5);
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Regular, big.len() as u64);
+        builder.append_data(&mut header, "big", &big[..]).unwrap();
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "small", &b"hello"[..])
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Link, 0);
+        builder.append_link(&mut header, "link", "small").unwrap();
+        let mut header = testutil::header(tar::EntryType::Link, 0);
+        builder.append_link(&mut header, "link2", "link").unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+        let ztoc = ZToc::with_span_size(&layer[..], 1 << 16).unwrap();
+        assert!(ztoc.compression_info.max_span_id > 1);
+        let read = |name| ztoc.read_file(name, Cursor::new(&layer)).unwrap();
+        assert_eq!(read("big"), big);
+        assert_eq!(read("small"), b"hello");
+        assert_eq!(read("link"), b"hello");
+        assert_eq!(read("link2"), b"hello");
+    }
+
+    #[test]
+    fn test_span_timing() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        assert!(ZToc::new(layer).unwrap().metrics.span_durations.is_empty());
+
+        let ztoc = ZToc::builder().span_timing(true).build(layer).unwrap();
+        assert_eq!(
+            ztoc.metrics.span_durations.len(),
+            ztoc.compression_info.max_span_id + 1
+        );
+    }
+
+    #[test]
+    fn test_lossy_names() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        let uname = b"\xef\xbb\xbfbob\xff";
+        header.as_gnu_mut().unwrap().uname[..uname.len()].copy_from_slice(uname);
+        header.set_groupname("staff").unwrap();
+        builder
+            .append_data(&mut header, "odd-owner", &b"hello"[..])
+            .unwrap();
+        builder
+            .append_data(
+                &mut testutil::header(tar::EntryType::Regular, 5),
+                "plain",
+                &b"hello"[..],
+            )
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let err = ZToc::new(&layer[..]).unwrap_err();
+        assert!(
+            matches!(&err, ZtocError::NonUtf8 { path, field: "uname" } if path.as_os_str() == "odd-owner"),
+            "{}",
+            err
+        );
+
+        let ztoc = ZToc::builder().lossy_names(true).build(&layer[..]).unwrap();
+        let entry = &ztoc.toc.metadata[0];
+        assert_eq!(entry.uname.as_deref(), Some("\u{feff}bob\u{fffd}"));
+        assert_eq!(entry.gname.as_deref(), Some("staff"));
+        assert_eq!(ztoc.metrics.lossy_names, vec![PathBuf::from("odd-owner")]);
+    }
+
+    #[test]
+    fn test_structured_errors() {
+        let mut header = testutil::header(tar::EntryType::Regular, 0);
+        header.set_mtime(1 << 62);
+        header.set_cksum();
+        let mut builder = tar::Builder::new(Vec::new());
+        builder
+            .append_data(&mut header, "future", io::empty())
+            .unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+        let err = ZToc::new(&layer[..]).unwrap_err();
+        assert!(
+            matches!(&err, ZtocError::InvalidMtime { mtime, .. } if *mtime == 1 << 62),
+            "{}",
+            err
+        );
+
+        // A corrupt CRC in the gzip trailer is reported by zlib as a data error.
+        let mut layer = testutil::gzip(&testutil::tar_of(&[("file", b"data")]));
+        let crc = layer.len() - 8;
+        layer[crc] ^= 0xff;
+        let err = ZToc::new(&layer[..]).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ZtocError::Zlib {
+                    code: libz_sys::Z_DATA_ERROR,
+                    ..
+                }
+            ),
+            "{}",
+            err
+        );
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_check_isize() {
+        let layer = &include_bytes!("testdata/layer.tar.gz")[..];
+        let isize = u32::from_le_bytes(layer[layer.len() - 4..].try_into().unwrap());
+        let ztoc = ZToc::builder().check_isize(true).build(layer).unwrap();
+        assert_eq!(ztoc.uncompressed_archive_size.0 as u32, isize);
+
+        let size = (1 << 32) + 5;
+        assert!(check_isize(size, Some(5)).is_ok());
+        assert!(matches!(
+            check_isize(size, Some(4)),
+            Err(ZtocError::SizeMismatch {
+                total_out,
+                isize: Some(4)
+            }) if total_out == size as u64
+        ));
+        assert!(check_isize(size, None).is_err());
+
+        // A zlib-wrapped layer, auto-detected, has no ISIZE to check.
+        let tar = testutil::tar_of(&[("file", b"contents")]);
+        let layer = testutil::deflate_with(&tar, 15);
+        let ztoc = ZToc::builder().check_isize(true).build(&layer[..]).unwrap();
+        assert_eq!(ztoc.uncompressed_archive_size.0, tar.len() as u64);
+    }
+
+    #[test]
+    fn test_compressed_span_offsets() {
+        let layer = &include_bytes!("testdata/layer.tar.gz")[..];
+        assert!(ZToc::new(layer)
+            .unwrap()
+            .toc
+            .metadata
+            .iter()
+            .all(|entry| entry.compressed_span_offset.is_none()));
+
+        let ztoc = ZToc::builder()
+            .compressed_span_offsets(true)
+            .build(layer)
+            .unwrap();
+        let checkpoints = parse_checkpoints(&ztoc.compression_info.checkpoints).unwrap();
+        let mut offsets = Vec::new();
+        for entry in &ztoc.toc.metadata {
+            let index =
+                checkpoint_for_offset(&checkpoints, entry.uncompressed_offset.0 as usize).unwrap();
+            assert_eq!(
+                entry.compressed_span_offset,
+                Some(checkpoints[index].r#in as u64)
+            );
+            offsets.push(checkpoints[index].r#in as i64);
+        }
+        assert!(offsets.iter().any(|&offset| offset > offsets[0]));
+
+        let encoded = crate::encode::encode_ztoc(&ztoc).unwrap();
+        let decoded = crate::ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        let decoded = decoded
+            .toc()
+            .unwrap()
+            .metadata()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.compressed_span_offset())
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, offsets);
+    }
+
+    #[test]
+    fn test_skip_unreadable_entries() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (i, path) in ["first", "bad", "last"].into_iter().enumerate() {
+            let mut header = testutil::header(tar::EntryType::Regular, 5);
+            if i == 1 {
+                header.as_gnu_mut().unwrap().uname[..2].copy_from_slice(b"\xff\xfe");
+            }
+            builder
+                .append_data(&mut header, path, &b"hello"[..])
+                .unwrap();
+        }
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        assert!(ZToc::new(&layer[..]).is_err());
+
+        let ztoc = ZToc::builder()
+            .skip_unreadable_entries(true)
+            .build(&layer[..])
+            .unwrap();
+        let names = ztoc
+            .toc
+            .metadata
+            .iter()
+            .map(|entry| entry.name.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["first", "last"]);
+        assert_eq!(ztoc.toc.metadata[1].uncompressed_offset.0, 512 * 5);
+        let skipped = &ztoc.metrics.skipped_entries;
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].index, 1);
+        assert_eq!(
+            skipped[0].error.to_string(),
+            "uname of bad is not valid UTF-8"
+        );
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let files = [("a", &b"1"[..]), ("b", &b"2"[..]), ("c", &b"3"[..])];
+        let layer = testutil::gzip(&testutil::tar_of(&files));
+        let ztoc = ZToc::builder().max_entries(3).build(&layer[..]).unwrap();
+        assert_eq!(ztoc.toc.metadata.len(), 3);
+
+        let err = ZToc::builder()
+            .max_entries(2)
+            .build(&layer[..])
+            .unwrap_err();
+        assert!(
+            matches!(err, ZtocError::TooManyEntries { limit: 2 }),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_with_span_size() {
+        let layer = &include_bytes!("testdata/layer.tar.gz")[..];
+        let spans = |ztoc: ZToc| parse_checkpoints(&ztoc.compression_info.checkpoints).unwrap();
+        let default = spans(ZToc::new(layer).unwrap());
+        let small = spans(ZToc::with_span_size(layer, 1024).unwrap());
+        assert!(small.len() > default.len());
+
+        let err = ZToc::with_span_size(layer, 0).unwrap_err();
+        assert!(
+            matches!(&err, ZtocError::Io(err) if err.kind() == io::ErrorKind::InvalidInput),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_last_checkpoint_near_end() {
+        // Full flushes every `interval` bytes put a block boundary at least that often.
+        let interval = 1 << 12;
+        let tar = testutil::tar_of(&[("text", &testutil::text_like(1 << 20))]);
+        let layer = testutil::gzip_rsyncable(&tar, interval);
+        let span_size = 1 << 16;
+        let ztoc = ZToc::with_span_size(&layer[..], span_size).unwrap();
+        let checkpoints = parse_checkpoints(&ztoc.compression_info.checkpoints).unwrap();
+        assert!(checkpoints.len() > 2);
+
+        // The end of the last block is the end of the stream, where a checkpoint would cover
+        // nothing. The last block starts at most a span past the last checkpoint.
+        let last = checkpoints.last().unwrap();
+        let total_out = ztoc.uncompressed_archive_size.0 as usize;
+        assert!(total_out - last.out <= span_size + interval);
+    }
+
+    #[test]
+    fn test_builder_default() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let built = ZtocBuilder::default().build(layer).unwrap();
+        let ztoc = ZToc::new(layer).unwrap();
+        assert_eq!(format!("{:?}", built), format!("{:?}", ztoc));
+        assert_eq!(ztoc.version, "0.9");
+        assert_eq!(
+            ztoc.build_tool_identifier,
+            format!("Replit SOCI v{}", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_resolve_hardlinks() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Directory, 0);
+        builder.append_data(&mut header, "dir", &[][..]).unwrap();
+        let mut header = testutil::header(tar::EntryType::Regular, 5);
+        builder
+            .append_data(&mut header, "dir/file", &b"hello"[..])
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Link, 0);
+        builder
+            .append_link(&mut header, "link", "dir/file")
+            .unwrap();
+        let mut header = testutil::header(tar::EntryType::Link, 0);
+        builder.append_link(&mut header, "link2", "link").unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+
+        let unresolved = ZToc::new(&layer[..]).unwrap();
+        assert_eq!(unresolved.toc.metadata[2].uncompressed_size.0, 0);
+        let ztoc = ZToc::builder()
+            .resolve_hardlinks(true)
+            .build(&layer[..])
+            .unwrap();
+        let [_, file, link, link2] = &ztoc.toc.metadata[..] else {
+            panic!("expected four entries, got {:?}", ztoc.toc.metadata);
+        };
+        for link in [link, link2] {
+            assert_eq!(link.r#type, tar::EntryType::Link);
+            assert_eq!(link.uncompressed_offset.0, file.uncompressed_offset.0);
+            assert_eq!(link.uncompressed_size.0, 5);
+        }
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = testutil::header(tar::EntryType::Link, 0);
+        builder.append_link(&mut header, "link", "missing").unwrap();
+        let layer = testutil::gzip(&builder.into_inner().unwrap());
+        let err = ZToc::builder()
+            .resolve_hardlinks(true)
+            .build(&layer[..])
+            .unwrap_err();
+        assert!(matches!(err, ZtocError::InvalidPath { .. }), "{}", err);
+        assert!(err.to_string().contains("missing"), "{}", err);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_from_path() {
+        let layer = include_bytes!("testdata/test.tar.gz");
+        let dir = std::env::temp_dir().join(format!("ztoc-from-path-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("layer.tar.gz");
+        std::fs::write(&path, layer).unwrap();
+        let empty = dir.join("empty");
+        std::fs::write(&empty, b"").unwrap();
+
+        let mapped = ZToc::from_path(&path, 1 << 12);
+        let empty_err = ZToc::from_path(&empty, 1 << 12).unwrap_err();
+        let missing_err = ZToc::from_path(dir.join("missing"), 1 << 12).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let read = ZToc::with_span_size(&layer[..], 1 << 12).unwrap();
+        assert_eq!(format!("{:?}", mapped.unwrap()), format!("{:?}", read));
+        let read_empty_err = ZToc::with_span_size(&[][..], 1 << 12).unwrap_err();
+        assert_eq!(empty_err.to_string(), read_empty_err.to_string());
+        assert!(matches!(missing_err, ZtocError::Io(_)), "{}", missing_err);
+    }
+
+    #[test]
+    fn test_version_and_build_tool_identifier() {
+        let layer = &include_bytes!("testdata/test.tar.gz")[..];
+        let ztoc = ZToc::builder()
+            .version("1.0")
+            .build_tool_identifier("acme-indexer 2.3.1")
+            .build(layer)
+            .unwrap();
+        assert_eq!(ztoc.version, "1.0");
+
+        let encoded = crate::encode::encode_ztoc(&ztoc).unwrap();
+        let decoded = crate::ztoc_flatbuffers::ztoc::root_as_ztoc(&encoded).unwrap();
+        assert_eq!(decoded.version(), Some("1.0"));
+        assert_eq!(decoded.build_tool_identifier(), Some("acme-indexer 2.3.1"));
+    }
+
+    #[test]
+    fn test_validate_consistency() {
+        let reader = Cursor::new(include_bytes!("testdata/test.tar.gz"));
+        let ztoc = ZToc::builder()
+            .extra_digest_algorithms(&[DigestAlgorithm::Sha512])
+            .build(reader)
+            .unwrap();
+        let mut info = ztoc.compression_info;
+        info.validate_consistency().unwrap();
+
+        info.max_span_id += 1;
+        let err = info.validate_consistency().unwrap_err();
+        assert!(err.to_string().contains("max_span_id"), "{}", err);
+        info.max_span_id -= 1;
+
+        info.extra_span_digests
+            .get_mut(&DigestAlgorithm::Sha512)
+            .unwrap()
+            .pop();
+        let err = info.validate_consistency().unwrap_err();
+        assert!(err.to_string().contains("sha512"), "{}", err);
+    }
+
+    #[test]
+    fn test_truncated_entry_data() {
+        let contents = testutil::random_bytes(10_000, 1);
+        let tar = testutil::tar_of(&[("small", b"hello"), ("big", &contents)]);
+        // Cut the archive in the middle of the big file's data.
+        let truncated = &tar[..512 * 3 + 4000];
+
+        let err = generate_tar_metadata(&mut Cursor::new(truncated)).unwrap_err();
+        match err {
+            ZtocError::TruncatedEntry {
+                path,
+                expected,
+                available,
+            } => {
+                assert_eq!(path, PathBuf::from("big"));
+                assert_eq!(expected, 10_000);
+                assert_eq!(available, 4000);
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+
+        let gzip = testutil::gzip(&tar);
+        let err = ZToc::new(Cursor::new(&gzip[..gzip.len() / 2])).unwrap_err();
+        assert!(
+            matches!(&err, ZtocError::TruncatedEntry { path, expected: 10_000, .. } if path == &PathBuf::from("big")),
+            "unexpected error: {}",
+            err
+        );
+
+        // A sparse file expanding to 8 KiB, of which only one 1 KiB segment is stored.
+        let mut header = testutil::header(tar::EntryType::GNUSparse, 1024);
+        let gnu = header.as_gnu_mut().unwrap();
+        let octal = |field: &mut [u8; 12], value: u64| {
+            field.copy_from_slice(format!("{:011o}\0", value).as_bytes())
+        };
+        octal(&mut gnu.sparse[0].offset, 0);
+        octal(&mut gnu.sparse[0].numbytes, 1024);
+        octal(&mut gnu.sparse[1].offset, 8192);
+        octal(&mut gnu.sparse[1].numbytes, 0);
+        octal(&mut gnu.realsize, 8192);
+        let mut builder = tar::Builder::new(Vec::new());
+        builder
+            .append_data(&mut header, "sparse", &[7u8; 1024][..])
+            .unwrap();
+        let mut sparse_tar = builder.into_inner().unwrap();
+        // The stored size is what is outstanding when the archive is cut short.
+        let err = generate_tar_metadata(&mut Cursor::new(&sparse_tar[..512 + 600])).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ZtocError::TruncatedEntry {
+                    expected: 1024,
+                    available: 600,
+                    ..
+                }
+            ),
+            "unexpected error: {}",
+            err
+        );
+        // Once the stored segments are complete, a malformed header after them is not a
+        // truncation, even though the expanded size reaches past it.
+        sparse_tar.truncate(512 + 1024);
+        sparse_tar.extend_from_slice(&[0xff; 512]);
+        let err = generate_tar_metadata(&mut Cursor::new(&sparse_tar[..])).unwrap_err();
+        assert!(matches!(err, ZtocError::Io(_)), "unexpected error: {}", err);
+    }
 }